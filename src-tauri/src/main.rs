@@ -135,6 +135,12 @@ fn get_process_path(pid: u32) -> Result<String, io::Error> {
 }
 
 fn main() {
+    // Режим демона постоянных терминальных сессий запускается без окна
+    if std::env::args().any(|arg| arg == utils::session_daemon::DAEMON_FLAG) {
+        utils::session_daemon::run_daemon();
+        return;
+    }
+
     // Создаем кэш для системной информации
     let system_info_cache = create_system_info_cache();
 
@@ -154,6 +160,9 @@ fn main() {
             start_system_info_thread(app.app_handle().clone(), app.state::<Arc<utils::system_info::SystemInfoCache>>().inner().clone());
             println!("[SystemInfo] Запущен фоновый поток обновления системной информации");
             
+            // Запускаем фоновую задачу статистики терминалов (события pty-stats)
+            utils::terminal_stats::start_terminal_stats_task(app.app_handle().clone());
+            
             // Инициализация базы данных
             let app_handle = app.app_handle();
             let db_state = DbState::new(&app_handle)
@@ -161,6 +170,24 @@ fn main() {
             app.manage(db_state);
             println!("[Main] База данных успешно инициализирована");
             
+            // Загружаем сохраненные настройки защиты ввода до открытия первого терминала
+            if let Err(e) = tauri::async_runtime::block_on(utils::terminal::load_input_guard_config(
+                &app.state::<PtyState>(),
+                &app.state::<DbState>(),
+            )) {
+                eprintln!("[Main] Не удалось загрузить настройки защиты ввода: {}", e);
+            }
+            
+            // Запускаем фоновую задачу политики хранения истории команд
+            utils::terminal_history::start_history_retention_task(app.app_handle().clone());
+            
+            // Запускаем планировщик скриптов
+            utils::script_scheduler::start_script_scheduler(
+                app.app_handle().clone(),
+                app.state::<DbState>().inner().clone(),
+                app.state::<utils::script_runner::ScriptRunState>().inner().clone(),
+            );
+            
             #[cfg(debug_assertions)]
             {
                 let window = app.get_webview_window("main").unwrap();
@@ -172,6 +199,7 @@ fn main() {
         })
         .manage(ports::create_ports_cache())
         .manage(PtyState::new())
+        .manage(utils::script_runner::ScriptRunState::new())
         .manage(system_info_cache)
         .invoke_handler(tauri::generate_handler![
             // Базовая функция
@@ -185,6 +213,40 @@ fn main() {
             utils::terminal::clear_terminal,
             utils::terminal::close_terminal_process,
             utils::terminal::get_active_terminals,
+            utils::terminal::bind_terminal_tab,
+            utils::terminal::start_serial_session,
+            utils::terminal::start_ssh_session,
+            utils::terminal::list_serial_ports,
+            utils::terminal::get_terminal_sessions,
+            utils::terminal::send_input_many,
+            utils::terminal::send_input_group,
+            utils::terminal::create_terminal_group,
+            utils::terminal::delete_terminal_group,
+            utils::terminal::add_terminal_to_group,
+            utils::terminal::remove_terminal_from_group,
+            utils::terminal::set_group_member_excluded,
+            utils::terminal::get_terminal_groups,
+            utils::terminal::add_output_watch,
+            utils::terminal::remove_output_watch,
+            utils::terminal::list_output_watches,
+            utils::terminal::set_terminal_encoding,
+            utils::terminal::set_terminal_output_mode,
+            utils::terminal::list_persistent_sessions,
+            utils::terminal::reconnect_persistent_sessions,
+            utils::terminal_layout::save_terminal_layout,
+            utils::terminal_layout::get_terminal_layout,
+            utils::terminal_layout::delete_terminal_layout,
+            utils::terminal_layout::restore_terminal_layout,
+            utils::terminal_search::search_terminal_output,
+            utils::terminal_history::get_history_retention,
+            utils::terminal_history::set_history_retention,
+            utils::terminal_history::apply_history_retention,
+            utils::terminal_history::get_command_analytics,
+            utils::terminal_history::query_command_history,
+            utils::terminal_stats::get_terminal_stats,
+            utils::terminal_stats::set_terminal_stats_active,
+            utils::terminal::get_input_guard_config,
+            utils::terminal::set_input_guard_config,
             
             // База данных терминала
             utils::db::save_terminal_tab,
@@ -194,6 +256,8 @@ fn main() {
             utils::db::get_terminal_commands,
             utils::db::delete_terminal_command,
             utils::db::clear_terminal_history,
+            utils::app_data::export_app_data,
+            utils::app_data::import_app_data,
             
             // Системная информация
             utils::system_info::get_system_info,
@@ -203,6 +267,32 @@ fn main() {
             
             // Запуск скриптов
             utils::script_runner::run_script,
+            utils::script_runner::start_script,
+            utils::script_runner::cancel_script,
+            utils::script_runner::send_script_input,
+            utils::script_runner::list_script_runs,
+            utils::script_interpreters::list_interpreters,
+            utils::script_interpreters::save_interpreter,
+            utils::script_interpreters::delete_interpreter,
+            utils::script_library::list_library_scripts,
+            utils::script_library::get_library_script,
+            utils::script_library::create_library_script,
+            utils::script_library::update_library_script,
+            utils::script_library::delete_library_script,
+            utils::script_library::list_library_script_versions,
+            utils::script_library::restore_library_script_version,
+            utils::script_library::search_library_scripts,
+            utils::script_history::get_script_run_history,
+            utils::script_history::get_script_run_record,
+            utils::script_history::diff_script_runs,
+            utils::script_history::clear_script_run_history,
+            utils::script_scheduler::list_script_schedules,
+            utils::script_scheduler::save_script_schedule,
+            utils::script_scheduler::delete_script_schedule,
+            utils::script_scheduler::preview_cron_expression,
+            utils::script_params::get_script_parameters,
+            utils::script_params::validate_script_parameters,
+            utils::script_lint::lint_script,
             utils::script_runner::save_script,
             utils::script_runner::save_script_by_language,
             utils::script_runner::save_script_with_custom_path,
@@ -0,0 +1,474 @@
+// Модуль резервного копирования, экспорта и импорта данных приложения
+// Архив - zip с manifest.json и копией terminals.db, снятой через SQLite online backup
+// (копия согласована, даже пока приложение пишет в базу). Импорт проверяет формат архива
+// и версию схемы, обновляет копию миграциями и переносит таблицы в текущую базу
+// с заменой или слиянием данных. При слиянии строки из архива получают новые id,
+// а ссылки на них в зависимых таблицах переписываются через временную таблицу соответствия
+
+use rusqlite::{params, Connection, DatabaseName, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+use tauri::State;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::utils::db::DbState;
+use crate::utils::db_migrations::{check_supported_version, current_version, run_migrations};
+use crate::utils::terminal::{load_input_guard_config, PtyState};
+
+// Идентификатор и версия формата архива
+const ARCHIVE_FORMAT: &str = "x-avto-app-data";
+const ARCHIVE_FORMAT_VERSION: u32 = 1;
+
+const MANIFEST_ENTRY: &str = "manifest.json";
+const DATABASE_ENTRY: &str = "terminals.db";
+
+// Псевдоним базы из архива при импорте
+const ARCHIVE_SCHEMA: &str = "archive";
+
+// Временная таблица соответствия id строк архива и id в текущей базе
+const ID_MAP_TABLE: &str = "temp.import_id_map";
+
+// Как строки из архива объединяются с уже существующими при слиянии
+enum MergeStrategy {
+    // Строки с уже существующим первичным ключом пропускаются
+    KeepExisting,
+    // Строки получают новый id; строка, совпадающая с существующей по одному из наборов колонок
+    // (проверяются по порядку), не добавляется, а ссылки на нее указывают на существующую
+    AppendUnique(&'static [&'static [&'static str]]),
+}
+
+// Колонка со ссылкой на id строки другой таблицы: при слиянии переписывается на id в текущей базе
+struct Reference {
+    column: &'static str,
+    table: &'static str,
+    // Строка со ссылкой на непереносимую строку пропускается (иначе ссылка становится NULL)
+    required: bool,
+}
+
+struct DataTable {
+    name: &'static str,
+    merge: MergeStrategy,
+    references: &'static [Reference],
+}
+
+// Таблицы с данными пользователя в порядке вставки (родительские раньше зависимых)
+// Индексы и служебные таблицы (schema_version, FTS) не переносятся: они поддерживаются базой
+const DATA_TABLES: &[DataTable] = &[
+    // id вкладки из другой установки может совпадать с id другой локальной вкладки,
+    // поэтому той же вкладкой считается строка с тем же id и именем
+    // или копия, добавленная прошлым импортом (то же имя и время использования)
+    DataTable {
+        name: "terminal_tabs",
+        merge: MergeStrategy::AppendUnique(&[&["id", "name"], &["name", "last_used"]]),
+        references: &[],
+    },
+    DataTable {
+        name: "terminal_layouts",
+        merge: MergeStrategy::KeepExisting,
+        references: &[Reference { column: "tab_id", table: "terminal_tabs", required: true }],
+    },
+    DataTable {
+        name: "terminal_commands",
+        merge: MergeStrategy::AppendUnique(&[&["terminal_tab_id", "command", "time"]]),
+        references: &[Reference { column: "terminal_tab_id", table: "terminal_tabs", required: true }],
+    },
+    DataTable { name: "app_settings", merge: MergeStrategy::KeepExisting, references: &[] },
+    // id скриптов - UUID, поэтому при слиянии скрипты из разных установок не пересекаются
+    DataTable { name: "scripts", merge: MergeStrategy::KeepExisting, references: &[] },
+    DataTable { name: "script_versions", merge: MergeStrategy::KeepExisting, references: &[] },
+    DataTable {
+        name: "script_runs",
+        merge: MergeStrategy::AppendUnique(&[&["script_hash", "interpreter", "started_at"]]),
+        references: &[],
+    },
+    DataTable {
+        name: "script_schedules",
+        merge: MergeStrategy::KeepExisting,
+        references: &[Reference { column: "last_history_id", table: "script_runs", required: false }],
+    },
+];
+
+// Описание архива
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppDataManifest {
+    pub format: String,
+    pub format_version: u32,
+    pub app_version: String,
+    pub schema_version: i64,
+    pub created_at: String,
+    // Число строк в каждой таблице на момент экспорта
+    pub tables: BTreeMap<String, i64>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    // Данные архива добавляются к существующим
+    Merge,
+    // Существующие данные удаляются и заменяются данными архива
+    Replace,
+}
+
+// Результат импорта: описание архива и число перенесенных строк по таблицам
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportSummary {
+    pub manifest: AppDataManifest,
+    pub mode: ImportMode,
+    pub tables: BTreeMap<String, usize>,
+}
+
+fn table_counts(conn: &Connection) -> Result<BTreeMap<String, i64>, String> {
+    DATA_TABLES
+        .iter()
+        .map(|table| {
+            conn.query_row(&format!("SELECT COUNT(*) FROM {}", table.name), [], |row| row.get(0))
+                .map(|count| (table.name.to_string(), count))
+                .map_err(|e| format!("Ошибка подсчета строк {}: {}", table.name, e))
+        })
+        .collect()
+}
+
+// Колонки таблицы текущей базы
+fn table_columns(conn: &Connection, table: &str) -> Result<Vec<String>, String> {
+    let mut stmt = conn.prepare(&format!("PRAGMA main.table_info({})", table))
+        .map_err(|e| format!("Ошибка подготовки запроса: {}", e))?;
+
+    let columns = stmt.query_map([], |row| row.get::<_, String>(1))
+        .map_err(|e| format!("Ошибка выполнения запроса: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Ошибка чтения строки: {}", e))?;
+
+    Ok(columns)
+}
+
+fn export_blocking(conn: &Connection, path: &Path) -> Result<AppDataManifest, String> {
+    let temp_dir = tempfile::tempdir()
+        .map_err(|e| format!("Ошибка при создании временной директории: {}", e))?;
+    let snapshot_path = temp_dir.path().join(DATABASE_ENTRY);
+
+    conn.backup(DatabaseName::Main, &snapshot_path, None)
+        .map_err(|e| format!("Не удалось создать копию БД: {}", e))?;
+
+    // Копия должна быть одним файлом, без журнала WAL рядом
+    let manifest = {
+        let snapshot = Connection::open(&snapshot_path)
+            .map_err(|e| format!("Не удалось открыть копию БД: {}", e))?;
+        snapshot.pragma_update_and_check(None, "journal_mode", "DELETE", |_| Ok(()))
+            .map_err(|e| format!("Не удалось переключить журнал копии БД: {}", e))?;
+
+        AppDataManifest {
+            format: ARCHIVE_FORMAT.to_string(),
+            format_version: ARCHIVE_FORMAT_VERSION,
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            schema_version: current_version(&snapshot)?,
+            created_at: chrono::Local::now().to_rfc3339(),
+            tables: table_counts(&snapshot)?,
+        }
+    };
+
+    let manifest_json = serde_json::to_string_pretty(&manifest)
+        .map_err(|e| format!("Ошибка сериализации описания архива: {}", e))?;
+
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() && !parent.exists() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Ошибка при создании директории: {}", e))?;
+        }
+    }
+
+    let file = fs::File::create(path)
+        .map_err(|e| format!("Не удалось создать файл архива {}: {}", path.display(), e))?;
+    let mut archive = ZipWriter::new(file);
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    archive.start_file(MANIFEST_ENTRY, options)
+        .and_then(|_| archive.write_all(manifest_json.as_bytes()).map_err(Into::into))
+        .map_err(|e| format!("Ошибка записи описания архива: {}", e))?;
+
+    let mut snapshot = fs::File::open(&snapshot_path)
+        .map_err(|e| format!("Не удалось открыть копию БД: {}", e))?;
+    archive.start_file(DATABASE_ENTRY, options)
+        .map_err(|e| format!("Ошибка записи БД в архив: {}", e))?;
+    std::io::copy(&mut snapshot, &mut archive)
+        .map_err(|e| format!("Ошибка записи БД в архив: {}", e))?;
+
+    archive.finish()
+        .map_err(|e| format!("Ошибка завершения архива: {}", e))?;
+
+    Ok(manifest)
+}
+
+// Чтение и проверка описания архива
+fn read_manifest<R: Read + std::io::Seek>(archive: &mut ZipArchive<R>) -> Result<AppDataManifest, String> {
+    let mut manifest_json = String::new();
+    archive.by_name(MANIFEST_ENTRY)
+        .map_err(|e| format!("В архиве нет {}: {}", MANIFEST_ENTRY, e))?
+        .read_to_string(&mut manifest_json)
+        .map_err(|e| format!("Ошибка чтения {}: {}", MANIFEST_ENTRY, e))?;
+
+    let manifest: AppDataManifest = serde_json::from_str(&manifest_json)
+        .map_err(|e| format!("Некорректное описание архива: {}", e))?;
+
+    if manifest.format != ARCHIVE_FORMAT {
+        return Err(format!("Файл не является архивом данных X-Avto (формат {:?})", manifest.format));
+    }
+    if manifest.format_version > ARCHIVE_FORMAT_VERSION {
+        return Err(format!(
+            "Архив создан более новой версией приложения (формат {}, поддерживается до {})",
+            manifest.format_version, ARCHIVE_FORMAT_VERSION
+        ));
+    }
+    check_supported_version(manifest.schema_version)?;
+
+    Ok(manifest)
+}
+
+// Распаковка и подготовка базы из архива: проверка версии и целостности, обновление схемы
+fn prepare_archive_database(path: &Path, target: &Path) -> Result<AppDataManifest, String> {
+    let file = fs::File::open(path)
+        .map_err(|e| format!("Не удалось открыть архив {}: {}", path.display(), e))?;
+    let mut archive = ZipArchive::new(file)
+        .map_err(|e| format!("Некорректный архив {}: {}", path.display(), e))?;
+
+    let manifest = read_manifest(&mut archive)?;
+
+    {
+        let mut entry = archive.by_name(DATABASE_ENTRY)
+            .map_err(|e| format!("В архиве нет {}: {}", DATABASE_ENTRY, e))?;
+        let mut database = fs::File::create(target)
+            .map_err(|e| format!("Ошибка при создании временного файла: {}", e))?;
+        std::io::copy(&mut entry, &mut database)
+            .map_err(|e| format!("Ошибка распаковки БД из архива: {}", e))?;
+    }
+
+    let mut conn = Connection::open(target)
+        .map_err(|e| format!("Не удалось открыть БД из архива: {}", e))?;
+
+    let version = current_version(&conn)?;
+    if version != manifest.schema_version {
+        return Err(format!(
+            "Архив поврежден: версия схемы БД {} не совпадает с описанием ({})",
+            version, manifest.schema_version
+        ));
+    }
+
+    let integrity: String = conn.query_row("PRAGMA integrity_check", [], |row| row.get(0))
+        .map_err(|e| format!("Ошибка проверки целостности БД из архива: {}", e))?;
+    if integrity != "ok" {
+        return Err(format!("БД в архиве повреждена: {}", integrity));
+    }
+
+    run_migrations(&mut conn, target)?;
+    Ok(manifest)
+}
+
+fn mapped_id(reference: &Reference) -> String {
+    format!(
+        "(SELECT new_id FROM {map} WHERE table_name = '{table}' AND old_id = src.{column})",
+        map = ID_MAP_TABLE, table = reference.table, column = reference.column
+    )
+}
+
+// Значение колонки строки архива (src) для вставки в текущую базу
+fn source_value(table: &DataTable, column: &str) -> String {
+    match table.references.iter().find(|reference| reference.column == column) {
+        Some(reference) => mapped_id(reference),
+        None => format!("src.{}", column),
+    }
+}
+
+// Условие отбора строк архива: обязательные ссылки должны указывать на перенесенные строки
+fn source_filter(table: &DataTable) -> String {
+    table.references
+        .iter()
+        .filter(|reference| reference.required)
+        .map(|reference| format!(" AND {} IS NOT NULL", mapped_id(reference)))
+        .collect()
+}
+
+// Слияние таблицы с новыми id: строки переносятся по одной, чтобы запомнить их новые id
+fn merge_append_unique(tx: &Connection, table: &DataTable, key_sets: &[&[&str]], columns: &[String]) -> Result<usize, String> {
+    let columns: Vec<&String> = columns.iter().filter(|column| column.as_str() != "id").collect();
+    let target = columns.iter().map(|column| column.as_str()).collect::<Vec<_>>().join(", ");
+    let source = columns.iter().map(|column| source_value(table, column)).collect::<Vec<_>>().join(", ");
+
+    let mut ids = tx.prepare(&format!("SELECT id FROM {}.{} ORDER BY rowid", ARCHIVE_SCHEMA, table.name))
+        .map_err(|e| format!("Ошибка подготовки запроса: {}", e))?;
+    let ids = ids.query_map([], |row| row.get::<_, i64>(0))
+        .map_err(|e| format!("Ошибка выполнения запроса: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Ошибка чтения строки: {}", e))?;
+
+    let mut existing = key_sets
+        .iter()
+        .map(|keys| {
+            let matches = keys
+                .iter()
+                .map(|key| format!("dst.{} IS {}", key, source_value(table, key)))
+                .collect::<Vec<_>>()
+                .join(" AND ");
+            tx.prepare(&format!(
+                "SELECT dst.id FROM {schema}.{table} AS src, main.{table} AS dst WHERE src.id = ? AND {matches} LIMIT 1",
+                schema = ARCHIVE_SCHEMA, table = table.name, matches = matches
+            )).map_err(|e| format!("Ошибка подготовки запроса: {}", e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut insert = tx.prepare(&format!(
+        "INSERT OR IGNORE INTO main.{table} ({target}) SELECT {source} FROM {schema}.{table} AS src WHERE src.id = ?{filter}",
+        table = table.name, target = target, source = source, schema = ARCHIVE_SCHEMA, filter = source_filter(table)
+    )).map_err(|e| format!("Ошибка подготовки запроса: {}", e))?;
+    let mut remember = tx.prepare(&format!(
+        "INSERT OR REPLACE INTO {} (table_name, old_id, new_id) VALUES (?, ?, ?)",
+        ID_MAP_TABLE
+    )).map_err(|e| format!("Ошибка подготовки запроса: {}", e))?;
+
+    let mut inserted = 0;
+    for old_id in ids {
+        let mut existing_id: Option<i64> = None;
+        for statement in existing.iter_mut() {
+            existing_id = statement.query_row(params![old_id], |row| row.get(0))
+                .optional()
+                .map_err(|e| format!("Ошибка выполнения запроса: {}", e))?;
+            if existing_id.is_some() {
+                break;
+            }
+        }
+
+        let new_id = match existing_id {
+            Some(id) => Some(id),
+            None => {
+                let added = insert.execute(params![old_id])
+                    .map_err(|e| format!("Ошибка импорта {}: {}", table.name, e))?;
+                inserted += added;
+                (added > 0).then(|| tx.last_insert_rowid())
+            }
+        };
+
+        if let Some(new_id) = new_id {
+            remember.execute(params![table.name, old_id, new_id])
+                .map_err(|e| format!("Ошибка сохранения соответствия id {}: {}", table.name, e))?;
+        }
+    }
+
+    Ok(inserted)
+}
+
+// Перенос таблиц из подключенной базы архива в текущую
+fn copy_tables(conn: &mut Connection, mode: ImportMode) -> Result<BTreeMap<String, usize>, String> {
+    let tx = conn.transaction()
+        .map_err(|e| format!("Ошибка создания транзакции: {}", e))?;
+
+    match mode {
+        ImportMode::Replace => {
+            for table in DATA_TABLES.iter().rev() {
+                tx.execute(&format!("DELETE FROM main.{}", table.name), [])
+                    .map_err(|e| format!("Ошибка очистки {}: {}", table.name, e))?;
+            }
+        }
+        ImportMode::Merge => {
+            tx.execute_batch(&format!(
+                "CREATE TABLE IF NOT EXISTS {map} (
+                    table_name TEXT NOT NULL,
+                    old_id INTEGER NOT NULL,
+                    new_id INTEGER NOT NULL,
+                    PRIMARY KEY (table_name, old_id)
+                );
+                DELETE FROM {map};",
+                map = ID_MAP_TABLE
+            )).map_err(|e| format!("Ошибка создания таблицы соответствия id: {}", e))?;
+        }
+    }
+
+    let mut imported = BTreeMap::new();
+    for table in DATA_TABLES {
+        let columns = table_columns(&tx, table.name)?;
+
+        let count = match (&table.merge, mode) {
+            (_, ImportMode::Replace) => {
+                let columns = columns.join(", ");
+                tx.execute(&format!(
+                    "INSERT OR IGNORE INTO main.{table} ({columns}) SELECT {columns} FROM {schema}.{table} ORDER BY rowid",
+                    table = table.name, columns = columns, schema = ARCHIVE_SCHEMA
+                ), [])
+                .map_err(|e| format!("Ошибка импорта {}: {}", table.name, e))?
+            }
+            (MergeStrategy::KeepExisting, ImportMode::Merge) => {
+                let target = columns.join(", ");
+                let source = columns.iter().map(|column| source_value(table, column)).collect::<Vec<_>>().join(", ");
+                tx.execute(&format!(
+                    "INSERT OR IGNORE INTO main.{table} ({target}) SELECT {source} FROM {schema}.{table} AS src
+                     WHERE 1{filter} ORDER BY src.rowid",
+                    table = table.name, target = target, source = source, schema = ARCHIVE_SCHEMA, filter = source_filter(table)
+                ), [])
+                .map_err(|e| format!("Ошибка импорта {}: {}", table.name, e))?
+            }
+            (MergeStrategy::AppendUnique(key_sets), ImportMode::Merge) => merge_append_unique(&tx, table, key_sets, &columns)?,
+        };
+
+        imported.insert(table.name.to_string(), count);
+    }
+
+    if let ImportMode::Merge = mode {
+        tx.execute(&format!("DROP TABLE {}", ID_MAP_TABLE), [])
+            .map_err(|e| format!("Ошибка удаления таблицы соответствия id: {}", e))?;
+    }
+
+    tx.commit()
+        .map_err(|e| format!("Ошибка фиксации транзакции: {}", e))?;
+
+    Ok(imported)
+}
+
+fn import_blocking(conn: &mut Connection, path: &Path, mode: ImportMode) -> Result<ImportSummary, String> {
+    let temp_dir = tempfile::tempdir()
+        .map_err(|e| format!("Ошибка при создании временной директории: {}", e))?;
+    let database_path = temp_dir.path().join(DATABASE_ENTRY);
+
+    let manifest = prepare_archive_database(path, &database_path)?;
+
+    conn.execute(
+        &format!("ATTACH DATABASE ? AS {}", ARCHIVE_SCHEMA),
+        params![database_path.to_string_lossy()],
+    ).map_err(|e| format!("Не удалось подключить БД из архива: {}", e))?;
+
+    // Соединение возвращается в пул, поэтому база архива отключается и при ошибке импорта
+    let result = copy_tables(conn, mode);
+    if let Err(e) = conn.execute(&format!("DETACH DATABASE {}", ARCHIVE_SCHEMA), []) {
+        eprintln!("Не удалось отключить БД из архива: {}", e);
+    }
+
+    Ok(ImportSummary { manifest, mode, tables: result? })
+}
+
+// Экспорт всех данных приложения в архив
+#[tauri::command]
+pub async fn export_app_data(db: State<'_, DbState>, path: String) -> Result<AppDataManifest, String> {
+    let manifest = db.run(move |conn| export_blocking(conn, Path::new(&path))).await?;
+
+    println!("Данные приложения экспортированы (версия схемы {}): {:?}", manifest.schema_version, manifest.tables);
+    Ok(manifest)
+}
+
+// Импорт данных приложения из архива
+#[tauri::command]
+pub async fn import_app_data(
+    db: State<'_, DbState>,
+    pty: State<'_, PtyState>,
+    path: String,
+    mode: ImportMode,
+) -> Result<ImportSummary, String> {
+    let summary = db.run(move |conn| import_blocking(conn, Path::new(&path), mode)).await?;
+
+    // Настройки защиты ввода хранятся в БД, но применяются из состояния терминалов
+    load_input_guard_config(&pty, &db).await?;
+
+    println!(
+        "Данные приложения импортированы ({:?}, архив от {}): {:?}",
+        summary.mode, summary.manifest.created_at, summary.tables
+    );
+    Ok(summary)
+}
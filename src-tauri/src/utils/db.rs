@@ -1,9 +1,23 @@
-use rusqlite::{Connection, Result, params};
-use serde::{Serialize, Deserialize};
+use r2d2::{Pool, PooledConnection};
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::{Connection, OptionalExtension, Result, params};
+use serde::{de::DeserializeOwned, Serialize, Deserialize};
 use std::fs;
-use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tauri::{AppHandle, Manager};
 
+use crate::utils::db_migrations::run_migrations;
+use crate::utils::terminal_layout::LayoutNode;
+
+// Максимальное число соединений в пуле
+const POOL_SIZE: u32 = 8;
+
+// Сколько подготовленных запросов кэшируется на каждом соединении
+const STATEMENT_CACHE_CAPACITY: usize = 64;
+
+// Сколько ждать освобождения блокировки записи другим соединением
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
 // Структура для хранения данных о команде в истории
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TerminalCommandRecord {
@@ -14,6 +28,12 @@ pub struct TerminalCommandRecord {
     pub status: Option<String>,
     pub exit_code: Option<i32>,
     pub output: Option<String>,
+    #[serde(default)]
+    pub cwd: Option<String>,
+    #[serde(default)]
+    pub started_at: Option<String>,
+    #[serde(default)]
+    pub finished_at: Option<String>,
 }
 
 // Структура для хранения данных о вкладке терминала
@@ -24,9 +44,12 @@ pub struct TerminalTabRecord {
     pub last_used: String,
 }
 
-// Состояние для хранения соединения с БД и управления им
+// Состояние БД: пул соединений в режиме WAL
+// Чтение не ждет запись (например, сохранение вывода команд из терминала),
+// а запросы выполняются в пуле блокирующих задач, не занимая потоки async runtime
+#[derive(Clone)]
 pub struct DbState {
-    connection: Arc<Mutex<Connection>>,
+    pool: Pool<SqliteConnectionManager>,
 }
 
 impl DbState {
@@ -47,48 +70,173 @@ impl DbState {
         let db_path = app_dir.join("terminals.db");
         println!("Используем базу данных по пути: {:?}", db_path);
         
-        // Открываем соединение с БД
-        let conn = Connection::open(db_path)
-            .map_err(|e| format!("Не удалось открыть соединение с БД: {}", e))?;
-        
-        // Инициализируем БД
-        Self::initialize_db(&conn)?;
+        Self::open(&db_path)
+    }
+
+    pub fn open(db_path: &std::path::Path) -> Result<Self, String> {
+        // Обновляем схему БД до версии приложения до открытия пула
+        {
+            let mut conn = Connection::open(db_path)
+                .map_err(|e| format!("Не удалось открыть соединение с БД: {}", e))?;
+            run_migrations(&mut conn, db_path)?;
+            
+            // Режим WAL сохраняется в файле БД, поэтому включается один раз
+            conn.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))
+                .map_err(|e| format!("Не удалось включить режим WAL: {}", e))?;
+        }
+        println!("Схема БД успешно инициализирована");
         
-        Ok(DbState {
-            connection: Arc::new(Mutex::new(conn)),
+        let manager = SqliteConnectionManager::file(db_path).with_init(|conn| {
+            conn.busy_timeout(BUSY_TIMEOUT)?;
+            conn.pragma_update(None, "synchronous", "NORMAL")?;
+            conn.set_prepared_statement_cache_capacity(STATEMENT_CACHE_CAPACITY);
+            Ok(())
+        });
+
+        let pool = Pool::builder()
+            .max_size(POOL_SIZE)
+            .build(manager)
+            .map_err(|e| format!("Не удалось создать пул соединений с БД: {}", e))?;
+
+        Ok(DbState { pool })
+    }
+
+    fn connection(&self) -> Result<PooledConnection<SqliteConnectionManager>, String> {
+        self.pool.get()
+            .map_err(|e| format!("Не удалось получить соединение с БД: {}", e))
+    }
+
+    // Выполнение работы с БД на отдельном соединении из пула в потоке для блокирующих задач
+    pub async fn run<T, F>(&self, task: F) -> Result<T, String>
+    where
+        F: FnOnce(&mut Connection) -> Result<T, String> + Send + 'static,
+        T: Send + 'static,
+    {
+        let db = self.clone();
+        tauri::async_runtime::spawn_blocking(move || {
+            let mut conn = db.connection()?;
+            task(&mut conn)
         })
+        .await
+        .map_err(|e| format!("Ошибка выполнения задачи БД: {}", e))?
     }
+}
+
+// Сохранение команды, захваченной интеграцией с оболочкой
+pub fn insert_terminal_command(conn: &Connection, command: &TerminalCommandRecord) -> Result<i64, String> {
+    conn.prepare_cached(
+        "INSERT INTO terminal_commands 
+         (terminal_tab_id, command, time, status, exit_code, output, cwd, started_at, finished_at, recorded_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .and_then(|mut stmt| stmt.insert(params![
+        command.terminal_tab_id, command.command, command.time, 
+        command.status, command.exit_code, command.output,
+        command.cwd, command.started_at, command.finished_at, utc_timestamp(chrono::Utc::now())
+    ]))
+    .map_err(|e| format!("Не удалось сохранить команду: {}", e))
+}
+
+// Метка времени в UTC в формате колонки recorded_at (сравнивается строками)
+pub fn utc_timestamp(time: chrono::DateTime<chrono::Utc>) -> String {
+    time.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+// Время в RFC 3339 от клиента в формате utc_timestamp (для фильтров по времени)
+pub fn to_utc_timestamp(time: &str) -> Result<String, String> {
+    chrono::DateTime::parse_from_rfc3339(time)
+        .map(|time| utc_timestamp(time.with_timezone(&chrono::Utc)))
+        .map_err(|e| format!("Некорректное время {}: {}", time, e))
+}
+
+// Чтение настройки приложения (None, если настройка не сохранялась)
+pub fn get_setting<T: DeserializeOwned>(conn: &Connection, key: &str) -> Result<Option<T>, String> {
+    let value: Option<String> = conn.prepare_cached("SELECT value FROM app_settings WHERE key = ?")
+        .and_then(|mut stmt| stmt.query_row(params![key], |row| row.get(0)).optional())
+        .map_err(|e| format!("Ошибка выполнения запроса: {}", e))?;
+
+    value
+        .map(|value| serde_json::from_str(&value)
+            .map_err(|e| format!("Поврежденная настройка {}: {}", key, e)))
+        .transpose()
+}
+
+pub fn set_setting<T: Serialize>(conn: &Connection, key: &str, value: &T) -> Result<(), String> {
+    let value = serde_json::to_string(value)
+        .map_err(|e| format!("Ошибка сериализации настройки {}: {}", key, e))?;
+
+    conn.prepare_cached("INSERT OR REPLACE INTO app_settings (key, value, updated_at) VALUES (?, ?, ?)")
+        .and_then(|mut stmt| stmt.execute(params![key, value, chrono::Local::now().to_rfc3339()]))
+        .map_err(|e| format!("Не удалось сохранить настройку {}: {}", key, e))?;
+
+    Ok(())
+}
+
+// Перебор вывода команд истории от новых к старым
+// fts_query отбирает кандидатов по индексу FTS5, без него просматривается вся история;
+// обработчик возвращает false, чтобы остановить перебор
+pub fn for_each_command_output<F>(conn: &Connection, fts_query: Option<&str>, tab_id: Option<i64>, mut handler: F) -> Result<(), String>
+where
+    F: FnMut(i64, i64, &str) -> bool,
+{
+    let sql = match fts_query {
+        Some(_) => "SELECT c.id, c.terminal_tab_id, c.output FROM terminal_commands_fts f
+                    JOIN terminal_commands c ON c.id = f.rowid
+                    WHERE terminal_commands_fts MATCH ?1 AND (?2 IS NULL OR c.terminal_tab_id = ?2)
+                    ORDER BY c.id DESC",
+        None => "SELECT id, terminal_tab_id, output FROM terminal_commands
+                 WHERE output IS NOT NULL AND (?2 IS NULL OR terminal_tab_id = ?2)
+                 ORDER BY id DESC",
+    };
     
-    // Инициализация схемы БД
-    fn initialize_db(conn: &Connection) -> Result<(), String> {
-        // Таблица для хранения вкладок терминала
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS terminal_tabs (
-                id INTEGER PRIMARY KEY,
-                name TEXT NOT NULL,
-                last_used TEXT NOT NULL
-            )",
-            [],
-        ).map_err(|e| format!("Не удалось создать таблицу terminal_tabs: {}", e))?;
-        
-        // Таблица для хранения истории команд
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS terminal_commands (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                terminal_tab_id INTEGER NOT NULL,
-                command TEXT NOT NULL,
-                time TEXT NOT NULL,
-                status TEXT,
-                exit_code INTEGER,
-                output TEXT,
-                FOREIGN KEY (terminal_tab_id) REFERENCES terminal_tabs (id)
-            )",
-            [],
-        ).map_err(|e| format!("Не удалось создать таблицу terminal_commands: {}", e))?;
+    let mut stmt = conn.prepare_cached(sql)
+        .map_err(|e| format!("Ошибка подготовки запроса: {}", e))?;
+    let mut rows = stmt.query(params![fts_query, tab_id])
+        .map_err(|e| format!("Ошибка выполнения запроса: {}", e))?;
+    
+    while let Some(row) = rows.next().map_err(|e| format!("Ошибка чтения строки: {}", e))? {
+        let id: i64 = row.get(0).map_err(|e| format!("Ошибка чтения строки: {}", e))?;
+        let tab_id: i64 = row.get(1).map_err(|e| format!("Ошибка чтения строки: {}", e))?;
+        let output: Option<String> = row.get(2).map_err(|e| format!("Ошибка чтения строки: {}", e))?;
         
-        println!("Схема БД успешно инициализирована");
-        Ok(())
+        if !handler(id, tab_id, output.as_deref().unwrap_or_default()) {
+            break;
+        }
     }
+    
+    Ok(())
+}
+
+// Сохранение раскладки вкладки
+pub fn save_terminal_layout(conn: &Connection, tab_id: i64, root: &LayoutNode) -> Result<(), String> {
+    let layout = serde_json::to_string(root)
+        .map_err(|e| format!("Ошибка сериализации раскладки: {}", e))?;
+    
+    conn.prepare_cached("INSERT OR REPLACE INTO terminal_layouts (tab_id, layout, updated_at) VALUES (?, ?, ?)")
+        .and_then(|mut stmt| stmt.execute(params![tab_id, layout, chrono::Local::now().to_rfc3339()]))
+        .map_err(|e| format!("Не удалось сохранить раскладку: {}", e))?;
+    
+    Ok(())
+}
+
+// Загрузка раскладки вкладки (None, если раскладка не сохранялась)
+pub fn get_terminal_layout(conn: &Connection, tab_id: i64) -> Result<Option<LayoutNode>, String> {
+    let layout: Option<String> = conn.prepare_cached("SELECT layout FROM terminal_layouts WHERE tab_id = ?")
+        .and_then(|mut stmt| stmt.query_row(params![tab_id], |row| row.get(0)).optional())
+        .map_err(|e| format!("Ошибка выполнения запроса: {}", e))?;
+    
+    layout
+        .map(|layout| serde_json::from_str(&layout)
+            .map_err(|e| format!("Поврежденная раскладка вкладки {}: {}", tab_id, e)))
+        .transpose()
+}
+
+pub fn delete_terminal_layout(conn: &Connection, tab_id: i64) -> Result<(), String> {
+    conn.prepare_cached("DELETE FROM terminal_layouts WHERE tab_id = ?")
+        .and_then(|mut stmt| stmt.execute(params![tab_id]))
+        .map_err(|e| format!("Ошибка удаления раскладки: {}", e))?;
+    
+    Ok(())
 }
 
 // Команды для работы с БД
@@ -98,43 +246,40 @@ pub async fn save_terminal_tab(
     state: tauri::State<'_, DbState>,
     tab: TerminalTabRecord,
 ) -> Result<(), String> {
-    let conn = state.connection.lock()
-        .map_err(|e| format!("Ошибка блокировки мьютекса: {}", e))?;
-    
-    conn.execute(
-        "INSERT OR REPLACE INTO terminal_tabs (id, name, last_used) VALUES (?, ?, ?)",
-        params![tab.id, tab.name, tab.last_used],
-    ).map_err(|e| format!("Не удалось сохранить вкладку: {}", e))?;
-    
-    println!("Сохранена вкладка с ID: {}", tab.id);
-    Ok(())
+    state.run(move |conn| {
+        conn.prepare_cached("INSERT OR REPLACE INTO terminal_tabs (id, name, last_used) VALUES (?, ?, ?)")
+            .and_then(|mut stmt| stmt.execute(params![tab.id, tab.name, tab.last_used]))
+            .map_err(|e| format!("Не удалось сохранить вкладку: {}", e))?;
+        
+        println!("Сохранена вкладка с ID: {}", tab.id);
+        Ok(())
+    }).await
 }
 
 #[tauri::command]
 pub async fn get_saved_terminal_tabs(
     state: tauri::State<'_, DbState>,
 ) -> Result<Vec<TerminalTabRecord>, String> {
-    let conn = state.connection.lock()
-        .map_err(|e| format!("Ошибка блокировки мьютекса: {}", e))?;
-    
-    let mut stmt = conn.prepare("SELECT id, name, last_used FROM terminal_tabs ORDER BY last_used DESC")
-        .map_err(|e| format!("Ошибка подготовки запроса: {}", e))?;
-    
-    let rows = stmt.query_map([], |row| {
-        Ok(TerminalTabRecord {
-            id: row.get(0)?,
-            name: row.get(1)?,
-            last_used: row.get(2)?,
-        })
-    }).map_err(|e| format!("Ошибка выполнения запроса: {}", e))?;
-    
-    let mut tabs = Vec::new();
-    for row in rows {
-        tabs.push(row.map_err(|e| format!("Ошибка чтения строки: {}", e))?);
-    }
-    
-    println!("Загружено {} вкладок из БД", tabs.len());
-    Ok(tabs)
+    state.run(|conn| {
+        let mut stmt = conn.prepare_cached("SELECT id, name, last_used FROM terminal_tabs ORDER BY last_used DESC")
+            .map_err(|e| format!("Ошибка подготовки запроса: {}", e))?;
+        
+        let rows = stmt.query_map([], |row| {
+            Ok(TerminalTabRecord {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                last_used: row.get(2)?,
+            })
+        }).map_err(|e| format!("Ошибка выполнения запроса: {}", e))?;
+        
+        let mut tabs = Vec::new();
+        for row in rows {
+            tabs.push(row.map_err(|e| format!("Ошибка чтения строки: {}", e))?);
+        }
+        
+        println!("Загружено {} вкладок из БД", tabs.len());
+        Ok(tabs)
+    }).await
 }
 
 #[tauri::command]
@@ -142,31 +287,31 @@ pub async fn delete_terminal_tab(
     state: tauri::State<'_, DbState>,
     tab_id: i64,
 ) -> Result<(), String> {
-    let mut conn = state.connection.lock()
-        .map_err(|e| format!("Ошибка блокировки мьютекса: {}", e))?;
-    
-    // Начинаем транзакцию
-    let tx = conn.transaction()
-        .map_err(|e| format!("Ошибка создания транзакции: {}", e))?;
-    
-    // Сначала удаляем все команды для этой вкладки
-    tx.execute(
-        "DELETE FROM terminal_commands WHERE terminal_tab_id = ?",
-        params![tab_id],
-    ).map_err(|e| format!("Ошибка удаления команд: {}", e))?;
-    
-    // Затем удаляем саму вкладку
-    tx.execute(
-        "DELETE FROM terminal_tabs WHERE id = ?",
-        params![tab_id],
-    ).map_err(|e| format!("Ошибка удаления вкладки: {}", e))?;
-    
-    // Завершаем транзакцию
-    tx.commit()
-        .map_err(|e| format!("Ошибка фиксации транзакции: {}", e))?;
-    
-    println!("Удалена вкладка с ID: {}", tab_id);
-    Ok(())
+    state.run(move |conn| {
+        // Начинаем транзакцию
+        let tx = conn.transaction()
+            .map_err(|e| format!("Ошибка создания транзакции: {}", e))?;
+        
+        // Сначала удаляем все команды для этой вкладки
+        tx.prepare_cached("DELETE FROM terminal_commands WHERE terminal_tab_id = ?")
+            .and_then(|mut stmt| stmt.execute(params![tab_id]))
+            .map_err(|e| format!("Ошибка удаления команд: {}", e))?;
+        
+        // Удаляем раскладку вкладки
+        delete_terminal_layout(&tx, tab_id)?;
+        
+        // Затем удаляем саму вкладку
+        tx.prepare_cached("DELETE FROM terminal_tabs WHERE id = ?")
+            .and_then(|mut stmt| stmt.execute(params![tab_id]))
+            .map_err(|e| format!("Ошибка удаления вкладки: {}", e))?;
+        
+        // Завершаем транзакцию
+        tx.commit()
+            .map_err(|e| format!("Ошибка фиксации транзакции: {}", e))?;
+        
+        println!("Удалена вкладка с ID: {}", tab_id);
+        Ok(())
+    }).await
 }
 
 #[tauri::command]
@@ -174,70 +319,60 @@ pub async fn save_terminal_command(
     state: tauri::State<'_, DbState>,
     command: TerminalCommandRecord,
 ) -> Result<i64, String> {
-    let conn = state.connection.lock()
-        .map_err(|e| format!("Ошибка блокировки мьютекса: {}", e))?;
-    
-    // Если ID уже существует, обновляем запись
-    if let Some(id) = command.id {
-        conn.execute(
-            "UPDATE terminal_commands SET 
-             terminal_tab_id = ?, command = ?, time = ?, status = ?, exit_code = ?, output = ?
-             WHERE id = ?",
-            params![
+    state.run(move |conn| {
+        // Если ID уже существует, обновляем запись
+        if let Some(id) = command.id {
+            conn.prepare_cached(
+                "UPDATE terminal_commands SET 
+                 terminal_tab_id = ?, command = ?, time = ?, status = ?, exit_code = ?, output = ?,
+                 cwd = COALESCE(?, cwd), started_at = COALESCE(?, started_at), finished_at = COALESCE(?, finished_at)
+                 WHERE id = ?",
+            )
+            .and_then(|mut stmt| stmt.execute(params![
                 command.terminal_tab_id, command.command, command.time, 
-                command.status, command.exit_code, command.output, id
-            ],
-        ).map_err(|e| format!("Не удалось обновить команду: {}", e))?;
+                command.status, command.exit_code, command.output,
+                command.cwd, command.started_at, command.finished_at, id
+            ]))
+            .map_err(|e| format!("Не удалось обновить команду: {}", e))?;
+            
+            println!("Обновлена команда с ID: {}", id);
+            return Ok(id);
+        }
         
-        println!("Обновлена команда с ID: {}", id);
-        return Ok(id);
-    }
-    
-    // Проверяем, нет ли уже такой же команды с тем же временем
-    // Это предотвратит дублирование команд в БД
-    let mut stmt = conn.prepare(
-        "SELECT id FROM terminal_commands 
-         WHERE terminal_tab_id = ? AND command = ? AND time = ? 
-         LIMIT 1"
-    ).map_err(|e| format!("Ошибка подготовки запроса: {}", e))?;
-    
-    let existing_ids: Vec<i64> = stmt.query_map(
-        params![command.terminal_tab_id, command.command, command.time],
-        |row| row.get(0)
-    )
-    .map_err(|e| format!("Ошибка выполнения запроса: {}", e))?
-    .filter_map(|r| r.ok())
-    .collect();
-    
-    // Если команда с таким же текстом и временем уже существует, обновляем её
-    if let Some(existing_id) = existing_ids.first() {
-        conn.execute(
-            "UPDATE terminal_commands SET 
-             status = ?, exit_code = ?, output = ?
-             WHERE id = ?",
-            params![
+        // Проверяем, нет ли уже такой же команды с тем же временем
+        // Это предотвратит дублирование команд в БД
+        let existing_id: Option<i64> = conn.prepare_cached(
+            "SELECT id FROM terminal_commands 
+             WHERE terminal_tab_id = ? AND command = ? AND time = ? 
+             LIMIT 1"
+        )
+        .and_then(|mut stmt| stmt.query_row(
+            params![command.terminal_tab_id, command.command, command.time],
+            |row| row.get(0)
+        ).optional())
+        .map_err(|e| format!("Ошибка выполнения запроса: {}", e))?;
+        
+        // Если команда с таким же текстом и временем уже существует, обновляем её
+        if let Some(existing_id) = existing_id {
+            conn.prepare_cached(
+                "UPDATE terminal_commands SET 
+                 status = ?, exit_code = ?, output = ?
+                 WHERE id = ?",
+            )
+            .and_then(|mut stmt| stmt.execute(params![
                 command.status, command.exit_code, command.output, existing_id
-            ],
-        ).map_err(|e| format!("Не удалось обновить существующую команду: {}", e))?;
+            ]))
+            .map_err(|e| format!("Не удалось обновить существующую команду: {}", e))?;
+            
+            println!("Обновлена существующая команда с ID: {}", existing_id);
+            return Ok(existing_id);
+        }
         
-        println!("Обновлена существующая команда с ID: {}", existing_id);
-        return Ok(*existing_id);
-    }
-    
-    // Иначе создаем новую запись
-    conn.execute(
-        "INSERT INTO terminal_commands 
-         (terminal_tab_id, command, time, status, exit_code, output)
-         VALUES (?, ?, ?, ?, ?, ?)",
-        params![
-            command.terminal_tab_id, command.command, command.time, 
-            command.status, command.exit_code, command.output
-        ],
-    ).map_err(|e| format!("Не удалось сохранить команду: {}", e))?;
-    
-    let id = conn.last_insert_rowid();
-    println!("Сохранена новая команда с ID: {}", id);
-    Ok(id)
+        // Иначе создаем новую запись
+        let id = insert_terminal_command(conn, &command)?;
+        println!("Сохранена новая команда с ID: {}", id);
+        Ok(id)
+    }).await
 }
 
 #[tauri::command]
@@ -245,35 +380,37 @@ pub async fn get_terminal_commands(
     state: tauri::State<'_, DbState>,
     tab_id: i64,
 ) -> Result<Vec<TerminalCommandRecord>, String> {
-    let conn = state.connection.lock()
-        .map_err(|e| format!("Ошибка блокировки мьютекса: {}", e))?;
-    
-    let mut stmt = conn.prepare(
-        "SELECT id, terminal_tab_id, command, time, status, exit_code, output 
-         FROM terminal_commands 
-         WHERE terminal_tab_id = ? 
-         ORDER BY id ASC"
-    ).map_err(|e| format!("Ошибка подготовки запроса: {}", e))?;
-    
-    let rows = stmt.query_map(params![tab_id], |row| {
-        Ok(TerminalCommandRecord {
-            id: Some(row.get(0)?),
-            terminal_tab_id: row.get(1)?,
-            command: row.get(2)?,
-            time: row.get(3)?,
-            status: row.get(4)?,
-            exit_code: row.get(5)?,
-            output: row.get(6)?,
-        })
-    }).map_err(|e| format!("Ошибка выполнения запроса: {}", e))?;
-    
-    let mut commands = Vec::new();
-    for row in rows {
-        commands.push(row.map_err(|e| format!("Ошибка чтения строки: {}", e))?);
-    }
-    
-    println!("Загружено {} команд для вкладки {}", commands.len(), tab_id);
-    Ok(commands)
+    state.run(move |conn| {
+        let mut stmt = conn.prepare_cached(
+            "SELECT id, terminal_tab_id, command, time, status, exit_code, output, cwd, started_at, finished_at 
+             FROM terminal_commands 
+             WHERE terminal_tab_id = ? 
+             ORDER BY id ASC"
+        ).map_err(|e| format!("Ошибка подготовки запроса: {}", e))?;
+        
+        let rows = stmt.query_map(params![tab_id], |row| {
+            Ok(TerminalCommandRecord {
+                id: Some(row.get(0)?),
+                terminal_tab_id: row.get(1)?,
+                command: row.get(2)?,
+                time: row.get(3)?,
+                status: row.get(4)?,
+                exit_code: row.get(5)?,
+                output: row.get(6)?,
+                cwd: row.get(7)?,
+                started_at: row.get(8)?,
+                finished_at: row.get(9)?,
+            })
+        }).map_err(|e| format!("Ошибка выполнения запроса: {}", e))?;
+        
+        let mut commands = Vec::new();
+        for row in rows {
+            commands.push(row.map_err(|e| format!("Ошибка чтения строки: {}", e))?);
+        }
+        
+        println!("Загружено {} команд для вкладки {}", commands.len(), tab_id);
+        Ok(commands)
+    }).await
 }
 
 #[tauri::command]
@@ -281,16 +418,14 @@ pub async fn delete_terminal_command(
     state: tauri::State<'_, DbState>,
     command_id: i64,
 ) -> Result<(), String> {
-    let conn = state.connection.lock()
-        .map_err(|e| format!("Ошибка блокировки мьютекса: {}", e))?;
-    
-    conn.execute(
-        "DELETE FROM terminal_commands WHERE id = ?",
-        params![command_id],
-    ).map_err(|e| format!("Ошибка удаления команды: {}", e))?;
-    
-    println!("Удалена команда с ID: {}", command_id);
-    Ok(())
+    state.run(move |conn| {
+        conn.prepare_cached("DELETE FROM terminal_commands WHERE id = ?")
+            .and_then(|mut stmt| stmt.execute(params![command_id]))
+            .map_err(|e| format!("Ошибка удаления команды: {}", e))?;
+        
+        println!("Удалена команда с ID: {}", command_id);
+        Ok(())
+    }).await
 }
 
 #[tauri::command]
@@ -298,14 +433,12 @@ pub async fn clear_terminal_history(
     state: tauri::State<'_, DbState>,
     tab_id: i64,
 ) -> Result<(), String> {
-    let conn = state.connection.lock()
-        .map_err(|e| format!("Ошибка блокировки мьютекса: {}", e))?;
-    
-    conn.execute(
-        "DELETE FROM terminal_commands WHERE terminal_tab_id = ?",
-        params![tab_id],
-    ).map_err(|e| format!("Ошибка очистки истории: {}", e))?;
-    
-    println!("Очищена история для вкладки с ID: {}", tab_id);
-    Ok(())
-} 
\ No newline at end of file
+    state.run(move |conn| {
+        conn.prepare_cached("DELETE FROM terminal_commands WHERE terminal_tab_id = ?")
+            .and_then(|mut stmt| stmt.execute(params![tab_id]))
+            .map_err(|e| format!("Ошибка очистки истории: {}", e))?;
+        
+        println!("Очищена история для вкладки с ID: {}", tab_id);
+        Ok(())
+    }).await
+} 
@@ -0,0 +1,411 @@
+// Модуль миграций базы данных
+// Схема terminals.db описывается упорядоченным списком миграций. Номер последней примененной
+// миграции хранится в таблице schema_version; при запуске недостающие миграции применяются
+// по одной в транзакции, а перед обновлением существующей базы делается резервная копия.
+// Первые миграции идемпотентны, чтобы базы, созданные до появления миграций, обновлялись корректно
+
+use rusqlite::{params, Connection, Transaction};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Сколько резервных копий перед миграцией хранить
+const MAX_BACKUPS: usize = 5;
+
+// Миграция схемы: SQL-скрипт или функция для изменений, которые нельзя выразить в SQL
+pub enum MigrationStep {
+    Sql(&'static str),
+    Function(fn(&Transaction) -> Result<(), String>),
+}
+
+pub struct Migration {
+    pub version: i64,
+    pub description: &'static str,
+    pub step: MigrationStep,
+}
+
+// Все миграции в порядке применения; новые добавляются только в конец
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "Вкладки терминала и история команд",
+        step: MigrationStep::Sql(
+            "CREATE TABLE IF NOT EXISTS terminal_tabs (
+                id INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                last_used TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS terminal_commands (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                terminal_tab_id INTEGER NOT NULL,
+                command TEXT NOT NULL,
+                time TEXT NOT NULL,
+                status TEXT,
+                exit_code INTEGER,
+                output TEXT,
+                FOREIGN KEY (terminal_tab_id) REFERENCES terminal_tabs (id)
+            );",
+        ),
+    },
+    Migration {
+        version: 2,
+        description: "Колонки интеграции с оболочкой (cwd, время начала и завершения команды)",
+        step: MigrationStep::Function(migrate_shell_integration_columns),
+    },
+    Migration {
+        version: 3,
+        description: "Раскладки вкладок терминала",
+        step: MigrationStep::Sql(
+            "CREATE TABLE IF NOT EXISTS terminal_layouts (
+                tab_id INTEGER PRIMARY KEY,
+                layout TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                FOREIGN KEY (tab_id) REFERENCES terminal_tabs (id)
+            );",
+        ),
+    },
+    Migration {
+        version: 4,
+        description: "Полнотекстовый индекс вывода команд",
+        step: MigrationStep::Function(migrate_output_index),
+    },
+    Migration {
+        version: 5,
+        description: "Время записи команд для политики хранения истории",
+        step: MigrationStep::Function(migrate_recorded_at),
+    },
+    Migration {
+        version: 6,
+        description: "Настройки приложения",
+        step: MigrationStep::Sql(
+            "CREATE TABLE IF NOT EXISTS app_settings (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            );",
+        ),
+    },
+    Migration {
+        version: 7,
+        description: "Библиотека скриптов с историей версий и полнотекстовым индексом",
+        // Индекс хранит собственную копию текста: у scripts нет целочисленного ключа,
+        // а rowid без него может измениться при VACUUM
+        step: MigrationStep::Sql(
+            "CREATE TABLE IF NOT EXISTS scripts (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                language TEXT NOT NULL,
+                content TEXT NOT NULL,
+                tags TEXT NOT NULL DEFAULT '[]',
+                version INTEGER NOT NULL,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS script_versions (
+                script_id TEXT NOT NULL,
+                version INTEGER NOT NULL,
+                name TEXT NOT NULL,
+                language TEXT NOT NULL,
+                content TEXT NOT NULL,
+                tags TEXT NOT NULL DEFAULT '[]',
+                created_at TEXT NOT NULL,
+                PRIMARY KEY (script_id, version),
+                FOREIGN KEY (script_id) REFERENCES scripts (id)
+            );
+            CREATE INDEX IF NOT EXISTS idx_scripts_updated_at ON scripts (updated_at);
+            CREATE VIRTUAL TABLE IF NOT EXISTS scripts_fts USING fts5(
+                script_id UNINDEXED,
+                name,
+                content,
+                tags,
+                tokenize = 'trigram'
+            );
+            CREATE TRIGGER IF NOT EXISTS scripts_fts_insert AFTER INSERT ON scripts BEGIN
+                INSERT INTO scripts_fts (script_id, name, content, tags) VALUES (new.id, new.name, new.content, new.tags);
+            END;
+            CREATE TRIGGER IF NOT EXISTS scripts_fts_delete AFTER DELETE ON scripts BEGIN
+                DELETE FROM scripts_fts WHERE script_id = old.id;
+            END;
+            CREATE TRIGGER IF NOT EXISTS scripts_fts_update AFTER UPDATE OF name, content, tags ON scripts BEGIN
+                DELETE FROM scripts_fts WHERE script_id = old.id;
+                INSERT INTO scripts_fts (script_id, name, content, tags) VALUES (new.id, new.name, new.content, new.tags);
+            END;",
+        ),
+    },
+    Migration {
+        version: 8,
+        description: "История запусков скриптов",
+        step: MigrationStep::Sql(
+            "CREATE TABLE IF NOT EXISTS script_runs (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                script_id TEXT,
+                script_hash TEXT NOT NULL,
+                interpreter TEXT NOT NULL,
+                args TEXT NOT NULL DEFAULT '[]',
+                cwd TEXT,
+                started_at TEXT NOT NULL,
+                finished_at TEXT NOT NULL,
+                duration_ms INTEGER NOT NULL,
+                status TEXT NOT NULL,
+                exit_code INTEGER,
+                stdout TEXT NOT NULL,
+                stderr TEXT NOT NULL,
+                stdout_truncated INTEGER NOT NULL DEFAULT 0,
+                stderr_truncated INTEGER NOT NULL DEFAULT 0,
+                error TEXT
+            );
+            CREATE INDEX IF NOT EXISTS idx_script_runs_started_at ON script_runs (started_at);
+            CREATE INDEX IF NOT EXISTS idx_script_runs_script ON script_runs (script_id, started_at);
+            CREATE INDEX IF NOT EXISTS idx_script_runs_hash ON script_runs (script_hash, started_at);",
+        ),
+    },
+    Migration {
+        version: 9,
+        description: "Расписания запуска скриптов",
+        step: MigrationStep::Sql(
+            "CREATE TABLE IF NOT EXISTS script_schedules (
+                id TEXT PRIMARY KEY,
+                script_id TEXT NOT NULL,
+                trigger_spec TEXT NOT NULL,
+                options TEXT NOT NULL DEFAULT '{}',
+                timeout_secs INTEGER,
+                enabled INTEGER NOT NULL DEFAULT 1,
+                next_run_at TEXT,
+                last_run_at TEXT,
+                last_status TEXT,
+                last_history_id INTEGER,
+                last_error TEXT,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                FOREIGN KEY (script_id) REFERENCES scripts (id)
+            );
+            CREATE INDEX IF NOT EXISTS idx_script_schedules_script ON script_schedules (script_id);",
+        ),
+    },
+    Migration {
+        version: 10,
+        description: "Ресурсы запусков скриптов в песочнице",
+        step: MigrationStep::Function(migrate_script_run_usage_columns),
+    },
+];
+
+// Версия схемы, которую поддерживает эта сборка приложения
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+fn migrate_shell_integration_columns(tx: &Transaction) -> Result<(), String> {
+    ensure_column(tx, "terminal_commands", "cwd", "TEXT")?;
+    ensure_column(tx, "terminal_commands", "started_at", "TEXT")?;
+    ensure_column(tx, "terminal_commands", "finished_at", "TEXT")
+}
+
+// Индекс FTS5 по выводу команд (триграммы - поиск по любой подстроке длиной от 3 символов)
+// Индекс синхронизируется с terminal_commands триггерами
+fn migrate_output_index(tx: &Transaction) -> Result<(), String> {
+    let exists = table_exists(tx, "terminal_commands_fts")?;
+
+    tx.execute_batch(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS terminal_commands_fts USING fts5(
+            output,
+            content = 'terminal_commands',
+            content_rowid = 'id',
+            tokenize = 'trigram'
+        );
+        CREATE TRIGGER IF NOT EXISTS terminal_commands_fts_insert AFTER INSERT ON terminal_commands BEGIN
+            INSERT INTO terminal_commands_fts (rowid, output) VALUES (new.id, new.output);
+        END;
+        CREATE TRIGGER IF NOT EXISTS terminal_commands_fts_delete AFTER DELETE ON terminal_commands BEGIN
+            INSERT INTO terminal_commands_fts (terminal_commands_fts, rowid, output) VALUES ('delete', old.id, old.output);
+        END;
+        CREATE TRIGGER IF NOT EXISTS terminal_commands_fts_update AFTER UPDATE OF output ON terminal_commands BEGIN
+            INSERT INTO terminal_commands_fts (terminal_commands_fts, rowid, output) VALUES ('delete', old.id, old.output);
+            INSERT INTO terminal_commands_fts (rowid, output) VALUES (new.id, new.output);
+        END;",
+    ).map_err(|e| format!("Не удалось создать индекс terminal_commands_fts: {}", e))?;
+
+    // Индексируем историю, накопленную до появления индекса
+    if !exists {
+        tx.execute(
+            "INSERT INTO terminal_commands_fts (terminal_commands_fts) VALUES ('rebuild')",
+            [],
+        ).map_err(|e| format!("Не удалось построить индекс terminal_commands_fts: {}", e))?;
+    }
+
+    Ok(())
+}
+
+// Время записи команды в историю (колонка time хранит только время суток)
+// Хранится в UTC в одном формате, чтобы сравнивать время строками по индексу
+// Для уже сохраненных команд берется время запуска, а если его нет - время миграции
+fn migrate_recorded_at(tx: &Transaction) -> Result<(), String> {
+    ensure_column(tx, "terminal_commands", "recorded_at", "TEXT")?;
+
+    tx.execute_batch(
+        "UPDATE terminal_commands SET recorded_at = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', started_at), strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+         WHERE recorded_at IS NULL;
+        CREATE INDEX IF NOT EXISTS idx_terminal_commands_recorded_at ON terminal_commands (recorded_at);
+        CREATE INDEX IF NOT EXISTS idx_terminal_commands_tab ON terminal_commands (terminal_tab_id, id);",
+    ).map_err(|e| format!("Не удалось заполнить recorded_at: {}", e))
+}
+
+fn migrate_script_run_usage_columns(tx: &Transaction) -> Result<(), String> {
+    ensure_column(tx, "script_runs", "peak_memory_kb", "INTEGER")?;
+    ensure_column(tx, "script_runs", "cpu_time_ms", "INTEGER")
+}
+
+fn table_exists(conn: &Connection, table: &str) -> Result<bool, String> {
+    conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?)",
+        params![table],
+        |row| row.get(0),
+    ).map_err(|e| format!("Ошибка выполнения запроса: {}", e))
+}
+
+// Добавление колонки в существующую таблицу, если ее еще нет
+fn ensure_column(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<(), String> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))
+        .map_err(|e| format!("Ошибка подготовки запроса: {}", e))?;
+
+    let exists = stmt.query_map([], |row| row.get::<_, String>(1))
+        .map_err(|e| format!("Ошибка выполнения запроса: {}", e))?
+        .filter_map(|r| r.ok())
+        .any(|name| name == column);
+
+    if !exists {
+        conn.execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+            [],
+        ).map_err(|e| format!("Не удалось добавить колонку {} в {}: {}", column, table, e))?;
+    }
+
+    Ok(())
+}
+
+// Текущая версия схемы (0 - база без таблицы schema_version)
+pub fn current_version(conn: &Connection) -> Result<i64, String> {
+    if !table_exists(conn, "schema_version")? {
+        return Ok(0);
+    }
+
+    conn.query_row("SELECT COALESCE(MAX(version), 0) FROM schema_version", [], |row| row.get(0))
+        .map_err(|e| format!("Не удалось прочитать версию схемы: {}", e))
+}
+
+// Есть ли в базе пользовательские таблицы (пустую базу резервировать незачем)
+fn has_user_tables(conn: &Connection) -> Result<bool, String> {
+    conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%')",
+        [],
+        |row| row.get(0),
+    ).map_err(|e| format!("Ошибка выполнения запроса: {}", e))
+}
+
+// Резервная копия базы перед миграцией (VACUUM INTO создает согласованную копию)
+fn backup_database(conn: &Connection, db_path: &Path, version: i64) -> Result<PathBuf, String> {
+    let file_name = db_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "terminals.db".to_string());
+    let backup_path = db_path.with_file_name(format!(
+        "{}.v{}-{}.bak",
+        file_name,
+        version,
+        chrono::Local::now().format("%Y%m%d-%H%M%S")
+    ));
+
+    conn.execute("VACUUM INTO ?", params![backup_path.to_string_lossy()])
+        .map_err(|e| format!("Не удалось создать резервную копию БД: {}", e))?;
+
+    prune_backups(db_path, &file_name);
+    Ok(backup_path)
+}
+
+// Удаление старых резервных копий сверх MAX_BACKUPS
+fn prune_backups(db_path: &Path, file_name: &str) {
+    let dir = match db_path.parent() {
+        Some(dir) => dir,
+        None => return,
+    };
+    let prefix = format!("{}.v", file_name);
+
+    let mut backups: Vec<(std::time::SystemTime, PathBuf)> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+                name.starts_with(&prefix) && name.ends_with(".bak")
+            })
+            .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+            .collect(),
+        Err(_) => return,
+    };
+
+    backups.sort_by_key(|backup| std::cmp::Reverse(backup.0));
+    for (_, path) in backups.into_iter().skip(MAX_BACKUPS) {
+        if let Err(e) = fs::remove_file(&path) {
+            eprintln!("Не удалось удалить старую резервную копию {:?}: {}", path, e);
+        }
+    }
+}
+
+// Проверка, что база не создана более новой версией приложения
+pub fn check_supported_version(version: i64) -> Result<(), String> {
+    let latest = latest_version();
+    if version > latest {
+        return Err(format!(
+            "База данных создана более новой версией приложения (версия схемы {}, эта версия поддерживает до {}). Обновите приложение",
+            version, latest
+        ));
+    }
+    Ok(())
+}
+
+// Применение недостающих миграций
+pub fn run_migrations(conn: &mut Connection, db_path: &Path) -> Result<(), String> {
+    let version = current_version(conn)?;
+    check_supported_version(version)?;
+
+    let pending: Vec<&Migration> = MIGRATIONS.iter().filter(|m| m.version > version).collect();
+    if pending.is_empty() {
+        println!("Схема БД актуальна (версия {})", version);
+        return Ok(());
+    }
+
+    if has_user_tables(conn)? {
+        let backup_path = backup_database(conn, db_path, version)?;
+        println!("Создана резервная копия БД перед миграцией: {:?}", backup_path);
+    }
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER PRIMARY KEY,
+            description TEXT NOT NULL,
+            applied_at TEXT NOT NULL
+        )",
+        [],
+    ).map_err(|e| format!("Не удалось создать таблицу schema_version: {}", e))?;
+
+    for migration in pending {
+        let tx = conn.transaction()
+            .map_err(|e| format!("Ошибка создания транзакции: {}", e))?;
+
+        match &migration.step {
+            MigrationStep::Sql(sql) => tx.execute_batch(sql)
+                .map_err(|e| format!("Ошибка миграции {}: {}", migration.version, e))?,
+            MigrationStep::Function(apply) => apply(&tx)
+                .map_err(|e| format!("Ошибка миграции {}: {}", migration.version, e))?,
+        }
+
+        tx.execute(
+            "INSERT INTO schema_version (version, description, applied_at) VALUES (?, ?, ?)",
+            params![migration.version, migration.description, chrono::Local::now().to_rfc3339()],
+        ).map_err(|e| format!("Не удалось записать версию схемы: {}", e))?;
+
+        tx.commit()
+            .map_err(|e| format!("Ошибка фиксации миграции {}: {}", migration.version, e))?;
+
+        println!("Применена миграция {}: {}", migration.version, migration.description);
+    }
+
+    Ok(())
+}
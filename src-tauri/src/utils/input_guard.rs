@@ -0,0 +1,200 @@
+// Модуль защиты ввода терминала
+// Отслеживает набираемую строку по вводу пользователя и перед ее выполнением (Enter)
+// проверяет по списку опасных шаблонов. При совпадении ввод не отправляется,
+// а клиент получает запрос подтверждения; повторная отправка с force пропускает проверку.
+// Многострочная вставка оборачивается в bracketed paste, если оболочка его включила
+
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+// Начало и конец bracketed paste (DECSET 2004)
+pub const PASTE_START: &str = "\x1b[200~";
+pub const PASTE_END: &str = "\x1b[201~";
+
+// Включение и выключение режима bracketed paste оболочкой
+const PASTE_MODE_ON: &str = "\x1b[?2004h";
+const PASTE_MODE_OFF: &str = "\x1b[?2004l";
+
+// Максимальная длина отслеживаемой строки
+const MAX_LINE: usize = 64 * 1024;
+
+// Шаблоны по умолчанию
+const DEFAULT_PATTERNS: &[&str] = &[
+    // rm -rf /, rm -rf /*, rm -rf ~
+    r"\brm\s+(?:-\S+\s+)+(?:/\*?|~/?)(?:[\s;&|]|$)",
+    r"\bmkfs(?:\.\w+)?\b",
+    r"\bdd\b.*\bof=/dev/(?:sd|hd|nvme|disk|mmcblk)",
+    r"(?i)\bFormat-Volume\b",
+    r"(?i)\bformat\s+[a-z]:",
+    r"(?i)\bDROP\s+DATABASE\b",
+];
+
+// Настройки защиты ввода
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InputGuardConfig {
+    // Проверка включается пользователем
+    pub enabled: bool,
+    // Регулярные выражения опасных команд (без учета регистра)
+    pub patterns: Vec<String>,
+}
+
+impl Default for InputGuardConfig {
+    fn default() -> Self {
+        InputGuardConfig {
+            enabled: false,
+            patterns: DEFAULT_PATTERNS.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+}
+
+// Проверка команд по опасным шаблонам
+pub struct InputGuard {
+    config: InputGuardConfig,
+    regexes: Vec<Regex>,
+}
+
+impl InputGuard {
+    pub fn new(config: InputGuardConfig) -> Result<Self, String> {
+        let regexes = config
+            .patterns
+            .iter()
+            .map(|pattern| {
+                RegexBuilder::new(pattern)
+                    .case_insensitive(true)
+                    .build()
+                    .map_err(|e| format!("Некорректный шаблон {}: {}", pattern, e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(InputGuard { config, regexes })
+    }
+
+    pub fn config(&self) -> &InputGuardConfig {
+        &self.config
+    }
+
+    // Первый опасный шаблон, совпавший с одной из строк команды
+    pub fn check(&self, command: &str) -> Option<&str> {
+        if !self.config.enabled {
+            return None;
+        }
+
+        command.lines().find_map(|line| {
+            self.regexes
+                .iter()
+                .position(|regex| regex.is_match(line))
+                .map(|index| self.config.patterns[index].as_str())
+        })
+    }
+}
+
+impl Default for InputGuard {
+    fn default() -> Self {
+        InputGuard::new(InputGuardConfig::default()).expect("шаблоны по умолчанию корректны")
+    }
+}
+
+// Результат отправки ввода
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum InputResult {
+    Sent,
+    // Ввод не отправлен: команда совпала с опасным шаблоном
+    ConfirmationRequired { pattern: String, command: String },
+}
+
+// Строка, набираемая в терминале, восстановленная по вводу пользователя
+// Редактирование стрелками и подстановка из истории оболочки не отслеживаются
+#[derive(Debug, Default)]
+pub struct InputLine {
+    buffer: String,
+    in_paste: bool,
+}
+
+impl InputLine {
+    // Команды, которые будут выполнены после отправки ввода, и состояние строки после него
+    fn apply(&self, input: &str) -> (Vec<String>, InputLine) {
+        let mut line = InputLine {
+            buffer: self.buffer.clone(),
+            in_paste: self.in_paste,
+        };
+        let mut executed = Vec::new();
+        let mut chars = input.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '\x1b' => {
+                    // Управляющая последовательность: учитываем только границы вставки
+                    let mut sequence = String::from(c);
+                    if chars.peek() == Some(&'[') {
+                        sequence.push(chars.next().unwrap_or('['));
+                        for next in chars.by_ref() {
+                            sequence.push(next);
+                            if ('@'..='~').contains(&next) {
+                                break;
+                            }
+                        }
+                    }
+                    if sequence == PASTE_START {
+                        line.in_paste = true;
+                    } else if sequence == PASTE_END {
+                        line.in_paste = false;
+                    }
+                }
+                '\r' | '\n' if line.in_paste => line.buffer.push('\n'),
+                '\r' | '\n' => {
+                    // \r\n от одного нажатия Enter считается одним переводом строки
+                    if c == '\r' && chars.peek() == Some(&'\n') {
+                        chars.next();
+                    }
+                    executed.push(std::mem::take(&mut line.buffer));
+                }
+                '\x7f' | '\x08' => {
+                    line.buffer.pop();
+                }
+                // Ctrl+C и Ctrl+U сбрасывают строку
+                '\x03' | '\x15' => line.buffer.clear(),
+                c if c.is_control() && c != '\t' => {}
+                c => line.buffer.push(c),
+            }
+        }
+
+        if line.buffer.len() > MAX_LINE {
+            line.buffer.clear();
+        }
+
+        (executed, line)
+    }
+
+    // Проверка ввода без изменения состояния строки
+    pub fn check<'a>(&self, input: &str, guard: &'a InputGuard) -> Option<(&'a str, String)> {
+        let (executed, _) = self.apply(input);
+        executed
+            .into_iter()
+            .find_map(|command| guard.check(&command).map(|pattern| (pattern, command)))
+    }
+
+    // Учет отправленного ввода
+    pub fn commit(&mut self, input: &str) {
+        let (_, line) = self.apply(input);
+        *self = line;
+    }
+}
+
+// Обертка многострочной вставки в bracketed paste
+// Маркер конца вставки внутри текста удаляется, чтобы вставка не могла из нее выйти
+pub fn wrap_paste(text: &str) -> String {
+    let sanitized = text.replace(PASTE_END, "").replace(PASTE_START, "");
+    let normalized = sanitized.replace("\r\n", "\r").replace('\n', "\r");
+    format!("{}{}{}", PASTE_START, normalized, PASTE_END)
+}
+
+// Последнее переключение режима bracketed paste в выводе оболочки
+pub fn paste_mode_change(output: &str) -> Option<bool> {
+    match (output.rfind(PASTE_MODE_ON), output.rfind(PASTE_MODE_OFF)) {
+        (Some(on), Some(off)) => Some(on > off),
+        (Some(_), None) => Some(true),
+        (None, Some(_)) => Some(false),
+        (None, None) => None,
+    }
+}
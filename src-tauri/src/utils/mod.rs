@@ -1,5 +1,24 @@
 pub mod terminal;
+pub mod shell_integration;
+pub mod terminal_backend;
+pub mod terminal_watch;
+pub mod terminal_encoding;
+pub mod session_daemon;
+pub mod terminal_layout;
+pub mod terminal_search;
+pub mod terminal_history;
+pub mod terminal_stats;
+pub mod input_guard;
 pub mod db;
+pub mod db_migrations;
+pub mod app_data;
 pub mod system_info;
 pub mod cpu_frequency;
-pub mod script_runner; 
\ No newline at end of file
+pub mod script_runner;
+pub mod script_interpreters;
+pub mod script_library;
+pub mod script_history;
+pub mod script_scheduler;
+pub mod script_params;
+pub mod script_sandbox;
+pub mod script_lint;
\ No newline at end of file
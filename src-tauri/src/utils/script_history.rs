@@ -0,0 +1,516 @@
+// Модуль истории запусков скриптов
+// Каждый запуск run_script и start_script записывается в таблицу script_runs: скрипт
+// (id в библиотеке и хеш текста), интерпретатор, аргументы, время, код возврата и вывод
+// stdout и stderr по отдельности. Вывод ограничен по размеру - сохраняется его конец,
+// где обычно видна причина ошибки. Запуски можно отбирать по скрипту и результату
+// и сравнивать построчно, чтобы найти момент, когда скрипт начал завершаться с ошибкой
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::utils::db::{to_utc_timestamp, utc_timestamp, DbState};
+use crate::utils::script_sandbox::SandboxUsage;
+
+// Сколько байт каждого потока вывода сохранять
+const MAX_RUN_OUTPUT_BYTES: usize = 256 * 1024;
+
+// Сколько запусков одного скрипта хранить (более старые удаляются)
+const MAX_RUNS_PER_SCRIPT: i64 = 200;
+
+// Предел размера таблицы сравнения строк; для больших выводов сравнение упрощается
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+fn default_limit() -> usize {
+    100
+}
+
+// Хеш текста скрипта (FNV-1a, 64 бита) - позволяет отличить запуски разных редакций скрипта
+pub fn script_hash(script: &str) -> String {
+    let hash = script.bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    });
+    format!("{:016x}", hash)
+}
+
+// Накопление вывода скрипта с ограничением размера (сохраняется конец вывода)
+#[derive(Debug, Default)]
+pub struct OutputCapture {
+    text: String,
+    truncated: bool,
+}
+
+impl OutputCapture {
+    pub fn from_text(text: &str) -> Self {
+        let mut capture = Self::default();
+        capture.push(text);
+        capture
+    }
+
+    pub fn push(&mut self, chunk: &str) {
+        self.text.push_str(chunk);
+        // Обрезаем с запасом, чтобы не сдвигать буфер на каждом фрагменте
+        if self.text.len() > MAX_RUN_OUTPUT_BYTES * 2 {
+            self.trim();
+        }
+    }
+
+    fn trim(&mut self) {
+        if self.text.len() <= MAX_RUN_OUTPUT_BYTES {
+            return;
+        }
+        let mut start = self.text.len() - MAX_RUN_OUTPUT_BYTES;
+        while !self.text.is_char_boundary(start) {
+            start += 1;
+        }
+        self.text.drain(..start);
+        self.truncated = true;
+    }
+
+    fn finish(mut self) -> (String, bool) {
+        self.trim();
+        (self.text, self.truncated)
+    }
+}
+
+// Результат запуска
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    // Процесс завершился с кодом 0
+    Success,
+    // Процесс завершился с ненулевым кодом
+    Failure,
+    Cancelled,
+    TimedOut,
+    // Процесс не удалось запустить или дождаться его завершения
+    Error,
+}
+
+impl RunStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RunStatus::Success => "success",
+            RunStatus::Failure => "failure",
+            RunStatus::Cancelled => "cancelled",
+            RunStatus::TimedOut => "timed_out",
+            RunStatus::Error => "error",
+        }
+    }
+
+    pub fn parse(value: &str) -> RunStatus {
+        match value {
+            "success" => RunStatus::Success,
+            "failure" => RunStatus::Failure,
+            "cancelled" => RunStatus::Cancelled,
+            "timed_out" => RunStatus::TimedOut,
+            _ => RunStatus::Error,
+        }
+    }
+}
+
+// Что и как запускалось
+#[derive(Debug, Clone, Serialize)]
+pub struct ScriptRunSource {
+    // id скрипта в библиотеке, если запускался скрипт из нее
+    pub script_id: Option<String>,
+    pub script_hash: String,
+    // id интерпретатора
+    pub interpreter: String,
+    pub args: Vec<String>,
+    pub cwd: Option<String>,
+}
+
+// Запуск скрипта без вывода (для списков)
+#[derive(Debug, Clone, Serialize)]
+pub struct ScriptRunSummary {
+    pub id: i64,
+    #[serde(flatten)]
+    pub source: ScriptRunSource,
+    pub started_at: String,
+    pub finished_at: String,
+    pub duration_ms: i64,
+    pub status: RunStatus,
+    pub exit_code: Option<i32>,
+    pub error: Option<String>,
+    // Размер сохраненного вывода в байтах
+    pub stdout_bytes: i64,
+    pub stderr_bytes: i64,
+    // Пиковая память (КБ) и процессорное время (мс) - только для запусков в песочнице
+    pub peak_memory_kb: Option<i64>,
+    pub cpu_time_ms: Option<i64>,
+}
+
+// Запуск скрипта с выводом
+#[derive(Debug, Clone, Serialize)]
+pub struct ScriptRunRecord {
+    pub id: i64,
+    #[serde(flatten)]
+    pub source: ScriptRunSource,
+    pub started_at: String,
+    pub finished_at: String,
+    pub duration_ms: i64,
+    pub status: RunStatus,
+    pub exit_code: Option<i32>,
+    pub error: Option<String>,
+    pub stdout: String,
+    pub stderr: String,
+    // Вывод был обрезан до MAX_RUN_OUTPUT_BYTES
+    pub stdout_truncated: bool,
+    pub stderr_truncated: bool,
+    pub peak_memory_kb: Option<i64>,
+    pub cpu_time_ms: Option<i64>,
+}
+
+impl ScriptRunRecord {
+    // Запись о завершенном запуске (id назначается при сохранении)
+    pub fn finished(
+        source: ScriptRunSource,
+        started_at: chrono::DateTime<chrono::Utc>,
+        status: RunStatus,
+        exit_code: Option<i32>,
+        stdout: OutputCapture,
+        stderr: OutputCapture,
+        error: Option<String>,
+    ) -> Self {
+        let finished_at = chrono::Utc::now();
+        let (stdout, stdout_truncated) = stdout.finish();
+        let (stderr, stderr_truncated) = stderr.finish();
+
+        ScriptRunRecord {
+            id: 0,
+            source,
+            started_at: utc_timestamp(started_at),
+            finished_at: utc_timestamp(finished_at),
+            duration_ms: (finished_at - started_at).num_milliseconds().max(0),
+            status,
+            exit_code,
+            error,
+            stdout,
+            stderr,
+            stdout_truncated,
+            stderr_truncated,
+            peak_memory_kb: None,
+            cpu_time_ms: None,
+        }
+    }
+
+    // Ресурсы, потребленные скриптом в песочнице
+    pub fn with_usage(mut self, usage: Option<SandboxUsage>) -> Self {
+        self.peak_memory_kb = usage.map(|usage| usage.peak_memory_kb);
+        self.cpu_time_ms = usage.map(|usage| usage.cpu_time_ms);
+        self
+    }
+}
+
+// Отбор запусков
+#[derive(Debug, Clone, Deserialize)]
+pub struct RunHistoryFilter {
+    #[serde(default)]
+    pub script_id: Option<String>,
+    #[serde(default)]
+    pub script_hash: Option<String>,
+    #[serde(default)]
+    pub interpreter: Option<String>,
+    #[serde(default)]
+    pub status: Option<RunStatus>,
+    // Границы времени запуска в RFC 3339
+    #[serde(default)]
+    pub since: Option<String>,
+    #[serde(default)]
+    pub until: Option<String>,
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+}
+
+impl Default for RunHistoryFilter {
+    fn default() -> Self {
+        RunHistoryFilter {
+            script_id: None,
+            script_hash: None,
+            interpreter: None,
+            status: None,
+            since: None,
+            until: None,
+            limit: default_limit(),
+        }
+    }
+}
+
+// Строка сравнения вывода
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffKind {
+    Equal,
+    // Строка есть только в выводе сравниваемого запуска
+    Added,
+    // Строка есть только в выводе базового запуска
+    Removed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiffLine {
+    pub kind: DiffKind,
+    pub text: String,
+}
+
+// Сравнение двух запусков
+#[derive(Debug, Clone, Serialize)]
+pub struct ScriptRunDiff {
+    pub base: ScriptRunSummary,
+    pub other: ScriptRunSummary,
+    // Запускались разные редакции скрипта
+    pub script_changed: bool,
+    pub args_changed: bool,
+    pub stdout: Vec<DiffLine>,
+    pub stderr: Vec<DiffLine>,
+}
+
+fn args_from_json(args: String) -> rusqlite::Result<Vec<String>> {
+    serde_json::from_str(&args).map_err(|e| rusqlite::Error::FromSqlConversionFailure(
+        0, rusqlite::types::Type::Text, Box::new(e),
+    ))
+}
+
+const SUMMARY_COLUMNS: &str = "id, script_id, script_hash, interpreter, args, cwd, started_at, finished_at,
+    duration_ms, status, exit_code, error, length(CAST(stdout AS BLOB)), length(CAST(stderr AS BLOB)),
+    peak_memory_kb, cpu_time_ms";
+
+fn summary_from_row(row: &Row) -> rusqlite::Result<ScriptRunSummary> {
+    Ok(ScriptRunSummary {
+        id: row.get(0)?,
+        source: ScriptRunSource {
+            script_id: row.get(1)?,
+            script_hash: row.get(2)?,
+            interpreter: row.get(3)?,
+            args: args_from_json(row.get(4)?)?,
+            cwd: row.get(5)?,
+        },
+        started_at: row.get(6)?,
+        finished_at: row.get(7)?,
+        duration_ms: row.get(8)?,
+        status: RunStatus::parse(&row.get::<_, String>(9)?),
+        exit_code: row.get(10)?,
+        error: row.get(11)?,
+        stdout_bytes: row.get(12)?,
+        stderr_bytes: row.get(13)?,
+        peak_memory_kb: row.get(14)?,
+        cpu_time_ms: row.get(15)?,
+    })
+}
+
+fn load_summary(conn: &Connection, id: i64) -> Result<ScriptRunSummary, String> {
+    conn.prepare_cached(&format!("SELECT {} FROM script_runs WHERE id = ?", SUMMARY_COLUMNS))
+        .and_then(|mut stmt| stmt.query_row(params![id], summary_from_row).optional())
+        .map_err(|e| format!("Ошибка выполнения запроса: {}", e))?
+        .ok_or_else(|| format!("Запуск {} не найден в истории", id))
+}
+
+fn load_output(conn: &Connection, id: i64) -> Result<(String, String, bool, bool), String> {
+    conn.prepare_cached("SELECT stdout, stderr, stdout_truncated, stderr_truncated FROM script_runs WHERE id = ?")
+        .and_then(|mut stmt| stmt.query_row(params![id], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        }).optional())
+        .map_err(|e| format!("Ошибка выполнения запроса: {}", e))?
+        .ok_or_else(|| format!("Запуск {} не найден в истории", id))
+}
+
+// Сохранение запуска; старые запуски того же скрипта сверх лимита удаляются
+pub fn insert_script_run(conn: &Connection, run: &ScriptRunRecord) -> Result<i64, String> {
+    let args = serde_json::to_string(&run.source.args)
+        .map_err(|e| format!("Ошибка сериализации аргументов: {}", e))?;
+
+    let id = conn.prepare_cached(
+        "INSERT INTO script_runs
+         (script_id, script_hash, interpreter, args, cwd, started_at, finished_at, duration_ms,
+          status, exit_code, stdout, stderr, stdout_truncated, stderr_truncated, error,
+          peak_memory_kb, cpu_time_ms)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .and_then(|mut stmt| stmt.insert(params![
+        run.source.script_id, run.source.script_hash, run.source.interpreter, args, run.source.cwd,
+        run.started_at, run.finished_at, run.duration_ms, run.status.as_str(), run.exit_code,
+        run.stdout, run.stderr, run.stdout_truncated, run.stderr_truncated, run.error,
+        run.peak_memory_kb, run.cpu_time_ms
+    ]))
+    .map_err(|e| format!("Не удалось сохранить запуск скрипта: {}", e))?;
+
+    // Скрипты библиотеки учитываются по id (разные редакции - один скрипт), остальные - по хешу
+    let pruned = match &run.source.script_id {
+        Some(script_id) => conn.prepare_cached(
+            "DELETE FROM script_runs WHERE id IN
+             (SELECT id FROM script_runs WHERE script_id = ? ORDER BY id DESC LIMIT -1 OFFSET ?)",
+        ).and_then(|mut stmt| stmt.execute(params![script_id, MAX_RUNS_PER_SCRIPT])),
+        None => conn.prepare_cached(
+            "DELETE FROM script_runs WHERE id IN
+             (SELECT id FROM script_runs WHERE script_id IS NULL AND script_hash = ? ORDER BY id DESC LIMIT -1 OFFSET ?)",
+        ).and_then(|mut stmt| stmt.execute(params![run.source.script_hash, MAX_RUNS_PER_SCRIPT])),
+    };
+    pruned.map_err(|e| format!("Не удалось удалить старые запуски скрипта: {}", e))?;
+
+    Ok(id)
+}
+
+// Сохранение запуска из исполнителя скриптов: ошибка записи истории не должна
+// влиять на результат запуска, поэтому она только выводится в лог
+pub async fn store_script_run(db: &DbState, run: ScriptRunRecord) -> Option<i64> {
+    match db.run(move |conn| insert_script_run(conn, &run)).await {
+        Ok(id) => Some(id),
+        Err(e) => {
+            eprintln!("[Script Runner] Не удалось записать запуск в историю: {}", e);
+            None
+        }
+    }
+}
+
+// Построчное сравнение текстов по наибольшей общей подпоследовательности
+// Общие начало и конец отбрасываются заранее; если остаток слишком велик,
+// он показывается как удаленный и добавленный целиком
+fn diff_lines(base: &str, other: &str) -> Vec<DiffLine> {
+    let base: Vec<&str> = base.lines().collect();
+    let other: Vec<&str> = other.lines().collect();
+
+    let prefix = base.iter().zip(&other).take_while(|(a, b)| a == b).count();
+    let suffix = base[prefix..].iter().rev()
+        .zip(other[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (a, b) = (&base[prefix..base.len() - suffix], &other[prefix..other.len() - suffix]);
+
+    let line = |kind: DiffKind, text: &str| DiffLine { kind, text: text.to_string() };
+    let mut diff: Vec<DiffLine> = base[..prefix].iter().map(|text| line(DiffKind::Equal, text)).collect();
+
+    if a.len().saturating_mul(b.len()) > MAX_DIFF_CELLS {
+        diff.extend(a.iter().map(|text| line(DiffKind::Removed, text)));
+        diff.extend(b.iter().map(|text| line(DiffKind::Added, text)));
+    } else {
+        // lengths[i * (m + 1) + j] - длина общей подпоследовательности a[i..] и b[j..]
+        let (n, m) = (a.len(), b.len());
+        let mut lengths = vec![0u32; (n + 1) * (m + 1)];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lengths[i * (m + 1) + j] = if a[i] == b[j] {
+                    lengths[(i + 1) * (m + 1) + j + 1] + 1
+                } else {
+                    lengths[(i + 1) * (m + 1) + j].max(lengths[i * (m + 1) + j + 1])
+                };
+            }
+        }
+
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            if i < n && j < m && a[i] == b[j] {
+                diff.push(line(DiffKind::Equal, a[i]));
+                i += 1;
+                j += 1;
+            } else if i < n && (j == m || lengths[(i + 1) * (m + 1) + j] >= lengths[i * (m + 1) + j + 1]) {
+                // При равной длине удаленные строки идут раньше добавленных
+                diff.push(line(DiffKind::Removed, a[i]));
+                i += 1;
+            } else {
+                diff.push(line(DiffKind::Added, b[j]));
+                j += 1;
+            }
+        }
+    }
+
+    diff.extend(base[base.len() - suffix..].iter().map(|text| line(DiffKind::Equal, text)));
+    diff
+}
+
+// Запуски скриптов, новые первыми
+#[tauri::command]
+pub async fn get_script_run_history(db: State<'_, DbState>, filter: Option<RunHistoryFilter>) -> Result<Vec<ScriptRunSummary>, String> {
+    let filter = filter.unwrap_or_default();
+    let since = filter.since.as_deref().map(to_utc_timestamp).transpose()?;
+    let until = filter.until.as_deref().map(to_utc_timestamp).transpose()?;
+
+    db.run(move |conn| {
+        let mut stmt = conn.prepare_cached(&format!(
+            "SELECT {} FROM script_runs
+             WHERE (?1 IS NULL OR script_id = ?1)
+               AND (?2 IS NULL OR script_hash = ?2)
+               AND (?3 IS NULL OR interpreter = ?3)
+               AND (?4 IS NULL OR status = ?4)
+               AND (?5 IS NULL OR started_at >= ?5)
+               AND (?6 IS NULL OR started_at <= ?6)
+             ORDER BY started_at DESC, id DESC
+             LIMIT ?7",
+            SUMMARY_COLUMNS
+        )).map_err(|e| format!("Ошибка подготовки запроса: {}", e))?;
+
+        let runs = stmt.query_map(params![
+            filter.script_id, filter.script_hash, filter.interpreter,
+            filter.status.map(RunStatus::as_str), since, until, filter.limit.max(1) as i64
+        ], summary_from_row)
+        .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+        .map_err(|e| format!("Ошибка выполнения запроса: {}", e))?;
+        Ok(runs)
+    }).await
+}
+
+// Загрузка запуска с сохраненным выводом
+pub fn load_script_run(conn: &Connection, id: i64) -> Result<ScriptRunRecord, String> {
+    let summary = load_summary(conn, id)?;
+    let (stdout, stderr, stdout_truncated, stderr_truncated) = load_output(conn, id)?;
+
+    Ok(ScriptRunRecord {
+        id: summary.id,
+        source: summary.source,
+        started_at: summary.started_at,
+        finished_at: summary.finished_at,
+        duration_ms: summary.duration_ms,
+        status: summary.status,
+        exit_code: summary.exit_code,
+        error: summary.error,
+        stdout,
+        stderr,
+        stdout_truncated,
+        stderr_truncated,
+        peak_memory_kb: summary.peak_memory_kb,
+        cpu_time_ms: summary.cpu_time_ms,
+    })
+}
+
+// Запуск с сохраненным выводом
+#[tauri::command]
+pub async fn get_script_run_record(db: State<'_, DbState>, id: i64) -> Result<ScriptRunRecord, String> {
+    db.run(move |conn| load_script_run(conn, id)).await
+}
+
+// Сравнение вывода двух запусков
+#[tauri::command]
+pub async fn diff_script_runs(db: State<'_, DbState>, base_id: i64, other_id: i64) -> Result<ScriptRunDiff, String> {
+    let (base, other, base_output, other_output) = db.run(move |conn| {
+        Ok((
+            load_summary(conn, base_id)?,
+            load_summary(conn, other_id)?,
+            load_output(conn, base_id)?,
+            load_output(conn, other_id)?,
+        ))
+    }).await?;
+
+    tauri::async_runtime::spawn_blocking(move || ScriptRunDiff {
+        script_changed: base.source.script_hash != other.source.script_hash,
+        args_changed: base.source.args != other.source.args,
+        stdout: diff_lines(&base_output.0, &other_output.0),
+        stderr: diff_lines(&base_output.1, &other_output.1),
+        base,
+        other,
+    })
+    .await
+    .map_err(|e| format!("Ошибка сравнения запусков: {}", e))
+}
+
+// Очистка истории запусков (всей или одного скрипта библиотеки)
+#[tauri::command]
+pub async fn clear_script_run_history(db: State<'_, DbState>, script_id: Option<String>) -> Result<usize, String> {
+    db.run(move |conn| {
+        let deleted = conn.prepare_cached("DELETE FROM script_runs WHERE ?1 IS NULL OR script_id = ?1")
+            .and_then(|mut stmt| stmt.execute(params![script_id]))
+            .map_err(|e| format!("Ошибка очистки истории запусков: {}", e))?;
+
+        println!("[Script Runner] Удалено {} запусков из истории", deleted);
+        Ok(deleted)
+    }).await
+}
@@ -0,0 +1,341 @@
+// Модуль реестра интерпретаторов для запуска скриптов
+// Встроенные интерпретаторы описывают поддерживаемые из коробки языки, пользовательские записи
+// хранятся в настройках приложения и могут переопределять встроенные с тем же id.
+// Команда запуска задается шаблоном с подстановками:
+//   {file} - путь к файлу скрипта, {dir} - его директория, {stem} - имя файла без расширения,
+//   {exe} - расширение исполняемых файлов платформы (".exe" на Windows, иначе пусто),
+//   {args} - аргументы запуска в кавычках PowerShell (для шаблонов, где аргументы нельзя
+//   просто дописать в конец команды); без {args} аргументы добавляются в конец
+
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+use tauri::State;
+
+use crate::utils::db::{get_setting, set_setting, DbState};
+
+// Ключ пользовательских интерпретаторов в app_settings
+const INTERPRETERS_SETTING: &str = "script_interpreters";
+
+// Сколько ждать ответа команды определения версии
+const VERSION_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Кодировка файла скрипта
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScriptEncoding {
+    #[default]
+    Utf8,
+    // UTF-8 с BOM, чтобы Windows PowerShell правильно распознавал кириллицу
+    Utf8Bom,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Interpreter {
+    pub id: String,
+    pub name: String,
+    // Расширение файла скрипта без точки
+    pub extension: String,
+    // Шаблон команды запуска
+    pub command: Vec<String>,
+    // Шаблон команды компиляции, выполняемой перед запуском
+    #[serde(default)]
+    pub compile: Option<Vec<String>>,
+    #[serde(default)]
+    pub encoding: ScriptEncoding,
+    // Команда, печатающая версию интерпретатора
+    pub version_command: Vec<String>,
+    #[serde(default)]
+    pub builtin: bool,
+}
+
+// Интерпретатор и результат проверки его наличия в системе
+#[derive(Debug, Clone, Serialize)]
+pub struct InterpreterStatus {
+    #[serde(flatten)]
+    pub interpreter: Interpreter,
+    pub installed: bool,
+    pub version: Option<String>,
+}
+
+fn strings(items: &[&str]) -> Vec<String> {
+    items.iter().map(|item| item.to_string()).collect()
+}
+
+fn builtin(id: &str, name: &str, extension: &str, command: &[&str], version_command: &[&str]) -> Interpreter {
+    Interpreter {
+        id: id.to_string(),
+        name: name.to_string(),
+        extension: extension.to_string(),
+        command: strings(command),
+        compile: None,
+        encoding: ScriptEncoding::Utf8,
+        version_command: strings(version_command),
+        builtin: true,
+    }
+}
+
+// Встроенные интерпретаторы
+pub fn builtin_interpreters() -> Vec<Interpreter> {
+    vec![
+        builtin("python", "Python", "py", &["python", "-u", "{file}"], &["python", "--version"]),
+        Interpreter {
+            encoding: ScriptEncoding::Utf8Bom,
+            ..builtin(
+                "powershell",
+                "PowerShell",
+                "ps1",
+                &[
+                    "powershell",
+                    "-ExecutionPolicy",
+                    "Bypass",
+                    "-Command",
+                    "$OutputEncoding = [System.Text.Encoding]::UTF8; & '{file}' {args}",
+                ],
+                &["powershell", "-NoProfile", "-Command", "$PSVersionTable.PSVersion.ToString()"],
+            )
+        },
+        builtin("shell", "Bash", "sh", &["bash", "{file}"], &["bash", "--version"]),
+        builtin("node", "Node.js", "js", &["node", "{file}"], &["node", "--version"]),
+        builtin("deno", "Deno", "ts", &["deno", "run", "--allow-all", "{file}"], &["deno", "--version"]),
+        builtin("ruby", "Ruby", "rb", &["ruby", "{file}"], &["ruby", "--version"]),
+        builtin("perl", "Perl", "pl", &["perl", "{file}"], &["perl", "--version"]),
+        builtin("lua", "Lua", "lua", &["lua", "{file}"], &["lua", "-v"]),
+        builtin("php", "PHP", "php", &["php", "{file}"], &["php", "--version"]),
+        builtin("go", "Go", "go", &["go", "run", "{file}"], &["go", "version"]),
+        Interpreter {
+            compile: Some(strings(&["rustc", "--edition", "2021", "{file}", "-o", "{dir}/{stem}{exe}"])),
+            ..builtin("rust", "Rust", "rs", &["{dir}/{stem}{exe}"], &["rustc", "--version"])
+        },
+    ]
+}
+
+impl Interpreter {
+    fn validate(&self) -> Result<(), String> {
+        let valid_id = !self.id.is_empty()
+            && self.id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid_id {
+            return Err(format!("Некорректный id интерпретатора {:?}: допустимы латинские буквы, цифры, - и _", self.id));
+        }
+        if self.extension.is_empty() || self.extension.contains(['.', '/', '\\']) {
+            return Err(format!("Некорректное расширение {:?}", self.extension));
+        }
+        // Первый элемент шаблона - запускаемая программа
+        let has_program = |template: &[String]| template.first().is_some_and(|program| !program.is_empty());
+        if !has_program(&self.command) {
+            return Err("Команда запуска не задана".to_string());
+        }
+        if self.compile.as_deref().is_some_and(|compile| !has_program(compile)) {
+            return Err("Команда компиляции не задана".to_string());
+        }
+        let uses_file = |template: &[String]| template.iter().any(|part| part.contains("{file}"));
+        if !uses_file(&self.command) && !self.compile.as_deref().is_some_and(uses_file) {
+            return Err("Команда запуска или компиляции должна содержать {file}".to_string());
+        }
+        if !has_program(&self.version_command) {
+            return Err("Команда определения версии не задана".to_string());
+        }
+        Ok(())
+    }
+
+    // Подстановка пути к скрипту и аргументов в шаблон команды
+    pub fn render(template: &[String], file: &Path, args: &[String]) -> Vec<String> {
+        let dir = file.parent().map(|dir| dir.to_string_lossy().to_string()).unwrap_or_default();
+        let stem = file.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+        let exe = if cfg!(target_os = "windows") { ".exe" } else { "" };
+        let quoted_args = args
+            .iter()
+            .map(|arg| format!("'{}'", arg.replace('\'', "''")))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let mut rendered: Vec<String> = template
+            .iter()
+            .map(|part| {
+                part.replace("{file}", &file.to_string_lossy())
+                    .replace("{dir}", &dir)
+                    .replace("{stem}", &stem)
+                    .replace("{exe}", exe)
+                    .replace("{args}", &quoted_args)
+            })
+            .collect();
+
+        if !template.iter().any(|part| part.contains("{args}")) {
+            rendered.extend(args.iter().cloned());
+        }
+        rendered
+    }
+}
+
+fn user_interpreters(conn: &Connection) -> Result<Vec<Interpreter>, String> {
+    Ok(get_setting(conn, INTERPRETERS_SETTING)?.unwrap_or_default())
+}
+
+// Все интерпретаторы: встроенные (с учетом переопределений) и пользовательские
+pub fn load_interpreters(conn: &Connection) -> Result<Vec<Interpreter>, String> {
+    let mut interpreters = builtin_interpreters();
+
+    for mut interpreter in user_interpreters(conn)? {
+        // Настройки могли прийти из импорта или старой версии без проверки:
+        // некорректный интерпретатор пропускается, а не ломает запуск
+        if let Err(e) = interpreter.validate() {
+            eprintln!("[Script Runner] Интерпретатор {:?} пропущен: {}", interpreter.id, e);
+            continue;
+        }
+        interpreter.builtin = false;
+        match interpreters.iter_mut().find(|existing| existing.id == interpreter.id) {
+            Some(existing) => *existing = interpreter,
+            None => interpreters.push(interpreter),
+        }
+    }
+
+    Ok(interpreters)
+}
+
+pub fn find_interpreter(conn: &Connection, id: &str) -> Result<Interpreter, String> {
+    load_interpreters(conn)?
+        .into_iter()
+        .find(|interpreter| interpreter.id == id)
+        .ok_or_else(|| format!("Неподдерживаемый язык: {}", id))
+}
+
+// Версия интерпретатора (первая непустая строка вывода команды версии) или None, если он не установлен
+async fn detect_version(interpreter: &Interpreter) -> Option<String> {
+    let output = tokio::time::timeout(
+        VERSION_TIMEOUT,
+        tokio::process::Command::new(&interpreter.version_command[0])
+            .args(&interpreter.version_command[1..])
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await
+    .ok()?
+    .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    // Некоторые интерпретаторы (lua, старый python) печатают версию в stderr
+    [&output.stdout, &output.stderr]
+        .iter()
+        .flat_map(|stream| String::from_utf8_lossy(stream).lines().map(str::trim).map(str::to_string).collect::<Vec<_>>())
+        .find(|line| !line.is_empty())
+        .or_else(|| Some(String::new()))
+}
+
+// Список интерпретаторов с проверкой их наличия в системе
+#[tauri::command]
+pub async fn list_interpreters(db: State<'_, DbState>) -> Result<Vec<InterpreterStatus>, String> {
+    let interpreters = db.run(|conn| load_interpreters(conn)).await?;
+
+    let mut checks = tokio::task::JoinSet::new();
+    for (index, interpreter) in interpreters.iter().cloned().enumerate() {
+        checks.spawn(async move { (index, detect_version(&interpreter).await) });
+    }
+
+    let mut versions = vec![None; interpreters.len()];
+    while let Some(result) = checks.join_next().await {
+        if let Ok((index, version)) = result {
+            versions[index] = version;
+        }
+    }
+
+    Ok(interpreters
+        .into_iter()
+        .zip(versions)
+        .map(|(interpreter, version)| InterpreterStatus {
+            interpreter,
+            installed: version.is_some(),
+            version: version.filter(|version| !version.is_empty()),
+        })
+        .collect())
+}
+
+// Добавление или изменение пользовательского интерпретатора
+// Запись с id встроенного интерпретатора переопределяет его
+#[tauri::command]
+pub async fn save_interpreter(db: State<'_, DbState>, interpreter: Interpreter) -> Result<(), String> {
+    interpreter.validate()?;
+
+    db.run(move |conn| {
+        let mut interpreters = user_interpreters(conn)?;
+        let interpreter = Interpreter { builtin: false, ..interpreter };
+
+        match interpreters.iter_mut().find(|existing| existing.id == interpreter.id) {
+            Some(existing) => *existing = interpreter.clone(),
+            None => interpreters.push(interpreter.clone()),
+        }
+        set_setting(conn, INTERPRETERS_SETTING, &interpreters)?;
+
+        println!("[Script Runner] Сохранен интерпретатор {} ({})", interpreter.id, interpreter.name);
+        Ok(())
+    }).await
+}
+
+// Удаление пользовательского интерпретатора (для переопределения - возврат к встроенному)
+#[tauri::command]
+pub async fn delete_interpreter(db: State<'_, DbState>, id: String) -> Result<(), String> {
+    db.run(move |conn| {
+        let mut interpreters = user_interpreters(conn)?;
+        let count = interpreters.len();
+        interpreters.retain(|interpreter| interpreter.id != id);
+
+        if interpreters.len() == count {
+            return Err(format!("Пользовательский интерпретатор {} не найден", id));
+        }
+        set_setting(conn, INTERPRETERS_SETTING, &interpreters)?;
+
+        println!("[Script Runner] Удален интерпретатор {}", id);
+        Ok(())
+    }).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn custom(command: &[&str], compile: Option<&[&str]>) -> Interpreter {
+        Interpreter {
+            compile: compile.map(strings),
+            builtin: false,
+            ..builtin("custom", "Custom", "x", command, &["custom", "--version"])
+        }
+    }
+
+    #[test]
+    fn builtin_interpreters_are_valid() {
+        for interpreter in builtin_interpreters() {
+            assert!(interpreter.validate().is_ok(), "{}", interpreter.id);
+        }
+    }
+
+    #[test]
+    fn empty_templates_are_rejected() {
+        assert!(custom(&["run", "{file}"], None).validate().is_ok());
+        assert!(custom(&[], None).validate().is_err());
+        assert!(custom(&["", "{file}"], None).validate().is_err());
+        assert!(custom(&["{dir}/{stem}"], Some(&[])).validate().is_err());
+        assert!(custom(&["{dir}/{stem}"], Some(&["", "{file}"])).validate().is_err());
+        assert!(custom(&["{dir}/{stem}"], Some(&["cc", "{file}"])).validate().is_ok());
+    }
+
+    #[test]
+    fn invalid_user_interpreters_are_skipped() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE app_settings (key TEXT PRIMARY KEY, value TEXT NOT NULL, updated_at TEXT NOT NULL)", []).unwrap();
+        let broken = Interpreter { id: "broken".to_string(), ..custom(&[], None) };
+        let rust = Interpreter { id: "rust".to_string(), ..custom(&["{dir}/{stem}"], Some(&[])) };
+        set_setting(&conn, INTERPRETERS_SETTING, &vec![custom(&["run", "{file}"], None), broken, rust]).unwrap();
+
+        let interpreters = load_interpreters(&conn).unwrap();
+        assert!(interpreters.iter().any(|interpreter| interpreter.id == "custom"));
+        assert!(!interpreters.iter().any(|interpreter| interpreter.id == "broken"));
+        // Некорректное переопределение не заменяет встроенный интерпретатор
+        let rust = interpreters.iter().find(|interpreter| interpreter.id == "rust").unwrap();
+        assert!(rust.builtin);
+    }
+}
@@ -0,0 +1,416 @@
+// Модуль библиотеки скриптов
+// Скрипты хранятся в SQLite (таблица scripts) вместе с историей версий (script_versions):
+// каждое изменение имени, языка, текста или тегов сохраняет новую версию, старые версии
+// можно просмотреть и восстановить. Поиск по имени, тексту и тегам идет по индексу FTS5
+// с триграммным токенизатором (scripts_fts), короткие запросы ищутся простым сравнением
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+use uuid::Uuid;
+
+use crate::utils::db::{utc_timestamp, DbState};
+use crate::utils::script_interpreters::find_interpreter;
+
+// Сколько версий одного скрипта хранить (более старые удаляются)
+const MAX_SCRIPT_VERSIONS: i64 = 100;
+
+// Минимальная длина запроса для поиска по триграммному индексу
+const MIN_INDEXED_QUERY: usize = 3;
+
+// Размер фрагмента с совпадением в результатах поиска (в токенах)
+const SNIPPET_TOKENS: i64 = 16;
+
+fn default_limit() -> usize {
+    50
+}
+
+// Скрипт библиотеки
+#[derive(Debug, Clone, Serialize)]
+pub struct LibraryScript {
+    pub id: String,
+    pub name: String,
+    // id интерпретатора
+    pub language: String,
+    pub content: String,
+    pub tags: Vec<String>,
+    // Номер текущей версии
+    pub version: i64,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+// Данные скрипта при создании и изменении
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScriptDraft {
+    pub name: String,
+    pub language: String,
+    pub content: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+// Сохраненная версия скрипта
+#[derive(Debug, Clone, Serialize)]
+pub struct ScriptVersion {
+    pub script_id: String,
+    pub version: i64,
+    pub name: String,
+    pub language: String,
+    pub content: String,
+    pub tags: Vec<String>,
+    pub created_at: String,
+}
+
+// Отбор скриптов по языку и тегу
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ScriptFilter {
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub tag: Option<String>,
+}
+
+// Параметры поиска по библиотеке
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScriptSearchQuery {
+    pub query: String,
+    #[serde(flatten)]
+    pub filter: ScriptFilter,
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+}
+
+// Найденный скрипт и фрагмент текста с совпадением
+#[derive(Debug, Clone, Serialize)]
+pub struct ScriptSearchHit {
+    #[serde(flatten)]
+    pub script: LibraryScript,
+    // Фрагмент есть только у результатов поиска по индексу
+    pub snippet: Option<String>,
+}
+
+impl ScriptDraft {
+    // Проверка и нормализация: имя без пробелов по краям, теги без повторов и пустых
+    fn normalize(self, conn: &Connection) -> Result<ScriptDraft, String> {
+        let name = self.name.trim().to_string();
+        if name.is_empty() {
+            return Err("Имя скрипта не задано".to_string());
+        }
+        find_interpreter(conn, &self.language)?;
+
+        let mut tags: Vec<String> = Vec::new();
+        for tag in self.tags.iter().map(|tag| tag.trim()).filter(|tag| !tag.is_empty()) {
+            if !tags.iter().any(|existing| existing == tag) {
+                tags.push(tag.to_string());
+            }
+        }
+
+        Ok(ScriptDraft { name, tags, ..self })
+    }
+}
+
+fn tags_to_json(tags: &[String]) -> Result<String, String> {
+    serde_json::to_string(tags).map_err(|e| format!("Ошибка сериализации тегов: {}", e))
+}
+
+fn tags_from_json(tags: String) -> rusqlite::Result<Vec<String>> {
+    serde_json::from_str(&tags).map_err(|e| rusqlite::Error::FromSqlConversionFailure(
+        0, rusqlite::types::Type::Text, Box::new(e),
+    ))
+}
+
+const SCRIPT_COLUMNS: &str = "s.id, s.name, s.language, s.content, s.tags, s.version, s.created_at, s.updated_at";
+
+fn script_from_row(row: &Row) -> rusqlite::Result<LibraryScript> {
+    Ok(LibraryScript {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        language: row.get(2)?,
+        content: row.get(3)?,
+        tags: tags_from_json(row.get(4)?)?,
+        version: row.get(5)?,
+        created_at: row.get(6)?,
+        updated_at: row.get(7)?,
+    })
+}
+
+pub fn load_script(conn: &Connection, id: &str) -> Result<LibraryScript, String> {
+    conn.prepare_cached(&format!("SELECT {} FROM scripts s WHERE s.id = ?", SCRIPT_COLUMNS))
+        .and_then(|mut stmt| stmt.query_row(params![id], script_from_row).optional())
+        .map_err(|e| format!("Ошибка выполнения запроса: {}", e))?
+        .ok_or_else(|| format!("Скрипт {} не найден", id))
+}
+
+// Запись версии скрипта и удаление версий сверх лимита
+fn insert_version(conn: &Connection, script: &LibraryScript) -> Result<(), String> {
+    let tags = tags_to_json(&script.tags)?;
+    conn.prepare_cached(
+        "INSERT INTO script_versions (script_id, version, name, language, content, tags, created_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?)",
+    )
+    .and_then(|mut stmt| stmt.execute(params![
+        script.id, script.version, script.name, script.language,
+        script.content, tags, script.updated_at
+    ]))
+    .map_err(|e| format!("Не удалось сохранить версию скрипта: {}", e))?;
+
+    conn.prepare_cached("DELETE FROM script_versions WHERE script_id = ? AND version <= ?")
+        .and_then(|mut stmt| stmt.execute(params![script.id, script.version - MAX_SCRIPT_VERSIONS]))
+        .map_err(|e| format!("Не удалось удалить старые версии скрипта: {}", e))?;
+
+    Ok(())
+}
+
+// Новая версия скрипта; если ничего не изменилось, версия не создается
+fn update_script_in(conn: &Connection, id: &str, draft: ScriptDraft) -> Result<LibraryScript, String> {
+    let current = load_script(conn, id)?;
+    let draft = draft.normalize(conn)?;
+
+    let unchanged = current.name == draft.name
+        && current.language == draft.language
+        && current.content == draft.content
+        && current.tags == draft.tags;
+    if unchanged {
+        return Ok(current);
+    }
+
+    let script = LibraryScript {
+        name: draft.name,
+        language: draft.language,
+        content: draft.content,
+        tags: draft.tags,
+        version: current.version + 1,
+        updated_at: utc_timestamp(chrono::Utc::now()),
+        ..current
+    };
+
+    let tags = tags_to_json(&script.tags)?;
+    conn.prepare_cached(
+        "UPDATE scripts SET name = ?, language = ?, content = ?, tags = ?, version = ?, updated_at = ? WHERE id = ?",
+    )
+    .and_then(|mut stmt| stmt.execute(params![
+        script.name, script.language, script.content, tags,
+        script.version, script.updated_at, script.id
+    ]))
+    .map_err(|e| format!("Не удалось сохранить скрипт: {}", e))?;
+    insert_version(conn, &script)?;
+
+    Ok(script)
+}
+
+// Условия отбора по языку (?1) и тегу (?2) для запросов по таблице scripts с псевдонимом s
+const FILTER_CONDITION: &str = "(?1 IS NULL OR s.language = ?1)
+     AND (?2 IS NULL OR EXISTS (SELECT 1 FROM json_each(s.tags) WHERE json_each.value = ?2))";
+
+// Список скриптов библиотеки, последние измененные первыми
+#[tauri::command]
+pub async fn list_library_scripts(db: State<'_, DbState>, filter: Option<ScriptFilter>) -> Result<Vec<LibraryScript>, String> {
+    let filter = filter.unwrap_or_default();
+
+    db.run(move |conn| {
+        let mut stmt = conn.prepare_cached(&format!(
+            "SELECT {} FROM scripts s WHERE {} ORDER BY s.updated_at DESC, s.name",
+            SCRIPT_COLUMNS, FILTER_CONDITION
+        )).map_err(|e| format!("Ошибка подготовки запроса: {}", e))?;
+
+        let scripts = stmt.query_map(params![filter.language, filter.tag], script_from_row)
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+            .map_err(|e| format!("Ошибка выполнения запроса: {}", e))?;
+        Ok(scripts)
+    }).await
+}
+
+#[tauri::command]
+pub async fn get_library_script(db: State<'_, DbState>, id: String) -> Result<LibraryScript, String> {
+    db.run(move |conn| load_script(conn, &id)).await
+}
+
+#[tauri::command]
+pub async fn create_library_script(db: State<'_, DbState>, script: ScriptDraft) -> Result<LibraryScript, String> {
+    db.run(move |conn| {
+        let tx = conn.transaction()
+            .map_err(|e| format!("Ошибка создания транзакции: {}", e))?;
+        let draft = script.normalize(&tx)?;
+        let now = utc_timestamp(chrono::Utc::now());
+
+        let script = LibraryScript {
+            id: Uuid::new_v4().to_string(),
+            name: draft.name,
+            language: draft.language,
+            content: draft.content,
+            tags: draft.tags,
+            version: 1,
+            created_at: now.clone(),
+            updated_at: now,
+        };
+
+        let tags = tags_to_json(&script.tags)?;
+        tx.prepare_cached(
+            "INSERT INTO scripts (id, name, language, content, tags, version, created_at, updated_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .and_then(|mut stmt| stmt.execute(params![
+            script.id, script.name, script.language, script.content,
+            tags, script.version, script.created_at, script.updated_at
+        ]))
+        .map_err(|e| format!("Не удалось сохранить скрипт: {}", e))?;
+        insert_version(&tx, &script)?;
+
+        tx.commit()
+            .map_err(|e| format!("Ошибка фиксации транзакции: {}", e))?;
+
+        println!("[Script Library] Создан скрипт {} ({})", script.name, script.id);
+        Ok(script)
+    }).await
+}
+
+#[tauri::command]
+pub async fn update_library_script(db: State<'_, DbState>, id: String, script: ScriptDraft) -> Result<LibraryScript, String> {
+    db.run(move |conn| {
+        let tx = conn.transaction()
+            .map_err(|e| format!("Ошибка создания транзакции: {}", e))?;
+        let script = update_script_in(&tx, &id, script)?;
+        tx.commit()
+            .map_err(|e| format!("Ошибка фиксации транзакции: {}", e))?;
+        Ok(script)
+    }).await
+}
+
+#[tauri::command]
+pub async fn delete_library_script(db: State<'_, DbState>, id: String) -> Result<(), String> {
+    db.run(move |conn| {
+        let tx = conn.transaction()
+            .map_err(|e| format!("Ошибка создания транзакции: {}", e))?;
+
+        tx.prepare_cached("DELETE FROM script_schedules WHERE script_id = ?")
+            .and_then(|mut stmt| stmt.execute(params![id]))
+            .map_err(|e| format!("Ошибка удаления расписаний скрипта: {}", e))?;
+
+        tx.prepare_cached("DELETE FROM script_versions WHERE script_id = ?")
+            .and_then(|mut stmt| stmt.execute(params![id]))
+            .map_err(|e| format!("Ошибка удаления версий скрипта: {}", e))?;
+
+        let deleted = tx.prepare_cached("DELETE FROM scripts WHERE id = ?")
+            .and_then(|mut stmt| stmt.execute(params![id]))
+            .map_err(|e| format!("Ошибка удаления скрипта: {}", e))?;
+        if deleted == 0 {
+            return Err(format!("Скрипт {} не найден", id));
+        }
+
+        tx.commit()
+            .map_err(|e| format!("Ошибка фиксации транзакции: {}", e))?;
+
+        println!("[Script Library] Удален скрипт {}", id);
+        Ok(())
+    }).await
+}
+
+// История версий скрипта, новые первыми
+#[tauri::command]
+pub async fn list_library_script_versions(db: State<'_, DbState>, id: String) -> Result<Vec<ScriptVersion>, String> {
+    db.run(move |conn| {
+        load_script(conn, &id)?;
+
+        let mut stmt = conn.prepare_cached(
+            "SELECT script_id, version, name, language, content, tags, created_at
+             FROM script_versions WHERE script_id = ? ORDER BY version DESC",
+        ).map_err(|e| format!("Ошибка подготовки запроса: {}", e))?;
+
+        let versions = stmt.query_map(params![id], |row| {
+            Ok(ScriptVersion {
+                script_id: row.get(0)?,
+                version: row.get(1)?,
+                name: row.get(2)?,
+                language: row.get(3)?,
+                content: row.get(4)?,
+                tags: tags_from_json(row.get(5)?)?,
+                created_at: row.get(6)?,
+            })
+        })
+        .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+        .map_err(|e| format!("Ошибка выполнения запроса: {}", e))?;
+        Ok(versions)
+    }).await
+}
+
+// Восстановление версии: ее содержимое сохраняется как новая версия скрипта
+#[tauri::command]
+pub async fn restore_library_script_version(db: State<'_, DbState>, id: String, version: i64) -> Result<LibraryScript, String> {
+    db.run(move |conn| {
+        let tx = conn.transaction()
+            .map_err(|e| format!("Ошибка создания транзакции: {}", e))?;
+
+        let draft = tx.prepare_cached(
+            "SELECT name, language, content, tags FROM script_versions WHERE script_id = ? AND version = ?",
+        )
+        .and_then(|mut stmt| stmt.query_row(params![id, version], |row| {
+            Ok(ScriptDraft {
+                name: row.get(0)?,
+                language: row.get(1)?,
+                content: row.get(2)?,
+                tags: tags_from_json(row.get(3)?)?,
+            })
+        }).optional())
+        .map_err(|e| format!("Ошибка выполнения запроса: {}", e))?
+        .ok_or_else(|| format!("Версия {} скрипта {} не найдена", version, id))?;
+
+        let script = update_script_in(&tx, &id, draft)?;
+        tx.commit()
+            .map_err(|e| format!("Ошибка фиксации транзакции: {}", e))?;
+
+        println!("[Script Library] Скрипт {} восстановлен из версии {}", id, version);
+        Ok(script)
+    }).await
+}
+
+// Поиск по имени, тексту и тегам скриптов
+// Запросы от MIN_INDEXED_QUERY символов ищутся по индексу с ранжированием по релевантности,
+// более короткие - подстрокой без учета регистра
+#[tauri::command]
+pub async fn search_library_scripts(db: State<'_, DbState>, query: ScriptSearchQuery) -> Result<Vec<ScriptSearchHit>, String> {
+    let text = query.query.trim().to_string();
+    if text.is_empty() {
+        return Err("Пустой поисковый запрос".to_string());
+    }
+    let limit = query.limit.max(1) as i64;
+    let filter = query.filter;
+
+    db.run(move |conn| {
+        let indexed = text.chars().count() >= MIN_INDEXED_QUERY;
+        let sql = if indexed {
+            format!(
+                "SELECT {}, snippet(scripts_fts, -1, '', '', '…', {}) FROM scripts_fts f
+                 JOIN scripts s ON s.id = f.script_id
+                 WHERE scripts_fts MATCH ?3 AND {}
+                 ORDER BY f.rank LIMIT ?4",
+                SCRIPT_COLUMNS, SNIPPET_TOKENS, FILTER_CONDITION
+            )
+        } else {
+            format!(
+                "SELECT {}, NULL FROM scripts s
+                 WHERE (instr(lower(s.name), lower(?3)) OR instr(lower(s.content), lower(?3)) OR instr(lower(s.tags), lower(?3)))
+                 AND {}
+                 ORDER BY s.updated_at DESC LIMIT ?4",
+                SCRIPT_COLUMNS, FILTER_CONDITION
+            )
+        };
+        let pattern = if indexed {
+            format!("\"{}\"", text.replace('"', "\"\""))
+        } else {
+            text
+        };
+
+        let mut stmt = conn.prepare_cached(&sql)
+            .map_err(|e| format!("Ошибка подготовки запроса: {}", e))?;
+        let hits = stmt.query_map(params![filter.language, filter.tag, pattern, limit], |row| {
+            Ok(ScriptSearchHit {
+                script: script_from_row(row)?,
+                snippet: row.get(8)?,
+            })
+        })
+        .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+        .map_err(|e| format!("Ошибка поиска скриптов: {}", e))?;
+        Ok(hits)
+    }).await
+}
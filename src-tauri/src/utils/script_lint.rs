@@ -0,0 +1,408 @@
+// Модуль статической проверки скриптов
+// lint_script проверяет скрипт инструментами, установленными в системе, и приводит их вывод
+// к общему виду - диагностикам со строкой, колонкой, важностью и сообщением, которые редактор
+// показывает прямо в тексте:
+//   python     - компиляция (то же, что python -m py_compile), затем pyflakes;
+//   shell      - bash -n, затем shellcheck;
+//   powershell - парсер PowerShell (System.Management.Automation.Language.Parser).
+// Второй инструмент запускается, только если синтаксических ошибок нет: иначе он повторил бы
+// те же ошибки. Строки и колонки считаются с 1. Для остальных языков проверка не выполняется
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Output, Stdio};
+use std::time::Duration;
+use tauri::State;
+use tempfile::tempdir;
+
+use crate::utils::db::DbState;
+use crate::utils::script_interpreters::{find_interpreter, Interpreter, ScriptEncoding};
+
+// Сколько ждать завершения инструмента проверки
+const LINT_TIMEOUT: Duration = Duration::from_secs(20);
+
+// Проверка синтаксиса Python: ошибка выводится строкой "строка<TAB>колонка<TAB>сообщение"
+const PYTHON_SYNTAX_CHECK: &str = "import sys
+path = sys.argv[1]
+with open(path, 'rb') as source:
+    code = source.read()
+try:
+    compile(code, path, 'exec')
+except SyntaxError as e:
+    print('%d\\t%d\\t%s: %s' % (e.lineno or 1, e.offset or 1, type(e).__name__, e.msg))
+except ValueError as e:
+    print('1\\t1\\t%s' % e)
+";
+
+// Ошибки разбора PowerShell в том же формате; путь к файлу - в переменной окружения
+const POWERSHELL_PARSE_CHECK: &str = "[Console]::OutputEncoding = [System.Text.Encoding]::UTF8
+$tokens = $null; $errors = $null
+[System.Management.Automation.Language.Parser]::ParseFile($env:LINT_SCRIPT_PATH, [ref]$tokens, [ref]$errors) | Out-Null
+foreach ($e in $errors) { \"{0}`t{1}`t{2}\" -f $e.Extent.StartLineNumber, $e.Extent.StartColumnNumber, $e.Message }";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LintSeverity {
+    Error,
+    Warning,
+    Info,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LintDiagnostic {
+    pub line: u32,
+    pub column: u32,
+    pub severity: LintSeverity,
+    pub message: String,
+    // Инструмент, сообщивший о проблеме
+    pub source: String,
+    // Код правила (например, SC2086 у shellcheck)
+    pub code: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LintReport {
+    // Инструменты, которыми проверен скрипт
+    pub tools: Vec<String>,
+    // Инструменты, которые не найдены в системе
+    pub missing: Vec<String>,
+    pub diagnostics: Vec<LintDiagnostic>,
+}
+
+// Замечание shellcheck в формате json1
+#[derive(Debug, Deserialize)]
+struct ShellcheckComment {
+    line: u32,
+    column: u32,
+    level: String,
+    code: u32,
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ShellcheckOutput {
+    comments: Vec<ShellcheckComment>,
+}
+
+fn diagnostic(line: u32, column: u32, severity: LintSeverity, message: &str, source: &str) -> LintDiagnostic {
+    LintDiagnostic {
+        line: line.max(1),
+        column: column.max(1),
+        severity,
+        message: message.trim().to_string(),
+        source: source.to_string(),
+        code: None,
+    }
+}
+
+// Запуск инструмента проверки; None - инструмент не установлен
+async fn run_tool(program: &str, args: &[&str], env: &[(&str, &str)]) -> Result<Option<Output>, String> {
+    let output = tokio::time::timeout(
+        LINT_TIMEOUT,
+        tokio::process::Command::new(program)
+            .args(args)
+            .envs(env.iter().copied())
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await
+    .map_err(|_| format!("Проверка {} не завершилась за {} с", program, LINT_TIMEOUT.as_secs()))?;
+
+    match output {
+        Ok(output) => Ok(Some(output)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("Ошибка при запуске {}: {}", program, e)),
+    }
+}
+
+// Запуск первой найденной в системе программы из списка
+async fn run_first(programs: &[&str], args: &[&str], env: &[(&str, &str)]) -> Result<Option<(String, Output)>, String> {
+    for program in programs {
+        if let Some(output) = run_tool(program, args, env).await? {
+            return Ok(Some((program.to_string(), output)));
+        }
+    }
+    Ok(None)
+}
+
+// Строки "строка<TAB>колонка<TAB>сообщение" из проверок синтаксиса Python и PowerShell
+fn parse_tab_separated(output: &str, source: &str) -> Vec<LintDiagnostic> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, '\t');
+            let line = parts.next()?.trim().parse().ok()?;
+            let column = parts.next()?.trim().parse().ok()?;
+            Some(diagnostic(line, column, LintSeverity::Error, parts.next()?, source))
+        })
+        .collect()
+}
+
+// Вывод pyflakes: "файл:строка:колонка: сообщение" (старые версии - без колонки)
+fn parse_pyflakes(output: &str, file: &str) -> Vec<LintDiagnostic> {
+    output
+        .lines()
+        .filter_map(|line| line.strip_prefix(file)?.strip_prefix(':'))
+        .filter_map(|rest| {
+            let (line, rest) = rest.split_once(':')?;
+            let line = line.parse().ok()?;
+            let (column, message) = match rest.split_once(':') {
+                Some((column, message)) if column.parse::<u32>().is_ok() => (column.parse().ok()?, message),
+                _ => (1, rest),
+            };
+            Some(diagnostic(line, column, LintSeverity::Warning, message, "pyflakes"))
+        })
+        .collect()
+}
+
+// Вывод bash -n: "файл: line N: сообщение". После ошибки "syntax error near unexpected token"
+// bash повторяет ошибочную строку скрипта в кавычках `...' - это не отдельная ошибка
+fn parse_bash(output: &str) -> Vec<LintDiagnostic> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (_, rest) = line.split_once(": line ")?;
+            let (line, message) = rest.split_once(": ")?;
+            if message.starts_with('`') && message.ends_with('\'') {
+                return None;
+            }
+            let (severity, message) = match message.strip_prefix("warning: ") {
+                Some(message) => (LintSeverity::Warning, message),
+                None => (LintSeverity::Error, message),
+            };
+            Some(diagnostic(line.parse().ok()?, 1, severity, message, "bash"))
+        })
+        .collect()
+}
+
+fn parse_shellcheck(output: &str) -> Result<Vec<LintDiagnostic>, String> {
+    let output: ShellcheckOutput = serde_json::from_str(output)
+        .map_err(|e| format!("Не удалось разобрать вывод shellcheck: {}", e))?;
+
+    Ok(output.comments.into_iter().map(|comment| {
+        let severity = match comment.level.as_str() {
+            "error" => LintSeverity::Error,
+            "warning" => LintSeverity::Warning,
+            _ => LintSeverity::Info,
+        };
+        LintDiagnostic {
+            code: Some(format!("SC{}", comment.code)),
+            ..diagnostic(comment.line, comment.column, severity, &comment.message, "shellcheck")
+        }
+    }).collect())
+}
+
+fn stderr_text(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).trim().to_string()
+}
+
+async fn lint_python(interpreter: &Interpreter, file: &str, report: &mut LintReport) -> Result<(), String> {
+    // python из реестра интерпретаторов; в Linux часто установлен только python3
+    let programs = [interpreter.command[0].as_str(), "python3"];
+    let env = [("PYTHONIOENCODING", "utf-8")];
+    let (python, output) = match run_first(&programs, &["-c", PYTHON_SYNTAX_CHECK, file], &env).await? {
+        Some(found) => found,
+        None => {
+            report.missing.push("python".to_string());
+            return Ok(());
+        }
+    };
+    if !output.status.success() {
+        return Err(format!("Ошибка проверки синтаксиса Python: {}", stderr_text(&output)));
+    }
+    report.tools.push("py_compile".to_string());
+    report.diagnostics.extend(parse_tab_separated(&String::from_utf8_lossy(&output.stdout), "py_compile"));
+    if !report.diagnostics.is_empty() {
+        return Ok(());
+    }
+
+    let output = run_tool(&python, &["-m", "pyflakes", file], &env).await?;
+    match output {
+        // pyflakes завершается с кодом 1, если нашел замечания
+        Some(output) if !stderr_text(&output).contains("No module named pyflakes") => {
+            report.tools.push("pyflakes".to_string());
+            report.diagnostics.extend(parse_pyflakes(&String::from_utf8_lossy(&output.stdout), file));
+        }
+        _ => report.missing.push("pyflakes".to_string()),
+    }
+    Ok(())
+}
+
+async fn lint_shell(interpreter: &Interpreter, file: &str, report: &mut LintReport) -> Result<(), String> {
+    // Сообщения bash на английском, чтобы их можно было разобрать
+    let env = [("LC_ALL", "C")];
+    match run_tool(&interpreter.command[0], &["-n", file], &env).await? {
+        Some(output) => {
+            report.tools.push("bash".to_string());
+            report.diagnostics.extend(parse_bash(&String::from_utf8_lossy(&output.stderr)));
+        }
+        None => report.missing.push("bash".to_string()),
+    }
+    // Предупреждения bash (например, о незакрытом here-document) не мешают проверке shellcheck
+    if report.diagnostics.iter().any(|diagnostic| diagnostic.severity == LintSeverity::Error) {
+        return Ok(());
+    }
+
+    match run_tool("shellcheck", &["--format=json1", "--shell=bash", file], &env).await? {
+        // Код 1 - есть замечания, другие ненулевые коды - ошибка самого shellcheck
+        Some(output) if output.status.code().is_some_and(|code| code > 1) => {
+            Err(format!("Ошибка shellcheck: {}", stderr_text(&output)))
+        }
+        Some(output) => {
+            report.tools.push("shellcheck".to_string());
+            report.diagnostics.extend(parse_shellcheck(&String::from_utf8_lossy(&output.stdout))?);
+            Ok(())
+        }
+        None => {
+            report.missing.push("shellcheck".to_string());
+            Ok(())
+        }
+    }
+}
+
+async fn lint_powershell(interpreter: &Interpreter, file: &str, report: &mut LintReport) -> Result<(), String> {
+    // powershell из реестра интерпретаторов (Windows PowerShell), иначе PowerShell 7
+    let programs = [interpreter.command[0].as_str(), "pwsh"];
+    let args = ["-NoProfile", "-NonInteractive", "-Command", POWERSHELL_PARSE_CHECK];
+    let output = match run_first(&programs, &args, &[("LINT_SCRIPT_PATH", file)]).await? {
+        Some((_, output)) => output,
+        None => {
+            report.missing.push("powershell".to_string());
+            return Ok(());
+        }
+    };
+    if !output.status.success() {
+        return Err(format!("Ошибка проверки синтаксиса PowerShell: {}", stderr_text(&output)));
+    }
+    report.tools.push("powershell".to_string());
+    report.diagnostics.extend(parse_tab_separated(&String::from_utf8_lossy(&output.stdout), "powershell"));
+    Ok(())
+}
+
+// Запись скрипта в файл для проверки в кодировке, в которой он запускается
+fn write_script(path: &Path, content: &str, encoding: ScriptEncoding) -> Result<(), String> {
+    let mut file = fs::File::create(path)
+        .map_err(|e| format!("Ошибка при создании временного файла: {}", e))?;
+    if encoding == ScriptEncoding::Utf8Bom {
+        file.write_all(&[0xEF, 0xBB, 0xBF])
+            .map_err(|e| format!("Ошибка при записи BOM: {}", e))?;
+    }
+    file.write_all(content.as_bytes())
+        .map_err(|e| format!("Ошибка при записи скрипта во временный файл: {}", e))
+}
+
+// Статическая проверка скрипта перед запуском
+#[tauri::command]
+pub async fn lint_script(db: State<'_, DbState>, language: String, content: String) -> Result<LintReport, String> {
+    let interpreter = db.run(move |conn| find_interpreter(conn, &language)).await?;
+
+    let temp_dir = tempdir().map_err(|e| format!("Ошибка при создании временной директории: {}", e))?;
+    let path = temp_dir.path().join(format!("script.{}", interpreter.extension));
+    write_script(&path, &content, interpreter.encoding)?;
+    let file = path.to_string_lossy().to_string();
+
+    let mut report = LintReport::default();
+    match interpreter.id.as_str() {
+        "python" => lint_python(&interpreter, &file, &mut report).await?,
+        "shell" => lint_shell(&interpreter, &file, &mut report).await?,
+        "powershell" => lint_powershell(&interpreter, &file, &mut report).await?,
+        _ => {}
+    }
+
+    report.diagnostics.sort_by_key(|diagnostic| (diagnostic.line, diagnostic.column));
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(diagnostics: &[LintDiagnostic]) -> Vec<(u32, u32, LintSeverity, &str)> {
+        diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.line, diagnostic.column, diagnostic.severity, diagnostic.message.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn python_syntax_check_output() {
+        // PYTHON_SYNTAX_CHECK, Python 3.11: "x = (1,\nprint(x)\n" и смешанные отступы
+        let output = "1\t5\tSyntaxError: '(' was never closed\n";
+        assert_eq!(
+            positions(&parse_tab_separated(output, "py_compile")),
+            [(1, 5, LintSeverity::Error, "SyntaxError: '(' was never closed")]
+        );
+        let output = "3\t1\tTabError: inconsistent use of tabs and spaces in indentation\n";
+        assert_eq!(parse_tab_separated(output, "py_compile")[0].message, "TabError: inconsistent use of tabs and spaces in indentation");
+        assert!(parse_tab_separated("", "py_compile").is_empty());
+    }
+
+    #[test]
+    fn pyflakes_output() {
+        let file = "/tmp/.tmpQ2xJ7a/script.py";
+        // pyflakes 3.x: с колонкой
+        let output = "/tmp/.tmpQ2xJ7a/script.py:1:1: 'os' imported but unused\n\
+                      /tmp/.tmpQ2xJ7a/script.py:4:5: local variable 'unused' is assigned to but never used\n\
+                      /tmp/.tmpQ2xJ7a/script.py:5:11: undefined name 'undefined_name'\n\
+                      /tmp/.tmpQ2xJ7a/script.py:7:7: '...'.format(...) has unused arguments at position(s): 1\n";
+        assert_eq!(positions(&parse_pyflakes(output, file)), [
+            (1, 1, LintSeverity::Warning, "'os' imported but unused"),
+            (4, 5, LintSeverity::Warning, "local variable 'unused' is assigned to but never used"),
+            (5, 11, LintSeverity::Warning, "undefined name 'undefined_name'"),
+            (7, 7, LintSeverity::Warning, "'...'.format(...) has unused arguments at position(s): 1"),
+        ]);
+
+        // pyflakes 2.1 и старше: без колонки
+        let output = "/tmp/.tmpQ2xJ7a/script.py:1: 'os' imported but unused\n\
+                      /tmp/.tmpQ2xJ7a/script.py:7: '...'.format(...) has unused arguments at position(s): 1\n";
+        assert_eq!(positions(&parse_pyflakes(output, file)), [
+            (1, 1, LintSeverity::Warning, "'os' imported but unused"),
+            (7, 1, LintSeverity::Warning, "'...'.format(...) has unused arguments at position(s): 1"),
+        ]);
+
+        // Строки про другие файлы и пояснения не разбираются
+        let output = "/tmp/other.py:1:1: 'sys' imported but unused\n    import sys\n    ^\n";
+        assert!(parse_pyflakes(output, file).is_empty());
+    }
+
+    #[test]
+    fn bash_output() {
+        // LC_ALL=C bash -n, GNU bash 5.2.15
+        let output = "/tmp/.tmpQ2xJ7a/script.sh: line 4: syntax error: unexpected end of file\n";
+        assert_eq!(positions(&parse_bash(output)), [(4, 1, LintSeverity::Error, "syntax error: unexpected end of file")]);
+
+        let output = "/tmp/.tmpQ2xJ7a/script.sh: line 5: syntax error near unexpected token `fi'\n\
+                      /tmp/.tmpQ2xJ7a/script.sh: line 5: `fi'\n";
+        assert_eq!(positions(&parse_bash(output)), [(5, 1, LintSeverity::Error, "syntax error near unexpected token `fi'")]);
+
+        let output = "/tmp/.tmpQ2xJ7a/script.sh: line 2: unexpected EOF while looking for matching `\"'\n";
+        assert_eq!(positions(&parse_bash(output)), [(2, 1, LintSeverity::Error, "unexpected EOF while looking for matching `\"'")]);
+
+        let output = "/tmp/.tmpQ2xJ7a/script.sh: line 2: warning: here-document at line 1 delimited by end-of-file (wanted `EOF')\n";
+        assert_eq!(
+            positions(&parse_bash(output)),
+            [(2, 1, LintSeverity::Warning, "here-document at line 1 delimited by end-of-file (wanted `EOF')")]
+        );
+    }
+
+    #[test]
+    fn shellcheck_output() {
+        // Формат shellcheck --format=json1 --shell=bash (ShellCheck 0.9)
+        let output = r#"{"comments":[{"file":"/tmp/.tmpQ2xJ7a/script.sh","line":2,"endLine":2,"column":1,"endColumn":7,"level":"warning","code":2164,"message":"Use 'cd ... || exit' or 'cd ... || return' in case cd fails.","fix":{"replacements":[{"column":7,"endColumn":7,"endLine":2,"insertionPoint":"beforeStart","line":2,"precedence":1,"replacement":" || exit"}]}},{"file":"/tmp/.tmpQ2xJ7a/script.sh","line":3,"endLine":3,"column":6,"endColumn":8,"level":"info","code":2086,"message":"Double quote to prevent globbing and word splitting.","fix":{"replacements":[{"column":6,"endColumn":6,"endLine":3,"insertionPoint":"afterEnd","line":3,"precedence":7,"replacement":"\""},{"column":8,"endColumn":8,"endLine":3,"insertionPoint":"beforeStart","line":3,"precedence":7,"replacement":"\""}]}},{"file":"/tmp/.tmpQ2xJ7a/script.sh","line":4,"endLine":4,"column":3,"endColumn":8,"level":"style","code":2006,"message":"Use $(...) notation instead of legacy backticks `...`.","fix":{"replacements":[{"column":3,"endColumn":8,"endLine":4,"insertionPoint":"afterEnd","line":4,"precedence":8,"replacement":"$(pwd)"}]}},{"file":"/tmp/.tmpQ2xJ7a/script.sh","line":5,"endLine":5,"column":1,"endColumn":4,"level":"error","code":2068,"message":"Double quote array expansions to avoid re-splitting elements.","fix":null}]}"#;
+        let diagnostics = parse_shellcheck(output).unwrap();
+        assert_eq!(positions(&diagnostics), [
+            (2, 1, LintSeverity::Warning, "Use 'cd ... || exit' or 'cd ... || return' in case cd fails."),
+            (3, 6, LintSeverity::Info, "Double quote to prevent globbing and word splitting."),
+            (4, 3, LintSeverity::Info, "Use $(...) notation instead of legacy backticks `...`."),
+            (5, 1, LintSeverity::Error, "Double quote array expansions to avoid re-splitting elements."),
+        ]);
+        let codes: Vec<_> = diagnostics.iter().map(|diagnostic| diagnostic.code.as_deref()).collect();
+        assert_eq!(codes, [Some("SC2164"), Some("SC2086"), Some("SC2006"), Some("SC2068")]);
+
+        assert!(parse_shellcheck(r#"{"comments":[]}"#).unwrap().is_empty());
+        assert!(parse_shellcheck("In script.sh line 1:").is_err());
+    }
+}
@@ -0,0 +1,379 @@
+// Модуль параметров скриптов
+// Скрипт объявляет параметры в заголовке - комментариях в начале файла (#, // или --,
+// поэтому заголовок одинаково пишется для всех поддерживаемых языков):
+//   # @param target: choice(dev|staging|prod) = staging -- Среда развертывания
+//   # @param count: int = 3
+//   # @param token: string secret
+//   # @param verbose: bool arg
+// Типы: string, int, number, bool, choice(a|b|...). Модификаторы: secret - значение не
+// показывается в интерфейсе и передается только через окружение; arg - параметр передается
+// позиционным аргументом (в порядке объявления), иначе переменной окружения с именем параметра;
+// optional - параметр без значения по умолчанию можно не указывать.
+// Перед запуском значения проверяются по типу, отсутствующие заменяются значениями по умолчанию
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+// Префиксы строчных комментариев, в которых ищется заголовок
+const COMMENT_PREFIXES: [&str; 3] = ["#", "//", "--"];
+
+const PARAM_DIRECTIVE: &str = "@param";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParameterType {
+    String,
+    Integer,
+    Number,
+    Boolean,
+    Choice,
+}
+
+// Как значение передается скрипту
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParameterTarget {
+    Env,
+    Arg,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScriptParameter {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: ParameterType,
+    // Значение по умолчанию в нормализованном виде
+    pub default: Option<String>,
+    // Допустимые значения для типа choice
+    pub choices: Vec<String>,
+    pub secret: bool,
+    pub target: ParameterTarget,
+    // Значение нужно указать перед запуском
+    pub required: bool,
+    pub description: Option<String>,
+    // Номер строки объявления (с 1)
+    pub line: usize,
+}
+
+// Значения параметров для запуска
+#[derive(Debug, Clone, Default)]
+pub struct ResolvedParameters {
+    pub env: Vec<(String, String)>,
+    pub args: Vec<String>,
+}
+
+fn is_identifier(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// Текст комментария строки заголовка (None - строка не комментарий)
+fn comment_text(line: &str) -> Option<&str> {
+    COMMENT_PREFIXES
+        .iter()
+        .find_map(|prefix| line.strip_prefix(prefix))
+        .map(|text| text.trim_start_matches(['#', '/', '-']).trim())
+}
+
+// Тип параметра: string, int, number, bool или choice(a|b|c)
+fn parse_type(spec: &str) -> Result<(ParameterType, Vec<String>, &str), String> {
+    if let Some(rest) = spec.strip_prefix("choice(") {
+        let end = rest.find(')').ok_or("Нет закрывающей скобки в choice(...)")?;
+        let choices: Vec<String> = rest[..end].split('|').map(|choice| choice.trim().to_string()).collect();
+        if choices.iter().any(|choice| choice.is_empty()) {
+            return Err("Пустое значение в choice(...)".to_string());
+        }
+        return Ok((ParameterType::Choice, choices, &rest[end + 1..]));
+    }
+
+    let end = spec.find(|c: char| c.is_whitespace() || c == '=').unwrap_or(spec.len());
+    let kind = match &spec[..end] {
+        "string" | "str" => ParameterType::String,
+        "int" | "integer" => ParameterType::Integer,
+        "number" | "float" => ParameterType::Number,
+        "bool" | "boolean" => ParameterType::Boolean,
+        other => return Err(format!("Неизвестный тип параметра {:?}", other)),
+    };
+    Ok((kind, Vec::new(), &spec[end..]))
+}
+
+// Значение по умолчанию: в кавычках или до описания (" --") либо конца строки
+fn parse_default(spec: &str) -> Result<(String, &str), String> {
+    if let Some(quote) = spec.chars().next().filter(|c| *c == '"' || *c == '\'') {
+        let end = spec[1..].find(quote).ok_or("Нет закрывающей кавычки в значении по умолчанию")?;
+        return Ok((spec[1..end + 1].to_string(), &spec[end + 2..]));
+    }
+
+    let end = spec.find(" --").unwrap_or(spec.len());
+    Ok((spec[..end].trim().to_string(), &spec[end..]))
+}
+
+// Имя и описание параметра из строки "@param name: ..."
+// Другие строки с @param (JSDoc "@param {string} name", справка "@param foo цель") не объявления
+fn split_declaration(declaration: &str) -> Option<(&str, &str)> {
+    let (name, spec) = declaration.split_once(':')?;
+    let name = name.trim();
+    is_identifier(name).then_some((name, spec))
+}
+
+// Разбор объявления "name: type [модификаторы] [= значение] [-- описание]"
+fn parse_declaration(name: &str, spec: &str, line: usize) -> Result<ScriptParameter, String> {
+    let (kind, choices, mut rest) = parse_type(spec.trim_start())?;
+    let mut parameter = ScriptParameter {
+        name: name.to_string(),
+        kind,
+        default: None,
+        choices,
+        secret: false,
+        target: ParameterTarget::Env,
+        required: true,
+        description: None,
+        line,
+    };
+    let mut optional = false;
+
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            break;
+        }
+        if let Some(description) = rest.strip_prefix("--") {
+            parameter.description = Some(description.trim().to_string()).filter(|text| !text.is_empty());
+            break;
+        }
+        if let Some(value) = rest.strip_prefix('=') {
+            let (default, tail) = parse_default(value.trim_start())?;
+            parameter.default = Some(default);
+            rest = tail;
+            continue;
+        }
+
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        match &rest[..end] {
+            "secret" => parameter.secret = true,
+            "arg" => parameter.target = ParameterTarget::Arg,
+            "env" => parameter.target = ParameterTarget::Env,
+            "optional" => optional = true,
+            other => return Err(format!("Неизвестный модификатор {:?}", other)),
+        }
+        rest = &rest[end..];
+    }
+
+    if parameter.secret && parameter.target == ParameterTarget::Arg {
+        return Err("Секретный параметр передается только через окружение".to_string());
+    }
+    if let Some(default) = parameter.default.take() {
+        parameter.default = Some(normalize_value(&parameter, &Value::String(default))
+            .map_err(|e| format!("Значение по умолчанию: {}", e))?);
+    }
+    // Флаг без значения по умолчанию выключен
+    if parameter.kind == ParameterType::Boolean && parameter.default.is_none() {
+        parameter.default = Some("false".to_string());
+    }
+    parameter.required = parameter.default.is_none() && !optional;
+
+    Ok(parameter)
+}
+
+// Параметры из заголовка скрипта
+// Заголовок - комментарии и пустые строки в начале файла; строки #! и <?php пропускаются.
+// Объявлением считается только строка "@param имя:", ошибка в ней останавливает запуск
+pub fn parse_parameters(script: &str) -> Result<Vec<ScriptParameter>, String> {
+    let mut parameters: Vec<ScriptParameter> = Vec::new();
+
+    for (index, line) in script.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("#!") || line.starts_with("<?php") {
+            continue;
+        }
+        let text = match comment_text(line) {
+            Some(text) => text,
+            None => break,
+        };
+        let (name, spec) = match text.strip_prefix(PARAM_DIRECTIVE) {
+            Some(declaration) if declaration.starts_with(char::is_whitespace) => match split_declaration(declaration) {
+                Some(declaration) => declaration,
+                None => continue,
+            },
+            _ => continue,
+        };
+
+        let parameter = parse_declaration(name, spec, index + 1)
+            .map_err(|e| format!("Строка {}: {}", index + 1, e))?;
+        if parameters.iter().any(|existing| existing.name == parameter.name) {
+            return Err(format!("Строка {}: параметр {} уже объявлен", index + 1, parameter.name));
+        }
+        parameters.push(parameter);
+    }
+
+    Ok(parameters)
+}
+
+// Проверка значения по типу параметра и приведение к строке для передачи скрипту
+fn normalize_value(parameter: &ScriptParameter, value: &Value) -> Result<String, String> {
+    let text = match value {
+        Value::String(text) => text.trim().to_string(),
+        Value::Number(number) => number.to_string(),
+        Value::Bool(flag) => flag.to_string(),
+        _ => return Err("ожидается строка, число или логическое значение".to_string()),
+    };
+
+    match parameter.kind {
+        ParameterType::String => Ok(match value {
+            // Строки передаются как есть, без обрезки пробелов
+            Value::String(text) => text.clone(),
+            _ => text,
+        }),
+        ParameterType::Integer => text.parse::<i64>()
+            .map(|number| number.to_string())
+            .map_err(|_| format!("ожидается целое число, получено {:?}", text)),
+        ParameterType::Number => text.parse::<f64>().ok()
+            .filter(|number| number.is_finite())
+            .map(|_| text.clone())
+            .ok_or_else(|| format!("ожидается число, получено {:?}", text)),
+        ParameterType::Boolean => match text.to_ascii_lowercase().as_str() {
+            "true" | "1" | "yes" | "on" => Ok("true".to_string()),
+            "false" | "0" | "no" | "off" => Ok("false".to_string()),
+            _ => Err(format!("ожидается true или false, получено {:?}", text)),
+        },
+        ParameterType::Choice => {
+            if parameter.choices.contains(&text) {
+                Ok(text)
+            } else {
+                Err(format!("допустимые значения: {}", parameter.choices.join(", ")))
+            }
+        }
+    }
+}
+
+// Проверка переданных значений и подготовка переменных окружения и аргументов
+// Ошибки по всем параметрам собираются в одно сообщение
+pub fn resolve_parameters(script: &str, values: &BTreeMap<String, Value>) -> Result<ResolvedParameters, String> {
+    let parameters = parse_parameters(script)?;
+    let mut resolved = ResolvedParameters::default();
+    let mut errors = Vec::new();
+
+    for name in values.keys() {
+        if !parameters.iter().any(|parameter| &parameter.name == name) {
+            errors.push(format!("{}: параметр не объявлен в скрипте", name));
+        }
+    }
+
+    for parameter in &parameters {
+        let value = match (values.get(&parameter.name), &parameter.default) {
+            (Some(Value::Null) | None, Some(default)) => default.clone(),
+            (Some(Value::Null) | None, None) => {
+                if parameter.required {
+                    errors.push(format!("{}: значение не указано", parameter.name));
+                }
+                continue;
+            }
+            (Some(value), _) => match normalize_value(parameter, value) {
+                Ok(value) => value,
+                Err(e) => {
+                    errors.push(format!("{}: {}", parameter.name, e));
+                    continue;
+                }
+            },
+        };
+
+        match parameter.target {
+            ParameterTarget::Env => resolved.env.push((parameter.name.clone(), value)),
+            ParameterTarget::Arg => resolved.args.push(value),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(format!("Некорректные параметры скрипта:\n{}", errors.join("\n")));
+    }
+    Ok(resolved)
+}
+
+// Параметры, объявленные в заголовке скрипта
+#[tauri::command]
+pub fn get_script_parameters(script: String) -> Result<Vec<ScriptParameter>, String> {
+    parse_parameters(&script)
+}
+
+// Проверка значений параметров перед запуском (те же правила, что и при запуске)
+#[tauri::command]
+pub fn validate_script_parameters(script: String, values: BTreeMap<String, Value>) -> Result<(), String> {
+    resolve_parameters(&script, &values).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn header_declarations() {
+        let script = "#!/usr/bin/env bash\n\
+            # @param target: choice(dev | staging|prod) = staging -- Среда развертывания\n\
+            # @param message: string = \"a -- b\" -- Текст\n\
+            # @param token: string secret\n\
+            # @param verbose: bool arg\n\
+            # @param count: int optional\n\
+            echo\n\
+            # @param late: int\n";
+        let parameters = parse_parameters(script).unwrap();
+
+        let names: Vec<_> = parameters.iter().map(|parameter| parameter.name.as_str()).collect();
+        assert_eq!(names, ["target", "message", "token", "verbose", "count"]);
+        assert_eq!(parameters[0].choices, ["dev", "staging", "prod"]);
+        assert_eq!(parameters[0].default.as_deref(), Some("staging"));
+        assert_eq!(parameters[0].description.as_deref(), Some("Среда развертывания"));
+        assert_eq!(parameters[0].line, 2);
+        assert_eq!(parameters[1].default.as_deref(), Some("a -- b"));
+        assert_eq!(parameters[1].description.as_deref(), Some("Текст"));
+        assert!(parameters[2].secret && parameters[2].required);
+        assert_eq!(parameters[3].target, ParameterTarget::Arg);
+        assert_eq!(parameters[3].default.as_deref(), Some("false"));
+        assert!(!parameters[4].required);
+    }
+
+    #[test]
+    fn header_skips_php_tag_and_foreign_param_lines() {
+        let script = "<?php\n\
+            // @param {string} name JSDoc\n\
+            // @param foo the target\n\
+            // @param name: string = 'x'\n\
+            -- @params not a directive\n";
+        let parameters = parse_parameters(script).unwrap();
+
+        assert_eq!(parameters.len(), 1);
+        assert_eq!(parameters[0].name, "name");
+        assert_eq!(parameters[0].default.as_deref(), Some("x"));
+    }
+
+    #[test]
+    fn header_errors() {
+        let error = |script: &str| parse_parameters(script).unwrap_err();
+
+        assert!(error("# @param token: string secret arg").contains("только через окружение"));
+        assert!(error("# @param a: int\n# @param a: string").contains("уже объявлен"));
+        assert!(error("# @param a: choice(x|) = x").contains("Пустое значение"));
+        assert!(error("# @param a: choice(x|y) = z").contains("допустимые значения"));
+        assert!(error("# @param a: string = \"open").contains("кавычки"));
+        assert!(error("# @param a: date").contains("Неизвестный тип"));
+        assert!(error("# @param a: int loud").contains("Неизвестный модификатор"));
+    }
+
+    #[test]
+    fn values_are_checked_and_placed() {
+        let script = "# @param env: choice(dev|prod)\n# @param n: int arg = 2\n# @param flag: bool arg";
+        let values = BTreeMap::from([("env".to_string(), json!("prod")), ("flag".to_string(), json!(true))]);
+        let resolved = resolve_parameters(script, &values).unwrap();
+
+        assert_eq!(resolved.env, [("env".to_string(), "prod".to_string())]);
+        assert_eq!(resolved.args, ["2", "true"]);
+
+        let values = BTreeMap::from([("n".to_string(), json!("x")), ("other".to_string(), json!(1))]);
+        let error = resolve_parameters(script, &values).unwrap_err();
+        assert!(error.contains("env: значение не указано"));
+        assert!(error.contains("n: ожидается целое число"));
+        assert!(error.contains("other: параметр не объявлен"));
+    }
+}
@@ -1,87 +1,302 @@
 use std::fs;
 use std::process::{Command, Stdio};
 use std::io::Write;
-use tauri::command;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use tauri::{command, AppHandle, Emitter, State};
+use tauri::async_runtime::{JoinHandle, Mutex};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::process::ChildStdin;
+use tokio::sync::{oneshot, watch};
+
+use crate::utils::db::DbState;
+use crate::utils::script_history::{load_script_run, script_hash, store_script_run, OutputCapture, RunStatus, ScriptRunRecord, ScriptRunSource};
+#[cfg(windows)]
+use crate::utils::script_interpreters::builtin_interpreters;
+use crate::utils::script_interpreters::{find_interpreter, Interpreter, ScriptEncoding};
+use crate::utils::script_params::resolve_parameters;
+use crate::utils::script_sandbox::{apply_sandbox, check_sandbox, SandboxMonitor, SandboxProfile, SANDBOX_SPAWN_HINT};
 use uuid::Uuid;
-use tempfile::tempdir;
+use tempfile::{tempdir, TempDir};
 use std::env;
 use std::path::PathBuf;
 use serde_json;
 
-/// Функция для запуска скрипта на исполнение
-#[command]
-pub fn run_script(script: String, language: String) -> Result<String, String> {
-    // Создаем временную директорию для хранения скрипта
-    let temp_dir = tempdir().map_err(|e| format!("Ошибка при создании временной директории: {}", e))?;
-    
-    // Генерируем имя файла с расширением в зависимости от языка
-    let script_id = Uuid::new_v4().to_string();
-    
-    // Настраиваем команды в зависимости от языка и платформы
-    let (filename, command, args) = match language.as_str() {
-        "python" => (format!("script_{}.py", script_id), "python".to_string(), vec![]),
-        "powershell" => (format!("script_{}.ps1", script_id), "powershell".to_string(), vec!["-ExecutionPolicy".to_string(), "Bypass".to_string()]),
-        "shell" => {
-            #[cfg(unix)]
-            {
-                (format!("script_{}.sh", script_id), "bash".to_string(), vec![])
+/// Параметры запуска скрипта
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScriptRunOptions {
+    /// Аргументы, передаваемые скрипту
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Переменные окружения процесса скрипта (поверх окружения приложения и .env)
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Рабочая директория (по умолчанию - папка проекта)
+    #[serde(default)]
+    pub cwd: Option<String>,
+    /// Файл с переменными окружения; относительный путь считается от рабочей директории
+    #[serde(default)]
+    pub env_file: Option<String>,
+    /// Папка проекта: рабочая директория по умолчанию, ее .env загружается автоматически,
+    /// а путь передается скрипту в PROJECT_DIR
+    #[serde(default)]
+    pub project_dir: Option<String>,
+    /// id скрипта библиотеки, к которому относится запуск (для истории запусков)
+    #[serde(default)]
+    pub script_id: Option<String>,
+    /// Значения параметров, объявленных в заголовке скрипта
+    #[serde(default)]
+    pub params: BTreeMap<String, serde_json::Value>,
+    /// Профиль песочницы (только Linux); без него скрипт запускается с правами пользователя
+    /// и без ограничений
+    #[serde(default)]
+    pub sandbox: Option<SandboxProfile>,
+}
+
+/// Скрипт, записанный во временный файл, и команда для его запуска
+/// Временная директория удаляется при удалении структуры
+struct PreparedScript {
+    temp_dir: TempDir,
+    command: String,
+    args: Vec<String>,
+    cwd: Option<PathBuf>,
+    env: Vec<(String, String)>,
+    sandbox: Option<SandboxProfile>,
+}
+
+impl PreparedScript {
+    /// Команда процесса скрипта; окружение задается только процессу скрипта
+    /// При запуске в песочнице возвращается и наблюдатель, который после завершения скрипта
+    /// сообщает потребленные ресурсы
+    fn process(&self) -> Result<(tokio::process::Command, Option<SandboxMonitor>), String> {
+        let mut command = tokio::process::Command::new(&self.command);
+        command.args(&self.args).envs(self.env.iter().cloned());
+        if let Some(cwd) = &self.cwd {
+            command.current_dir(cwd);
+        }
+
+        let monitor = match &self.sandbox {
+            Some(profile) => {
+                // В песочнице запись разрешена только во временную папку скрипта
+                command.env("TMPDIR", self.temp_dir.path());
+                Some(apply_sandbox(&mut command, profile, self.temp_dir.path())?)
             }
-            #[cfg(windows)]
-            {
-                // На Windows пытаемся найти доступный bash-подобный интерпретатор
-                let wsl_check = Command::new("wsl")
-                    .arg("--version")
-                    .stdout(Stdio::null())
-                    .stderr(Stdio::null())
-                    .status();
-                
-                let has_wsl = match wsl_check {
-                    Ok(status) => status.success(),
-                    Err(_) => false
-                };
-                
-                let bash_check = Command::new("bash")
-                    .arg("--version")
-                    .stdout(Stdio::null())
-                    .stderr(Stdio::null())
-                    .status();
-                
-                let has_bash = match bash_check {
-                    Ok(status) => status.success(),
-                    Err(_) => false
-                };
-                
-                if has_wsl {
-                    // WSL доступен
-                    println!("[Script Runner] WSL обнаружен, используем его для запуска bash-скрипта");
-                    (format!("script_{}.sh", script_id), "wsl".to_string(), vec!["bash".to_string()])
-                } else if has_bash {
-                    // Git Bash или другой bash доступен
-                    println!("[Script Runner] Bash обнаружен, используем его для запуска скрипта");
-                    (format!("script_{}.sh", script_id), "bash".to_string(), vec![])
-                } else {
-                    // Если bash не доступен, используем PowerShell с явным предупреждением
-                    println!("[Script Runner] Bash не обнаружен, конвертируем в PowerShell");
-                    // Конвертируем bash-скрипт в PowerShell-совместимый формат
-                    let ps_script = format!(
-                        "Write-Host \"Внимание: Bash не обнаружен на вашей системе. Попытка выполнить скрипт через PowerShell.\"
-Write-Host \"Некоторые bash-команды могут не работать.\"
-Write-Host \"\"
-{}",
-                        script
-                    );
-                    return run_script(ps_script, "powershell".to_string());
+            None => None,
+        };
+        Ok((command, monitor))
+    }
+
+    /// Сообщение об ошибке запуска процесса скрипта
+    fn spawn_error(&self, e: std::io::Error) -> String {
+        let mut error = format!("Ошибка при запуске скрипта: {}\nКоманда: {} {:?}", e, self.command, self.args);
+        if self.sandbox.is_some() {
+            error.push('\n');
+            error.push_str(SANDBOX_SPAWN_HINT);
+        }
+        error
+    }
+}
+
+/// После закрывающей кавычки допустим только комментарий
+fn check_after_quote(rest: &str, number: usize) -> Result<(), String> {
+    let rest = rest.trim_start();
+    if rest.is_empty() || rest.starts_with('#') {
+        Ok(())
+    } else {
+        Err(format!("Строка {}: лишние символы после закрывающей кавычки: {:?}", number + 1, rest))
+    }
+}
+
+/// Разбор файла .env: строки KEY=VALUE, комментарии #, префикс export и значения в кавычках
+/// В двойных кавычках поддерживаются \n, \t, \" и \\
+fn parse_env_file(content: &str) -> Result<Vec<(String, String)>, String> {
+    let mut vars = Vec::new();
+
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line
+            .strip_prefix("export")
+            .filter(|rest| rest.starts_with(char::is_whitespace))
+            .unwrap_or(line)
+            .trim_start();
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("Строка {}: ожидается KEY=VALUE", number + 1))?;
+        let key = key.trim();
+        let valid_key = !key.is_empty()
+            && !key.starts_with(|c: char| c.is_ascii_digit())
+            && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_key {
+            return Err(format!("Строка {}: некорректное имя переменной {:?}", number + 1, key));
+        }
+
+        let value = value.trim();
+        let unclosed = || format!("Строка {}: нет закрывающей кавычки", number + 1);
+        let value = if let Some(quoted) = value.strip_prefix('"') {
+            // Значение заканчивается первой неэкранированной кавычкой
+            let mut unescaped = String::new();
+            let mut chars = quoted.char_indices();
+            let end = loop {
+                match chars.next().ok_or_else(unclosed)? {
+                    (index, '"') => break index,
+                    (_, '\\') => match chars.next() {
+                        Some((_, 'n')) => unescaped.push('\n'),
+                        Some((_, 't')) => unescaped.push('\t'),
+                        Some((_, other)) => unescaped.push(other),
+                        None => return Err(unclosed()),
+                    },
+                    (_, c) => unescaped.push(c),
                 }
+            };
+            check_after_quote(&quoted[end + 1..], number)?;
+            unescaped
+        } else if let Some(quoted) = value.strip_prefix('\'') {
+            let end = quoted.find('\'').ok_or_else(unclosed)?;
+            check_after_quote(&quoted[end + 1..], number)?;
+            quoted[..end].to_string()
+        } else {
+            // Комментарий после значения без кавычек отделяется пробелом
+            match value.find(" #") {
+                Some(comment) => value[..comment].trim_end().to_string(),
+                None => value.to_string(),
             }
-        },
-        _ => return Err(format!("Неподдерживаемый язык: {}", language)),
+        };
+
+        vars.push((key.to_string(), value));
+    }
+
+    Ok(vars)
+}
+
+fn existing_dir(path: &str, what: &str) -> Result<PathBuf, String> {
+    let dir = PathBuf::from(path);
+    if !dir.is_dir() {
+        return Err(format!("{} не найдена: {}", what, path));
+    }
+    Ok(dir)
+}
+
+/// Рабочая директория и окружение процесса скрипта
+struct ScriptEnvironment {
+    cwd: Option<PathBuf>,
+    env: Vec<(String, String)>,
+}
+
+/// Рабочая директория и окружение процесса скрипта по параметрам запуска
+fn resolve_environment(options: &ScriptRunOptions) -> Result<ScriptEnvironment, String> {
+    let project_dir = options.project_dir.as_deref()
+        .map(|dir| existing_dir(dir, "Папка проекта"))
+        .transpose()?;
+    let cwd = match options.cwd.as_deref() {
+        Some(cwd) => Some(existing_dir(cwd, "Рабочая директория")?),
+        None => project_dir.clone(),
     };
+
+    let mut env = Vec::new();
+    if let Some(project_dir) = &project_dir {
+        env.push(("PROJECT_DIR".to_string(), project_dir.to_string_lossy().to_string()));
+    }
+
+    let env_file = match (&options.env_file, &project_dir) {
+        (Some(env_file), _) => {
+            let path = PathBuf::from(env_file);
+            Some(match (&cwd, path.is_relative()) {
+                (Some(cwd), true) => cwd.join(path),
+                _ => path,
+            })
+        }
+        // .env проекта загружается, только если он есть
+        (None, Some(project_dir)) => Some(project_dir.join(".env")).filter(|path| path.is_file()),
+        (None, None) => None,
+    };
+
+    if let Some(env_file) = env_file {
+        let content = fs::read_to_string(&env_file)
+            .map_err(|e| format!("Не удалось прочитать файл окружения {}: {}", env_file.display(), e))?;
+        env.extend(parse_env_file(&content)
+            .map_err(|e| format!("Ошибка в файле окружения {}: {}", env_file.display(), e))?);
+    }
+
+    env.extend(options.env.iter().map(|(key, value)| (key.clone(), value.clone())));
+    Ok(ScriptEnvironment { cwd, env })
+}
+
+/// Подготовка скрипта к запуску: запись во временный файл, компиляция (если нужна)
+/// и команда интерпретатора
+fn prepare_script(script: &str, interpreter: &Interpreter, options: &ScriptRunOptions) -> Result<PreparedScript, String> {
+    let ScriptEnvironment { cwd, env } = resolve_environment(options)?;
+    
+    // На Windows для shell-скриптов ищем доступный bash-подобный интерпретатор
+    #[cfg(windows)]
+    let mut wsl = false;
+    #[cfg(windows)]
+    if interpreter.id == "shell" {
+        let wsl_check = Command::new("wsl")
+            .arg("--version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+        
+        let has_wsl = match wsl_check {
+            Ok(status) => status.success(),
+            Err(_) => false
+        };
+        
+        let bash_check = Command::new("bash")
+            .arg("--version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+        
+        let has_bash = match bash_check {
+            Ok(status) => status.success(),
+            Err(_) => false
+        };
+        
+        if has_wsl {
+            // WSL доступен
+            println!("[Script Runner] WSL обнаружен, используем его для запуска bash-скрипта");
+            wsl = true;
+        } else if has_bash {
+            // Git Bash или другой bash доступен
+            println!("[Script Runner] Bash обнаружен, используем его для запуска скрипта");
+        } else {
+            // Если bash не доступен, используем PowerShell с явным предупреждением
+            println!("[Script Runner] Bash не обнаружен, конвертируем в PowerShell");
+            // Конвертируем bash-скрипт в PowerShell-совместимый формат
+            let ps_script = format!(
+                "Write-Host \"Внимание: Bash не обнаружен на вашей системе. Попытка выполнить скрипт через PowerShell.\"
+Write-Host \"Некоторые bash-команды могут не работать.\"
+Write-Host \"\"
+{}",
+                script
+            );
+            let powershell = builtin_interpreters()
+                .into_iter()
+                .find(|interpreter| interpreter.id == "powershell")
+                .ok_or("Интерпретатор PowerShell не найден")?;
+            return prepare_script(&ps_script, &powershell, options);
+        }
+    }
+    
+    // Создаем временную директорию для хранения скрипта
+    let temp_dir = tempdir().map_err(|e| format!("Ошибка при создании временной директории: {}", e))?;
+    
+    // Генерируем имя файла с расширением интерпретатора
+    let script_id = Uuid::new_v4().simple().to_string();
+    let filename = format!("script_{}.{}", script_id, interpreter.extension);
     
     // Собираем полный путь к временному файлу
     let file_path = temp_dir.path().join(&filename);
     
     // Записываем содержимое скрипта во временный файл
-    if language == "powershell" {
+    if interpreter.encoding == ScriptEncoding::Utf8Bom {
         // Для PowerShell используем UTF-8 с BOM, чтобы Windows правильно распознавала кириллицу
         let mut file = fs::File::create(&file_path)
             .map_err(|e| format!("Ошибка при создании временного файла: {}", e))?;
@@ -100,7 +315,7 @@ Write-Host \"\"
     }
     
     // Делаем файл исполняемым для bash-скриптов на Unix
-    if language == "shell" {
+    if interpreter.extension == "sh" {
         #[cfg(unix)]
         {
             use std::os::unix::fs::PermissionsExt;
@@ -113,87 +328,615 @@ Write-Host \"\"
         }
     }
     
-    // Создаем команду для запуска скрипта
-    let mut cmd_args = args;
+    // Компилируем скрипт, если интерпретатор этого требует
+    if let Some(compile) = &interpreter.compile {
+        let compile = Interpreter::render(compile, &file_path, &[]);
+        println!("[Script Runner] Компиляция скрипта командой: {:?}", compile);
+        
+        let output = Command::new(&compile[0])
+            .args(&compile[1..])
+            .current_dir(temp_dir.path())
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|e| format!("Ошибка при запуске компилятора: {}\nКоманда: {:?}", e, compile))?;
+        
+        if !output.status.success() {
+            return Err(format!(
+                "Ошибка компиляции (код возврата {}):\n{}",
+                output.status.code().unwrap_or(-1),
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+    }
+    
+    let mut cmd_args = Interpreter::render(&interpreter.command, &file_path, &options.args);
     
     // Для WSL передаем путь к файлу в другом формате
     #[cfg(windows)]
-    let file_path_str = if command == "wsl" {
+    if wsl {
         // Преобразуем путь Windows в формат WSL
         let wsl_path = file_path.to_string_lossy().to_string()
             .replace("\\", "/")
             .replace(":", "");
-        format!("/mnt/{}", wsl_path)
+        cmd_args = vec!["wsl".to_string(), "bash".to_string(), format!("/mnt/{}", wsl_path)];
+        cmd_args.extend(options.args.iter().cloned());
+    }
+    
+    // Кодировка UTF-8 вывода Python задается только процессу скрипта
+    // (переменные из параметров запуска могут ее переопределить)
+    #[cfg(windows)]
+    let env = if interpreter.id == "python" {
+        let mut env = env;
+        env.insert(0, ("PYTHONIOENCODING".to_string(), "utf-8".to_string()));
+        env
     } else {
-        file_path.to_string_lossy().to_string()
+        env
     };
     
-    #[cfg(not(windows))]
-    let file_path_str = file_path.to_string_lossy().to_string();
+    let command = cmd_args.remove(0);
     
-    // Обрабатываем путь к файлу в зависимости от языка
-    if language == "powershell" {
-        // Для PowerShell используем специальную команду с установкой кодировки UTF-8
-        cmd_args.push("-Command".to_string());
-        cmd_args.push(format!("$OutputEncoding = [System.Text.Encoding]::UTF8; & '{}'", file_path_str));
-    } else if language == "python" {
-        // Для Python добавляем параметры для обработки UTF-8
-        cmd_args.clear(); // Очищаем предыдущие аргументы
-        
-        // Используем специальный аргумент PYTHONIOENCODING для корректной работы с русскими символами
-        // и добавляем -u для отключения буферизации вывода
-        #[cfg(windows)]
-        {
-            cmd_args.push("-u".to_string());
-            cmd_args.push(file_path_str);
-            
-            // Устанавливаем переменную окружения для кодировки UTF-8
-            std::env::set_var("PYTHONIOENCODING", "utf-8");
-        }
-        #[cfg(not(windows))]
-        {
-            cmd_args.push("-u".to_string());
-            cmd_args.push(file_path_str);
-        }
-    } else {
-        // Для остальных языков просто добавляем путь как аргумент
-        cmd_args.push(file_path_str);
+    Ok(PreparedScript {
+        temp_dir,
+        command,
+        args: cmd_args,
+        cwd,
+        env,
+        sandbox: options.sandbox.clone(),
+    })
+}
+
+/// Поиск интерпретатора языка и подготовка скрипта в потоке для блокирующих задач
+/// Вместе со скриптом возвращает описание запуска для истории
+/// Параметры из заголовка скрипта добавляются к окружению и аргументам запуска
+async fn prepare_script_for(
+    db: &DbState,
+    script: String,
+    language: String,
+    mut options: ScriptRunOptions,
+) -> Result<(PreparedScript, ScriptRunSource), String> {
+    if let Some(profile) = &options.sandbox {
+        check_sandbox(profile)?;
     }
+    let parameters = resolve_parameters(&script, &options.params)?;
+    options.env.extend(parameters.env);
+    options.args.splice(0..0, parameters.args);
+
+    let source = ScriptRunSource {
+        script_id: options.script_id.clone(),
+        script_hash: script_hash(&script),
+        interpreter: language.clone(),
+        args: options.args.clone(),
+        cwd: None,
+    };
+    let interpreter = db.run(move |conn| find_interpreter(conn, &language)).await?;
     
-    println!("[Script Runner] Запуск скрипта командой: {} {:?}", command, cmd_args);
-    
-    let output = Command::new(&command)
-        .args(&cmd_args)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .output()
-        .map_err(|e| format!("Ошибка при запуске скрипта: {}\nКоманда: {} {:?}", e, command, cmd_args))?;
-    
-    // Получаем вывод и ошибки
-    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-    
+    let prepared = tauri::async_runtime::spawn_blocking(move || prepare_script(&script, &interpreter, &options))
+        .await
+        .map_err(|e| format!("Ошибка подготовки скрипта: {}", e))??;
+    let cwd = prepared.cwd.as_ref().map(|cwd| cwd.to_string_lossy().to_string());
+    Ok((prepared, ScriptRunSource { cwd, ..source }))
+}
+
+/// Функция для запуска скрипта на исполнение
+/// Скрипт запускается через реестр, как start_script: его можно отменить по идентификатору
+/// из событий script-output, а при заданном timeout_secs он принудительно завершается.
+/// Результат собирается из записи истории запусков после завершения
+#[command]
+pub async fn run_script(
+    app: AppHandle,
+    state: State<'_, ScriptRunState>,
+    db: State<'_, DbState>,
+    script: String,
+    language: String,
+    timeout_secs: Option<u64>,
+    options: Option<ScriptRunOptions>,
+) -> Result<String, String> {
+    let (run_id, task) = launch_script(app, &state, &db, script, language, timeout_secs, options.unwrap_or_default()).await?;
+    // Ввод скрипту не передается: он сразу получает конец файла
+    if let Some(run) = state.runs.lock().await.get_mut(&run_id) {
+        run.stdin = None;
+    }
+    let event = task.await
+        .map_err(|e| format!("Ошибка ожидания завершения скрипта: {}", e))?;
+
+    let record = match event.history_id {
+        Some(id) => Some(db.run(move |conn| load_script_run(conn, id)).await?),
+        None => None,
+    };
+    if let (Some(error), None) = (&event.error, &record) {
+        return Err(error.clone());
+    }
+
     // Формируем результат выполнения
     let mut result = String::new();
-    if !stdout.is_empty() {
-        result.push_str(&stdout);
-    }
-    
-    if !stderr.is_empty() {
-        if !result.is_empty() {
-            result.push_str("\n\n");
+    if let Some(record) = &record {
+        if record.stdout_truncated {
+            result.push_str("[Начало вывода обрезано]\n");
+        }
+        result.push_str(&record.stdout);
+
+        if !record.stderr.is_empty() {
+            if !result.is_empty() {
+                result.push_str("\n\n");
+            }
+            result.push_str("ОШИБКА:\n");
+            if record.stderr_truncated {
+                result.push_str("[Начало вывода обрезано]\n");
+            }
+            result.push_str(&record.stderr);
         }
-        result.push_str("ОШИБКА:\n");
-        result.push_str(&stderr);
     }
-    
+
+    if let Some(error) = &event.error {
+        result.push_str(&format!("\n\n{}", error));
+    }
+    match event.reason {
+        ScriptExitReason::Exited => {}
+        ScriptExitReason::Cancelled => result.push_str("\n\nСкрипт отменен"),
+        ScriptExitReason::TimedOut => result.push_str("\n\nСкрипт завершен по таймауту"),
+    }
+
     // Добавляем информацию о коде возврата
-    result.push_str(&format!("\n\nКод возврата: {}", output.status.code().unwrap_or(-1)));
-    
-    // Временная директория будет удалена автоматически при выходе из функции
+    result.push_str(&format!("\n\nКод возврата: {}", event.exit_code.unwrap_or(-1)));
+
+    // Ресурсы, потребленные скриптом в песочнице
+    if let (Some(peak_memory_kb), Some(cpu_time_ms)) = (event.peak_memory_kb, event.cpu_time_ms) {
+        result.push_str(&format!(
+            "\nПиковая память: {:.1} МБ, процессорное время: {:.2} с",
+            peak_memory_kb as f64 / 1024.0,
+            cpu_time_ms as f64 / 1000.0
+        ));
+    }
+
     Ok(result)
 }
 
+/// Поток вывода скрипта
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScriptStream {
+    Stdout,
+    Stderr,
+}
+
+/// Событие script-output: очередная порция вывода скрипта
+#[derive(Debug, Clone, Serialize)]
+pub struct ScriptOutputEvent {
+    pub run_id: String,
+    pub stream: ScriptStream,
+    pub chunk: String,
+    /// Время получения порции, миллисекунды Unix
+    pub timestamp: i64,
+}
+
+/// Причина завершения скрипта
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScriptExitReason {
+    Exited,
+    Cancelled,
+    TimedOut,
+}
+
+/// Событие script-exit: завершение скрипта
+#[derive(Debug, Clone, Serialize)]
+pub struct ScriptExitEvent {
+    pub run_id: String,
+    pub reason: ScriptExitReason,
+    /// None - процесс завершен сигналом или его не удалось дождаться
+    pub exit_code: Option<i32>,
+    pub success: bool,
+    pub duration_ms: u64,
+    pub error: Option<String>,
+    /// id записи в истории запусков (None, если запуск не удалось записать)
+    pub history_id: Option<i64>,
+    /// Пиковая память (КБ) и процессорное время (мс) скрипта - только при запуске в песочнице
+    pub peak_memory_kb: Option<i64>,
+    pub cpu_time_ms: Option<i64>,
+}
+
+impl ScriptExitEvent {
+    /// Результат запуска для истории запусков
+    pub(crate) fn run_status(&self) -> RunStatus {
+        match (self.reason, self.success, &self.error) {
+            (_, _, Some(_)) => RunStatus::Error,
+            (ScriptExitReason::Exited, true, _) => RunStatus::Success,
+            (ScriptExitReason::Exited, false, _) => RunStatus::Failure,
+            (ScriptExitReason::Cancelled, _, _) => RunStatus::Cancelled,
+            (ScriptExitReason::TimedOut, _, _) => RunStatus::TimedOut,
+        }
+    }
+}
+
+/// Размер буфера чтения вывода скрипта
+const SCRIPT_READ_BUFFER: usize = 8192;
+
+/// Сколько дочитывается вывод после завершения процесса скрипта
+/// Запущенные скриптом фоновые процессы могут унаследовать вывод и держать его открытым
+const SCRIPT_OUTPUT_DRAIN: Duration = Duration::from_secs(2);
+
+/// Декодирование накопленных байтов UTF-8
+/// Незавершенный многобайтовый символ в конце остается в буфере до следующего чтения
+fn take_utf8(pending: &mut Vec<u8>) -> String {
+    let mut text = String::new();
+    let mut rest: &[u8] = pending;
+
+    loop {
+        match std::str::from_utf8(rest) {
+            Ok(valid) => {
+                text.push_str(valid);
+                rest = &[];
+                break;
+            }
+            Err(e) => {
+                let (valid, tail) = rest.split_at(e.valid_up_to());
+                text.push_str(std::str::from_utf8(valid).unwrap_or_default());
+                match e.error_len() {
+                    Some(len) => {
+                        text.push(char::REPLACEMENT_CHARACTER);
+                        rest = &tail[len..];
+                    }
+                    None => {
+                        rest = tail;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    *pending = rest.to_vec();
+    text
+}
+
+/// Пересылка вывода скрипта событиями script-output
+/// После сигнала exited вывод дочитывается не дольше SCRIPT_OUTPUT_DRAIN
+/// Возвращает вывод для истории запусков
+async fn forward_script_output<R: AsyncRead + Unpin>(
+    app: AppHandle,
+    run_id: String,
+    stream: ScriptStream,
+    reader: Option<R>,
+    mut exited: watch::Receiver<bool>,
+) -> OutputCapture {
+    let mut capture = OutputCapture::default();
+    let mut reader = match reader {
+        Some(reader) => reader,
+        None => return capture,
+    };
+    let mut buffer = vec![0u8; SCRIPT_READ_BUFFER];
+    let mut pending = Vec::new();
+    let mut deadline: Option<tokio::time::Instant> = None;
+
+    loop {
+        let read = tokio::select! {
+            result = reader.read(&mut buffer) => match result {
+                Ok(read) => read,
+                Err(e) => {
+                    eprintln!("[Script Runner] Ошибка чтения вывода скрипта {}: {}", run_id, e);
+                    0
+                }
+            },
+            _ = exited.changed(), if deadline.is_none() => {
+                deadline = Some(tokio::time::Instant::now() + SCRIPT_OUTPUT_DRAIN);
+                continue;
+            }
+            _ = tokio::time::sleep_until(deadline.unwrap_or_else(tokio::time::Instant::now)), if deadline.is_some() => {
+                println!("[Script Runner] Вывод скрипта {} остается открытым после его завершения, чтение прекращено", run_id);
+                0
+            }
+        };
+
+        let chunk = if read == 0 {
+            // Конец потока: остаток выводится как есть
+            String::from_utf8_lossy(&std::mem::take(&mut pending)).to_string()
+        } else {
+            pending.extend_from_slice(&buffer[..read]);
+            take_utf8(&mut pending)
+        };
+
+        if !chunk.is_empty() {
+            capture.push(&chunk);
+            let event = ScriptOutputEvent {
+                run_id: run_id.clone(),
+                stream,
+                chunk,
+                timestamp: chrono::Utc::now().timestamp_millis(),
+            };
+            if let Err(e) = app.emit("script-output", event) {
+                eprintln!("[Script Runner] Ошибка отправки вывода скрипта {}: {}", run_id, e);
+            }
+        }
+
+        if read == 0 {
+            break;
+        }
+    }
+
+    capture
+}
+
+/// Запущенный скрипт в реестре
+struct ScriptRun {
+    pid: Option<u32>,
+    language: String,
+    started_at: String,
+    timeout_secs: Option<u64>,
+    /// Запись идет под собственной блокировкой, чтобы не держать реестр во время записи
+    stdin: Option<Arc<Mutex<ChildStdin>>>,
+    cancel: Option<oneshot::Sender<()>>,
+}
+
+/// Информация о запущенном скрипте для клиента
+#[derive(Debug, Clone, Serialize)]
+pub struct ScriptRunInfo {
+    pub run_id: String,
+    pub pid: Option<u32>,
+    pub language: String,
+    pub started_at: String,
+    pub timeout_secs: Option<u64>,
+}
+
+/// Реестр запущенных скриптов
+/// Запись удаляется, когда процесс скрипта завершается
+#[derive(Default, Clone)]
+pub struct ScriptRunState {
+    runs: Arc<Mutex<HashMap<String, ScriptRun>>>,
+}
+
+impl ScriptRunState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Завершение процесса скрипта вместе со всеми его потомками
+/// На Unix скрипт запускается в отдельной группе процессов, и сигнал отправляется всей группе
+#[cfg(unix)]
+fn kill_process_tree(pid: u32) -> Result<(), String> {
+    let group = libc::pid_t::try_from(pid)
+        .map_err(|_| format!("Некорректный идентификатор процесса {}", pid))?;
+    if unsafe { libc::killpg(group, libc::SIGKILL) } == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error().to_string())
+    }
+}
+
+/// В Windows дерево процессов завершает taskkill
+#[cfg(windows)]
+fn kill_process_tree(pid: u32) -> Result<(), String> {
+    match Command::new("taskkill").args(["/F", "/T", "/PID", &pid.to_string()]).output() {
+        Ok(output) if output.status.success() => Ok(()),
+        Ok(output) => Err(String::from_utf8_lossy(&output.stderr).trim().to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Запуск скрипта с регистрацией в реестре и пересылкой вывода событиями
+/// Возвращает идентификатор запуска и задачу, которая завершается вместе со скриптом
+/// и возвращает отправленное событие script-exit
+pub(crate) async fn launch_script(
+    app: AppHandle,
+    state: &ScriptRunState,
+    db: &DbState,
+    script: String,
+    language: String,
+    timeout_secs: Option<u64>,
+    options: ScriptRunOptions,
+) -> Result<(String, JoinHandle<ScriptExitEvent>), String> {
+    let (prepared, source) = prepare_script_for(db, script, language.clone(), options).await?;
+    let run_id = Uuid::new_v4().to_string();
+
+    println!("[Script Runner] Запуск скрипта {} командой: {} {:?}", run_id, prepared.command, prepared.args);
+
+    let started_at = chrono::Utc::now();
+    let spawned = prepared.process().and_then(|(mut command, monitor)| {
+        command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+
+        // Отдельная группа процессов, чтобы отмена завершала и запущенные скриптом процессы
+        #[cfg(unix)]
+        command.process_group(0);
+
+        command.spawn()
+            .map(|child| (child, monitor))
+            .map_err(|e| prepared.spawn_error(e))
+    });
+    let (mut child, monitor) = match spawned {
+        Ok(spawned) => spawned,
+        Err(error) => {
+            store_script_run(db, ScriptRunRecord::finished(
+                source, started_at, RunStatus::Error, None,
+                OutputCapture::default(), OutputCapture::default(), Some(error.clone()),
+            )).await;
+            return Err(error);
+        }
+    };
+
+    let started = Instant::now();
+    let pid = child.id();
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+    let (cancel_tx, cancel_rx) = oneshot::channel();
+
+    state.runs.lock().await.insert(run_id.clone(), ScriptRun {
+        pid,
+        language,
+        started_at: chrono::Local::now().to_rfc3339(),
+        timeout_secs,
+        stdin: child.stdin.take().map(|stdin| Arc::new(Mutex::new(stdin))),
+        cancel: Some(cancel_tx),
+    });
+
+    let runs = state.runs.clone();
+    let db = db.clone();
+    let id = run_id.clone();
+
+    let task = tauri::async_runtime::spawn(async move {
+        // Временный файл скрипта нужен до завершения процесса
+        let _prepared = prepared;
+
+        let (exited_tx, exited_rx) = watch::channel(false);
+        let wait = async {
+            let timeout = async {
+                match timeout_secs {
+                    Some(secs) => tokio::time::sleep(Duration::from_secs(secs)).await,
+                    None => std::future::pending().await,
+                }
+            };
+
+            let reason = tokio::select! {
+                status = child.wait() => {
+                    let _ = exited_tx.send(true);
+                    return (ScriptExitReason::Exited, status);
+                }
+                _ = cancel_rx => ScriptExitReason::Cancelled,
+                _ = timeout => ScriptExitReason::TimedOut,
+            };
+
+            println!("[Script Runner] Принудительное завершение скрипта {} ({:?})", id, reason);
+            if let Some(pid) = pid {
+                if let Err(e) = kill_process_tree(pid) {
+                    eprintln!("[Script Runner] Не удалось завершить дерево процессов скрипта {}: {}", id, e);
+                }
+            }
+            if let Err(e) = child.start_kill() {
+                eprintln!("[Script Runner] Не удалось завершить процесс скрипта {}: {}", id, e);
+            }
+            let status = child.wait().await;
+            let _ = exited_tx.send(true);
+            (reason, status)
+        };
+
+        let (stdout, stderr, (reason, status)) = tokio::join!(
+            forward_script_output(app.clone(), id.clone(), ScriptStream::Stdout, stdout, exited_rx.clone()),
+            forward_script_output(app.clone(), id.clone(), ScriptStream::Stderr, stderr, exited_rx),
+            wait
+        );
+
+        runs.lock().await.remove(&id);
+
+        let usage = match monitor {
+            Some(monitor) => monitor.usage().await,
+            None => None,
+        };
+
+        let mut event = match status {
+            Ok(status) => ScriptExitEvent {
+                run_id: id.clone(),
+                reason,
+                exit_code: status.code(),
+                success: reason == ScriptExitReason::Exited && status.success(),
+                duration_ms: started.elapsed().as_millis() as u64,
+                error: None,
+                history_id: None,
+                peak_memory_kb: usage.map(|usage| usage.peak_memory_kb),
+                cpu_time_ms: usage.map(|usage| usage.cpu_time_ms),
+            },
+            Err(e) => ScriptExitEvent {
+                run_id: id.clone(),
+                reason,
+                exit_code: None,
+                success: false,
+                duration_ms: started.elapsed().as_millis() as u64,
+                error: Some(format!("Ошибка ожидания завершения скрипта: {}", e)),
+                history_id: None,
+                peak_memory_kb: usage.map(|usage| usage.peak_memory_kb),
+                cpu_time_ms: usage.map(|usage| usage.cpu_time_ms),
+            },
+        };
+
+        event.history_id = store_script_run(&db, ScriptRunRecord::finished(
+            source, started_at, event.run_status(), event.exit_code, stdout, stderr, event.error.clone(),
+        ).with_usage(usage)).await;
+
+        println!("[Script Runner] Скрипт {} завершен с кодом {:?} за {} мс", id, event.exit_code, event.duration_ms);
+        if let Err(e) = app.emit("script-exit", event.clone()) {
+            eprintln!("[Script Runner] Ошибка отправки завершения скрипта {}: {}", id, e);
+        }
+        event
+    });
+
+    Ok((run_id, task))
+}
+
+/// Асинхронный запуск скрипта
+/// Возвращает идентификатор запуска; вывод приходит событиями script-output,
+/// завершение - событием script-exit. При заданном timeout_secs скрипт принудительно
+/// завершается по истечении времени
+#[command]
+pub async fn start_script(
+    app: AppHandle,
+    state: State<'_, ScriptRunState>,
+    db: State<'_, DbState>,
+    script: String,
+    language: String,
+    timeout_secs: Option<u64>,
+    options: Option<ScriptRunOptions>,
+) -> Result<String, String> {
+    let (run_id, _) = launch_script(app, &state, &db, script, language, timeout_secs, options.unwrap_or_default()).await?;
+    Ok(run_id)
+}
+
+/// Отмена запущенного скрипта (завершается все дерево процессов)
+#[command]
+pub async fn cancel_script(state: State<'_, ScriptRunState>, run_id: String) -> Result<(), String> {
+    let mut runs = state.runs.lock().await;
+    let run = runs.get_mut(&run_id)
+        .ok_or_else(|| format!("Скрипт {} не найден или уже завершен", run_id))?;
+
+    match run.cancel.take() {
+        Some(cancel) => {
+            let _ = cancel.send(());
+            println!("[Script Runner] Запрошена отмена скрипта {}", run_id);
+            Ok(())
+        }
+        None => Err(format!("Отмена скрипта {} уже запрошена", run_id)),
+    }
+}
+
+/// Отправка данных на стандартный ввод скрипта
+/// close закрывает ввод (скрипт получает конец файла)
+#[command]
+pub async fn send_script_input(state: State<'_, ScriptRunState>, run_id: String, data: String, close: Option<bool>) -> Result<(), String> {
+    let stdin = {
+        let mut runs = state.runs.lock().await;
+        let run = runs.get_mut(&run_id)
+            .ok_or_else(|| format!("Скрипт {} не найден или уже завершен", run_id))?;
+        // При закрытии ввод убирается из реестра; канал закрывается после этой записи
+        let stdin = if close.unwrap_or(false) { run.stdin.take() } else { run.stdin.clone() };
+        stdin.ok_or_else(|| format!("Стандартный ввод скрипта {} закрыт", run_id))?
+    };
+
+    let mut stdin = stdin.lock().await;
+    stdin.write_all(data.as_bytes()).await
+        .map_err(|e| format!("Ошибка записи во ввод скрипта {}: {}", run_id, e))?;
+    stdin.flush().await
+        .map_err(|e| format!("Ошибка записи во ввод скрипта {}: {}", run_id, e))?;
+
+    Ok(())
+}
+
+/// Список запущенных скриптов
+#[command]
+pub async fn list_script_runs(state: State<'_, ScriptRunState>) -> Result<Vec<ScriptRunInfo>, String> {
+    let runs = state.runs.lock().await;
+    Ok(runs
+        .iter()
+        .map(|(run_id, run)| ScriptRunInfo {
+            run_id: run_id.clone(),
+            pid: run.pid,
+            language: run.language.clone(),
+            started_at: run.started_at.clone(),
+            timeout_secs: run.timeout_secs,
+        })
+        .collect())
+}
+
 /// Функция для сохранения скрипта в файл
 /// Эта функция сохраняет скрипт в папку "Документы" пользователя
 #[command]
@@ -353,4 +1096,65 @@ pub async fn save_file_to_path(path: String, content: String, with_bom: bool) ->
         Ok(_) => Ok(format!("Файл успешно сохранен в {}", path)),
         Err(e) => Err(e)
     }
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(content: &str) -> Vec<(String, String)> {
+        parse_env_file(content).unwrap()
+    }
+
+    fn pair(key: &str, value: &str) -> (String, String) {
+        (key.to_string(), value.to_string())
+    }
+
+    #[test]
+    fn env_file_plain_values_and_export() {
+        let content = "# comment\n\nA=1\nexport B = two words \nexport\tC=3\nexported=4\nD=x # note\nE=a#b\nF=\n";
+        assert_eq!(env(content), [
+            pair("A", "1"),
+            pair("B", "two words"),
+            pair("C", "3"),
+            pair("exported", "4"),
+            pair("D", "x"),
+            pair("E", "a#b"),
+            pair("F", ""),
+        ]);
+    }
+
+    #[test]
+    fn env_file_quoted_values() {
+        let content = r##"A="a" # note "b"
+B='single # not a comment' # note 'c'
+C="line\nnext\ttab \"quoted\" back\\slash"
+D="# inside"
+E='no \n escapes'
+F="=" "##;
+        assert_eq!(env(content), [
+            pair("A", "a"),
+            pair("B", "single # not a comment"),
+            pair("C", "line\nnext\ttab \"quoted\" back\\slash"),
+            pair("D", "# inside"),
+            pair("E", "no \\n escapes"),
+            pair("F", "="),
+        ]);
+    }
+
+    #[test]
+    fn env_file_errors() {
+        for content in [
+            "NOVALUE",
+            "1KEY=x",
+            "BAD-KEY=x",
+            "A=\"unclosed",
+            "A=\"escaped end\\\"",
+            "A='unclosed",
+            "A=\"a\" b",
+            "A='a'b",
+        ] {
+            assert!(parse_env_file(content).is_err(), "{}", content);
+        }
+        assert!(parse_env_file("A=1\nB").unwrap_err().starts_with("Строка 2"));
+    }
+}
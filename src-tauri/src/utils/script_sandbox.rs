@@ -0,0 +1,688 @@
+// Модуль песочницы для запуска скриптов (Linux)
+// Профиль песочницы ограничивает процесс скрипта:
+//   - лимиты ресурсов (rlimit): процессорное время, память (сегмент данных) и открытые файлы;
+//   - сеть: отдельное сетевое пространство имен, в котором есть только интерфейс lo;
+//   - файловая система: все точки монтирования только для чтения, кроме временной папки скрипта;
+//   - seccomp: запрет монтирования, создания и смены пространств имен (unshare, setns, clone
+//     с флагами CLONE_NEW*; clone3 недоступен, и libc переходит на clone), отладки чужих
+//     процессов, загрузки модулей ядра и т.п. (если ядро и архитектура поддерживают фильтры).
+// Пространства имен создаются от имени пользователя (user namespace), поэтому права администратора
+// не нужны, но ядро должно разрешать непривилегированные пространства имен пользователей.
+// Скрипт запускается через процесс-наблюдатель: он входит в новые пространства имен, запускает
+// скрипт, дожидается его и передает по каналу пиковую память и процессорное время скрипта
+// (вместе с дочерними процессами, которых скрипт дождался), а затем завершается с тем же кодом.
+// При отмене и по таймауту завершается вся группа процессов, и отчета о ресурсах нет
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+// Подсказка к ошибке запуска процесса в песочнице
+pub const SANDBOX_SPAWN_HINT: &str =
+    "Не удалось подготовить песочницу: проверьте, что ядро разрешает непривилегированные пространства имен пользователей (user namespaces)";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SandboxProfile {
+    // Лимит процессорного времени в секундах (None - без ограничения)
+    pub cpu_time_secs: Option<u64>,
+    // Лимит памяти в мегабайтах
+    pub memory_mb: Option<u64>,
+    pub max_open_files: Option<u64>,
+    // Разрешить доступ к сети
+    pub network: bool,
+    // Файловая система только для чтения (кроме временной папки скрипта)
+    pub read_only_fs: bool,
+    // Фильтр системных вызовов seccomp
+    pub seccomp: bool,
+}
+
+impl Default for SandboxProfile {
+    fn default() -> Self {
+        SandboxProfile {
+            cpu_time_secs: Some(60),
+            memory_mb: Some(1024),
+            max_open_files: Some(256),
+            network: false,
+            read_only_fs: true,
+            seccomp: true,
+        }
+    }
+}
+
+impl SandboxProfile {
+    pub fn validate(&self) -> Result<(), String> {
+        let limits = [
+            ("процессорного времени", self.cpu_time_secs),
+            ("памяти", self.memory_mb),
+            ("открытых файлов", self.max_open_files),
+        ];
+        for (name, limit) in limits {
+            if limit == Some(0) {
+                return Err(format!("Лимит {} должен быть больше нуля", name));
+            }
+        }
+        Ok(())
+    }
+
+    fn needs_namespaces(&self) -> bool {
+        !self.network || self.read_only_fs
+    }
+}
+
+// Ресурсы, потребленные скриптом в песочнице
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct SandboxUsage {
+    pub peak_memory_kb: i64,
+    pub cpu_time_ms: i64,
+}
+
+// Проверка профиля и доступности песочницы в этой системе
+pub fn check_sandbox(profile: &SandboxProfile) -> Result<(), String> {
+    profile.validate()?;
+    if !cfg!(target_os = "linux") {
+        return Err("Песочница для скриптов доступна только в Linux".to_string());
+    }
+    if !profile.needs_namespaces() {
+        return Ok(());
+    }
+
+    let setting = |path: &str| std::fs::read_to_string(path).ok().map(|value| value.trim().to_string());
+    let disabled = setting("/proc/sys/user/max_user_namespaces").as_deref() == Some("0")
+        || (setting("/proc/sys/kernel/unprivileged_userns_clone").as_deref() == Some("0") && !is_root());
+    if disabled {
+        return Err("Пространства имен пользователей отключены в ядре: песочница без сети и с файловой \
+                    системой только для чтения недоступна".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn is_root() -> bool {
+    unsafe { libc::getuid() == 0 }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_root() -> bool {
+    false
+}
+
+// Отчет наблюдателя о ресурсах скрипта
+#[cfg(target_os = "linux")]
+pub struct SandboxMonitor {
+    report: std::fs::File,
+    writer: std::os::fd::OwnedFd,
+}
+
+#[cfg(not(target_os = "linux"))]
+pub struct SandboxMonitor;
+
+impl SandboxMonitor {
+    // Ресурсы скрипта; вызывается после завершения процесса
+    // (None - наблюдатель завершен до отправки отчета)
+    #[cfg(target_os = "linux")]
+    pub async fn usage(self) -> Option<SandboxUsage> {
+        use std::io::Read;
+
+        let SandboxMonitor { mut report, writer } = self;
+        // Без копии записывающего конца у родителя чтение завершится, даже если отчета нет
+        drop(writer);
+        tauri::async_runtime::spawn_blocking(move || {
+            let mut buffer = [0u8; 16];
+            report.read_exact(&mut buffer).ok()?;
+            let (memory, cpu) = buffer.split_at(8);
+            Some(SandboxUsage {
+                peak_memory_kb: i64::from_le_bytes(memory.try_into().ok()?),
+                cpu_time_ms: i64::from_le_bytes(cpu.try_into().ok()?),
+            })
+        }).await.ok().flatten()
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub async fn usage(self) -> Option<SandboxUsage> {
+        None
+    }
+}
+
+// Настройка запуска команды в песочнице; scratch - папка, доступная скрипту для записи
+#[cfg(target_os = "linux")]
+pub fn apply_sandbox(
+    command: &mut tokio::process::Command,
+    profile: &SandboxProfile,
+    scratch: &Path,
+) -> Result<SandboxMonitor, String> {
+    use std::os::fd::AsRawFd;
+
+    check_sandbox(profile)?;
+    let setup = linux::SandboxSetup::new(profile, scratch)?;
+    let (report, writer) = linux::report_pipe()
+        .map_err(|e| format!("Не удалось создать канал отчета песочницы: {}", e))?;
+    let report_fd = writer.as_raw_fd();
+
+    // Замыкание выполняется в дочернем процессе между fork и exec
+    unsafe {
+        command.pre_exec(move || setup.enter(report_fd));
+    }
+    Ok(SandboxMonitor { report, writer })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn apply_sandbox(
+    _command: &mut tokio::process::Command,
+    profile: &SandboxProfile,
+    _scratch: &Path,
+) -> Result<SandboxMonitor, String> {
+    check_sandbox(profile).map(|_| SandboxMonitor)
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::ffi::{CStr, CString};
+    use std::fs::File;
+    use std::io;
+    use std::os::fd::{FromRawFd, OwnedFd, RawFd};
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+    use std::ptr::null;
+
+    use super::SandboxProfile;
+
+    // Атрибут "только для чтения" для mount_setattr (linux/mount.h)
+    const MOUNT_ATTR_RDONLY: u64 = 0x1;
+
+    #[repr(C)]
+    struct MountAttr {
+        attr_set: u64,
+        attr_clr: u64,
+        propagation: u64,
+        userns_fd: u64,
+    }
+
+    // Архитектура в seccomp_data (linux/audit.h)
+    #[cfg(target_arch = "x86_64")]
+    const AUDIT_ARCH: u32 = 0xc000_003e;
+    #[cfg(target_arch = "aarch64")]
+    const AUDIT_ARCH: u32 = 0xc000_00b7;
+
+    // Флаги clone, создающие новые пространства имен. Они умещаются в младшие 32 бита,
+    // а CLONE_NEWTIME в clone совпадает с битом сигнала завершения и доступен только через clone3
+    const NAMESPACE_FLAGS: libc::c_int = libc::CLONE_NEWNS
+        | libc::CLONE_NEWCGROUP
+        | libc::CLONE_NEWUTS
+        | libc::CLONE_NEWIPC
+        | libc::CLONE_NEWUSER
+        | libc::CLONE_NEWPID
+        | libc::CLONE_NEWNET;
+
+    // Смещение младшей половины первого аргумента в seccomp_data (little-endian)
+    const FIRST_ARG_OFFSET: u32 = 16;
+
+    // Системные вызовы, запрещенные фильтром seccomp (возвращают EPERM)
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    const DENIED_SYSCALLS: &[libc::c_long] = &[
+        libc::SYS_mount,
+        libc::SYS_umount2,
+        libc::SYS_pivot_root,
+        libc::SYS_chroot,
+        libc::SYS_setns,
+        libc::SYS_unshare,
+        libc::SYS_ptrace,
+        libc::SYS_process_vm_readv,
+        libc::SYS_process_vm_writev,
+        libc::SYS_kexec_load,
+        libc::SYS_init_module,
+        libc::SYS_finit_module,
+        libc::SYS_delete_module,
+        libc::SYS_bpf,
+        libc::SYS_perf_event_open,
+        libc::SYS_keyctl,
+        libc::SYS_add_key,
+        libc::SYS_request_key,
+        libc::SYS_userfaultfd,
+        libc::SYS_swapon,
+        libc::SYS_swapoff,
+        libc::SYS_reboot,
+        libc::SYS_open_by_handle_at,
+        libc::SYS_fsopen,
+        libc::SYS_fsmount,
+        libc::SYS_move_mount,
+        libc::SYS_open_tree,
+        libc::SYS_mount_setattr,
+    ];
+
+    // Все, что нужно наблюдателю, готовится заранее: после fork многопоточного процесса
+    // нельзя выделять память, допустимы только системные вызовы
+    pub struct SandboxSetup {
+        // Флаги unshare (0 - без новых пространств имен)
+        namespaces: libc::c_int,
+        isolate_network: bool,
+        read_only: bool,
+        uid_map: CString,
+        gid_map: CString,
+        scratch: CString,
+        // Точки монтирования для перемонтирования по одной на ядрах без mount_setattr
+        mounts: Vec<CString>,
+        cpu_time: Option<libc::rlimit>,
+        memory: Option<libc::rlimit>,
+        open_files: Option<libc::rlimit>,
+        filter: Vec<libc::sock_filter>,
+    }
+
+    fn c_path(path: &Path) -> Result<CString, String> {
+        CString::new(path.as_os_str().as_bytes())
+            .map_err(|_| format!("Недопустимый путь {}", path.display()))
+    }
+
+    // Лимит не выше текущего жесткого: поднять его без прав администратора нельзя
+    fn limit(current: libc::rlimit, soft: u64, hard: u64) -> libc::rlimit {
+        libc::rlimit {
+            rlim_cur: soft.min(current.rlim_max),
+            rlim_max: hard.min(current.rlim_max),
+        }
+    }
+
+    // Точки монтирования из /proc/self/mountinfo (пятое поле, пробелы экранированы как \040)
+    fn mount_points() -> Vec<CString> {
+        let mountinfo = std::fs::read_to_string("/proc/self/mountinfo").unwrap_or_default();
+        mountinfo
+            .lines()
+            .filter_map(|line| line.split(' ').nth(4))
+            .filter(|point| *point != "/")
+            .filter_map(|point| {
+                let bytes = point.as_bytes();
+                let mut decoded = Vec::with_capacity(bytes.len());
+                let mut index = 0;
+                while index < bytes.len() {
+                    let octal = bytes.get(index + 1..index + 4)
+                        .filter(|_| bytes[index] == b'\\')
+                        .and_then(|digits| std::str::from_utf8(digits).ok())
+                        .and_then(|digits| u8::from_str_radix(digits, 8).ok());
+                    match octal {
+                        Some(byte) => {
+                            decoded.push(byte);
+                            index += 4;
+                        }
+                        None => {
+                            decoded.push(bytes[index]);
+                            index += 1;
+                        }
+                    }
+                }
+                CString::new(decoded).ok()
+            })
+            .collect()
+    }
+
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    pub fn seccomp_filter() -> Vec<libc::sock_filter> {
+        let statement = |code: u32, k: u32| libc::sock_filter { code: code as u16, jt: 0, jf: 0, k };
+        let jump = |code: u32, k: u32, jt: u8, jf: u8| libc::sock_filter { code: code as u16, jt, jf, k };
+        let deny = statement(libc::BPF_RET | libc::BPF_K, libc::SECCOMP_RET_ERRNO | libc::EPERM as u32);
+        let allow = statement(libc::BPF_RET | libc::BPF_K, libc::SECCOMP_RET_ALLOW);
+        let jump_equal = libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K;
+
+        let mut filter = vec![
+            // Вызовы другой архитектуры (например, 32-битные) запрещены целиком
+            statement(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, 4),
+            jump(jump_equal, AUDIT_ARCH, 1, 0),
+            deny,
+            statement(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, 0),
+        ];
+        // Вызовы x32 имеют ту же архитектуру, но номера с битом 0x40000000
+        #[cfg(target_arch = "x86_64")]
+        filter.extend([jump(libc::BPF_JMP | libc::BPF_JGE | libc::BPF_K, 0x4000_0000, 0, 1), deny]);
+        // clone разрешен только без новых пространств имен (флаги - первый аргумент на обеих архитектурах)
+        filter.extend([
+            jump(jump_equal, libc::SYS_clone as u32, 0, 4),
+            statement(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, FIRST_ARG_OFFSET),
+            jump(libc::BPF_JMP | libc::BPF_JSET | libc::BPF_K, NAMESPACE_FLAGS as u32, 0, 1),
+            deny,
+            allow,
+        ]);
+        // Флаги clone3 передаются в памяти, и фильтр не может их проверить:
+        // ENOSYS заставляет libc повторить вызов через clone
+        filter.extend([
+            jump(jump_equal, libc::SYS_clone3 as u32, 0, 1),
+            statement(libc::BPF_RET | libc::BPF_K, libc::SECCOMP_RET_ERRNO | libc::ENOSYS as u32),
+        ]);
+        for syscall in DENIED_SYSCALLS {
+            filter.extend([jump(jump_equal, *syscall as u32, 0, 1), deny]);
+        }
+        filter.push(allow);
+        filter
+    }
+
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    pub fn seccomp_filter() -> Vec<libc::sock_filter> {
+        Vec::new()
+    }
+
+    impl SandboxSetup {
+        pub fn new(profile: &SandboxProfile, scratch: &Path) -> Result<Self, String> {
+            let scratch = std::fs::canonicalize(scratch)
+                .map_err(|e| format!("Временная папка песочницы недоступна: {}", e))?;
+            let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+
+            let mut namespaces = 0;
+            if profile.needs_namespaces() {
+                namespaces |= libc::CLONE_NEWUSER;
+            }
+            if !profile.network {
+                namespaces |= libc::CLONE_NEWNET;
+            }
+            if profile.read_only_fs {
+                namespaces |= libc::CLONE_NEWNS;
+            }
+
+            let mut cpu_time = unsafe { std::mem::zeroed() };
+            let mut memory = unsafe { std::mem::zeroed() };
+            let mut open_files = unsafe { std::mem::zeroed() };
+            unsafe {
+                libc::getrlimit(libc::RLIMIT_CPU, &mut cpu_time);
+                libc::getrlimit(libc::RLIMIT_DATA, &mut memory);
+                libc::getrlimit(libc::RLIMIT_NOFILE, &mut open_files);
+            }
+
+            Ok(SandboxSetup {
+                namespaces,
+                isolate_network: !profile.network,
+                read_only: profile.read_only_fs,
+                uid_map: CString::new(format!("{0} {0} 1", uid)).unwrap_or_default(),
+                gid_map: CString::new(format!("{0} {0} 1", gid)).unwrap_or_default(),
+                scratch: c_path(&scratch)?,
+                mounts: if profile.read_only_fs { mount_points() } else { Vec::new() },
+                // По достижении мягкого лимита скрипт получает SIGXCPU, через секунду - SIGKILL
+                cpu_time: profile.cpu_time_secs
+                    .map(|secs| limit(cpu_time, secs, secs.saturating_add(1))),
+                memory: profile.memory_mb
+                    .map(|mb| mb.saturating_mul(1024 * 1024))
+                    .map(|bytes| limit(memory, bytes, bytes)),
+                open_files: profile.max_open_files
+                    .map(|count| limit(open_files, count, count)),
+                filter: if profile.seccomp { seccomp_filter() } else { Vec::new() },
+            })
+        }
+
+        // Выполняется в дочернем процессе после fork: вход в пространства имен и запуск скрипта
+        // Возвращается только в процессе скрипта, наблюдатель завершается сам
+        pub fn enter(&self, report: RawFd) -> io::Result<()> {
+            unsafe {
+                if self.namespaces != 0 {
+                    check(libc::unshare(self.namespaces))?;
+                    write_file(c"/proc/self/setgroups", b"deny")?;
+                    write_file(c"/proc/self/uid_map", self.uid_map.as_bytes())?;
+                    write_file(c"/proc/self/gid_map", self.gid_map.as_bytes())?;
+                    if self.isolate_network {
+                        loopback_up();
+                    }
+                    if self.read_only {
+                        self.mount_read_only()?;
+                    }
+                }
+
+                let supervisor = libc::getpid();
+                let pid = check(libc::fork())?;
+                if pid != 0 {
+                    supervise(pid, report);
+                }
+
+                // Скрипт не переживает наблюдателя
+                libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL as libc::c_ulong);
+                if libc::getppid() != supervisor {
+                    libc::_exit(127);
+                }
+                self.set_limits()?;
+                self.apply_seccomp()
+            }
+        }
+
+        unsafe fn mount_read_only(&self) -> io::Result<()> {
+            let root = c"/".as_ptr();
+            // Изменения точек монтирования не должны распространяться за пределы песочницы
+            check(libc::mount(null(), root, null(), libc::MS_REC | libc::MS_PRIVATE, null()))?;
+
+            let attr = MountAttr { attr_set: MOUNT_ATTR_RDONLY, attr_clr: 0, propagation: 0, userns_fd: 0 };
+            let result = libc::syscall(
+                libc::SYS_mount_setattr,
+                libc::AT_FDCWD,
+                root,
+                libc::AT_RECURSIVE,
+                &attr as *const MountAttr,
+                std::mem::size_of::<MountAttr>(),
+            );
+            if result != 0 {
+                let error = io::Error::last_os_error();
+                if error.raw_os_error() != Some(libc::ENOSYS) {
+                    return Err(error);
+                }
+                // Ядро до 5.12: точки монтирования перемонтируются по одной
+                remount(root, libc::MS_RDONLY)?;
+                for mount in &self.mounts {
+                    let _ = remount(mount.as_ptr(), libc::MS_RDONLY);
+                }
+            }
+
+            // Временная папка - отдельная точка монтирования, доступная для записи
+            check(libc::mount(self.scratch.as_ptr(), self.scratch.as_ptr(), null(), libc::MS_BIND, null()))?;
+            remount(self.scratch.as_ptr(), 0)
+        }
+
+        unsafe fn set_limits(&self) -> io::Result<()> {
+            if let Some(limit) = &self.cpu_time {
+                check(libc::setrlimit(libc::RLIMIT_CPU, limit))?;
+            }
+            if let Some(limit) = &self.memory {
+                check(libc::setrlimit(libc::RLIMIT_DATA, limit))?;
+            }
+            if let Some(limit) = &self.open_files {
+                check(libc::setrlimit(libc::RLIMIT_NOFILE, limit))?;
+            }
+            Ok(())
+        }
+
+        unsafe fn apply_seccomp(&self) -> io::Result<()> {
+            if self.filter.is_empty() {
+                return Ok(());
+            }
+            let program = libc::sock_fprog {
+                len: self.filter.len() as libc::c_ushort,
+                filter: self.filter.as_ptr() as *mut libc::sock_filter,
+            };
+            check(libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1 as libc::c_ulong, 0 as libc::c_ulong, 0 as libc::c_ulong, 0 as libc::c_ulong))?;
+            let result = libc::prctl(
+                libc::PR_SET_SECCOMP,
+                libc::SECCOMP_MODE_FILTER as libc::c_ulong,
+                &program as *const libc::sock_fprog,
+            );
+            // Ядро без фильтров seccomp: скрипт запускается без них
+            if result != 0 && io::Error::last_os_error().raw_os_error() != Some(libc::EINVAL) {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        }
+    }
+
+    // Канал отчета: конец для чтения у родителя, для записи - у наблюдателя
+    pub fn report_pipe() -> io::Result<(File, OwnedFd)> {
+        let mut fds = [0; 2];
+        unsafe {
+            check(libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC))?;
+            Ok((File::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])))
+        }
+    }
+
+    fn check(result: libc::c_int) -> io::Result<libc::c_int> {
+        if result < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(result)
+        }
+    }
+
+    unsafe fn write_file(path: &CStr, content: &[u8]) -> io::Result<()> {
+        let fd = check(libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC))?;
+        let written = libc::write(fd, content.as_ptr().cast(), content.len());
+        let error = io::Error::last_os_error();
+        libc::close(fd);
+        if written != content.len() as isize {
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    // Перемонтирование с сохранением флагов, которые нельзя сбросить в пространстве имен пользователя
+    unsafe fn remount(target: *const libc::c_char, flags: libc::c_ulong) -> io::Result<()> {
+        let mut stat: libc::statvfs = std::mem::zeroed();
+        check(libc::statvfs(target, &mut stat))?;
+        let kept = [
+            (libc::ST_NOSUID, libc::MS_NOSUID),
+            (libc::ST_NODEV, libc::MS_NODEV),
+            (libc::ST_NOEXEC, libc::MS_NOEXEC),
+            (libc::ST_NOATIME, libc::MS_NOATIME),
+            (libc::ST_NODIRATIME, libc::MS_NODIRATIME),
+            (libc::ST_RELATIME, libc::MS_RELATIME),
+        ]
+        .iter()
+        .filter(|(stat_flag, _)| stat.f_flag & stat_flag != 0)
+        .fold(0, |kept, (_, mount_flag)| kept | mount_flag);
+        check(libc::mount(null(), target, null(), libc::MS_REMOUNT | libc::MS_BIND | flags | kept, null()))
+            .map(|_| ())
+    }
+
+    // В новом сетевом пространстве имен интерфейс lo выключен; без него не работает localhost
+    unsafe fn loopback_up() {
+        let socket = libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0);
+        if socket < 0 {
+            return;
+        }
+        let mut request: libc::ifreq = std::mem::zeroed();
+        for (slot, byte) in request.ifr_name.iter_mut().zip(b"lo") {
+            *slot = *byte as libc::c_char;
+        }
+        if libc::ioctl(socket, libc::SIOCGIFFLAGS as _, &mut request) == 0 {
+            request.ifr_ifru.ifru_flags |= libc::IFF_UP as libc::c_short;
+            libc::ioctl(socket, libc::SIOCSIFFLAGS as _, &request);
+        }
+        libc::close(socket);
+    }
+
+    // Закрытие всех дескрипторов, кроме канала отчета
+    unsafe fn close_descriptors(keep: RawFd) {
+        let close_range = |first: RawFd, last: libc::c_uint| {
+            libc::syscall(libc::SYS_close_range, first as libc::c_uint, last, 0 as libc::c_uint) == 0
+        };
+        let closed = (keep == 0 || close_range(0, keep as libc::c_uint - 1))
+            && close_range(keep + 1, libc::c_uint::MAX);
+        if !closed {
+            let mut limit: libc::rlimit = std::mem::zeroed();
+            libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit);
+            for fd in 0..limit.rlim_cur.min(65536) as RawFd {
+                if fd != keep {
+                    libc::close(fd);
+                }
+            }
+        }
+    }
+
+    // На 32-битных платформах поля timeval 32-битные
+    #[allow(clippy::unnecessary_cast)]
+    fn timeval_ms(time: libc::timeval) -> i64 {
+        time.tv_sec as i64 * 1000 + time.tv_usec as i64 / 1000
+    }
+
+    // Наблюдатель: ожидание скрипта, отчет о ресурсах и завершение с результатом скрипта
+    unsafe fn supervise(pid: libc::pid_t, report: RawFd) -> ! {
+        // Копии каналов вывода и служебного канала запуска не должны задерживать родителя
+        close_descriptors(report);
+
+        let mut status = 0;
+        let mut usage: libc::rusage = std::mem::zeroed();
+        while libc::wait4(pid, &mut status, 0, &mut usage) < 0 {
+            if io::Error::last_os_error().raw_os_error() != Some(libc::EINTR) {
+                libc::_exit(127);
+            }
+        }
+
+        let mut buffer = [0u8; 16];
+        buffer[..8].copy_from_slice(&(usage.ru_maxrss as i64).to_le_bytes());
+        buffer[8..].copy_from_slice(&(timeval_ms(usage.ru_utime) + timeval_ms(usage.ru_stime)).to_le_bytes());
+        libc::write(report, buffer.as_ptr().cast(), buffer.len());
+
+        if libc::WIFSIGNALED(status) {
+            // Скрипт завершен сигналом - наблюдатель завершается тем же сигналом
+            let signal = libc::WTERMSIG(status);
+            let mut set: libc::sigset_t = std::mem::zeroed();
+            libc::sigemptyset(&mut set);
+            libc::sigaddset(&mut set, signal);
+            libc::signal(signal, libc::SIG_DFL);
+            libc::sigprocmask(libc::SIG_UNBLOCK, &set, std::ptr::null_mut());
+            libc::kill(libc::getpid(), signal);
+            libc::_exit(128 + signal);
+        }
+        libc::_exit(libc::WEXITSTATUS(status))
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use std::io;
+
+    // Фильтр действует только на поток, который его установил
+    #[test]
+    fn seccomp_denies_namespace_creation() {
+        let filter = linux::seccomp_filter();
+        if filter.is_empty() {
+            return;
+        }
+
+        let errors = std::thread::spawn(move || unsafe {
+            let program = libc::sock_fprog { len: filter.len() as libc::c_ushort, filter: filter.as_ptr() as *mut _ };
+            assert_eq!(libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1 as libc::c_ulong, 0 as libc::c_ulong, 0 as libc::c_ulong, 0 as libc::c_ulong), 0);
+            assert_eq!(libc::prctl(libc::PR_SET_SECCOMP, libc::SECCOMP_MODE_FILTER as libc::c_ulong, &program as *const libc::sock_fprog), 0);
+
+            let error = |result: libc::c_long| (result == -1).then(|| io::Error::last_os_error().raw_os_error()).flatten();
+            let flags = (libc::CLONE_NEWUSER | libc::SIGCHLD) as libc::c_ulong;
+            [
+                error(libc::syscall(libc::SYS_unshare, libc::CLONE_NEWNET)),
+                error(libc::syscall(libc::SYS_clone, flags, 0, 0, 0, 0)),
+                error(libc::syscall(libc::SYS_clone3, std::ptr::null::<u8>(), 0)),
+            ]
+        }).join().unwrap();
+
+        assert_eq!(errors, [Some(libc::EPERM), Some(libc::EPERM), Some(libc::ENOSYS)]);
+    }
+
+    #[tokio::test]
+    async fn sandboxed_script_is_isolated() {
+        let profile = SandboxProfile::default();
+        if let Err(e) = check_sandbox(&profile) {
+            eprintln!("Тест пропущен: {}", e);
+            return;
+        }
+
+        let scratch = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        let script = "echo ok > \"$1/inside\" && echo SCRATCH_OK\n\
+                      echo x > \"$2/outside\" || echo WRITE_FAILED\n\
+                      (exec 3<>/dev/tcp/192.0.2.1/80) || echo CONNECT_FAILED";
+
+        let mut command = tokio::process::Command::new("bash");
+        command
+            .args(["-c", script, "bash"])
+            .arg(scratch.path())
+            .arg(outside.path())
+            .env("LC_ALL", "C")
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped());
+        let monitor = apply_sandbox(&mut command, &profile, scratch.path()).unwrap();
+        let output = command.output().await.unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+
+        assert!(stdout.contains("SCRATCH_OK"), "{}", stderr);
+        assert!(stdout.contains("WRITE_FAILED") && stderr.contains("Read-only file system"), "{}", stderr);
+        assert!(!outside.path().join("outside").exists());
+        assert!(stdout.contains("CONNECT_FAILED"), "{}", stderr);
+        assert!(monitor.usage().await.is_some());
+    }
+}
@@ -0,0 +1,819 @@
+// Модуль планировщика скриптов
+// Расписание связывает скрипт библиотеки с условием запуска: cron-выражение, интервал,
+// запуск приложения, открытие или закрытие локального TCP-порта. Фоновая задача раз в секунду
+// сверяет расписания из базы (таблица script_schedules) и запускает скрипты через исполнитель
+// скриптов, поэтому такие запуски видны в реестре, их можно отменить, и они попадают в историю.
+// Итог последнего запуска сохраняется в расписании, при неудаче отправляется событие
+// script-schedule-failed. Запуски, пропущенные пока приложение было закрыто, не повторяются
+
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, Local, LocalResult, NaiveDate, TimeZone, Timelike, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, State};
+use uuid::Uuid;
+
+use crate::utils::db::{utc_timestamp, DbState};
+use crate::utils::script_history::RunStatus;
+use crate::utils::script_library::load_script;
+use crate::utils::script_params::{parse_parameters, resolve_parameters};
+use crate::utils::script_runner::{launch_script, ScriptRunOptions, ScriptRunState};
+use crate::utils::script_sandbox::check_sandbox;
+
+// Период проверки расписаний
+const SCHEDULER_TICK: Duration = Duration::from_secs(1);
+
+// Как часто проверять порты для условий открытия и закрытия порта
+const PORT_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+// Сколько ждать подключения к порту при проверке
+const PORT_PROBE_TIMEOUT: Duration = Duration::from_millis(300);
+
+// Предел шагов поиска следующего срабатывания cron (выражение вроде "0 0 31 2 *" не срабатывает никогда)
+const MAX_CRON_STEPS: usize = 100_000;
+
+// Сколько срабатываний cron-выражения можно запросить для предпросмотра
+const MAX_CRON_PREVIEW: usize = 50;
+
+const CRON_FIELDS: [&str; 5] = ["минута", "час", "день месяца", "месяц", "день недели"];
+const MONTH_NAMES: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+const WEEKDAY_NAMES: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+fn default_true() -> bool {
+    true
+}
+
+// Разобранное cron-выражение из 5 полей: минута, час, день месяца, месяц, день недели
+// Значения полей хранятся битовыми масками; время срабатывания считается по местному времени
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronExpression {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    // Если одно из полей дня задано "*", учитывается только другое,
+    // иначе подходит день, совпавший с любым из них (как в cron)
+    days_any: bool,
+    weekdays_any: bool,
+}
+
+fn parse_cron_value(value: &str, min: u32, max: u32, names: &[&str], first_name: u32) -> Result<u32, String> {
+    let lower = value.to_ascii_lowercase();
+    if let Some(index) = names.iter().position(|name| *name == lower) {
+        return Ok(index as u32 + first_name);
+    }
+
+    let number: u32 = value.parse()
+        .map_err(|_| format!("некорректное значение {:?}", value))?;
+    if number < min || number > max {
+        return Err(format!("значение {} вне диапазона {}-{}", number, min, max));
+    }
+    Ok(number)
+}
+
+// Поле cron: список через запятую из "*", значений, диапазонов "a-b" и шагов "*/n", "a-b/n", "a/n"
+fn parse_cron_field(field: &str, min: u32, max: u32, names: &[&str], first_name: u32) -> Result<u64, String> {
+    let mut mask = 0u64;
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step = step.parse::<u32>().ok().filter(|step| *step > 0)
+                    .ok_or_else(|| format!("некорректный шаг {:?}", step))?;
+                (range, Some(step))
+            }
+            None => (part, None),
+        };
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (parse_cron_value(start, min, max, names, first_name)?, parse_cron_value(end, min, max, names, first_name)?)
+        } else {
+            let value = parse_cron_value(range, min, max, names, first_name)?;
+            // "a/n" - от a до конца диапазона с шагом n
+            (value, if step.is_some() { max } else { value })
+        };
+        if start > end {
+            return Err(format!("пустой диапазон {:?}", range));
+        }
+
+        for value in (start..=end).step_by(step.unwrap_or(1) as usize) {
+            mask |= 1 << value;
+        }
+    }
+
+    Ok(mask)
+}
+
+impl CronExpression {
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        let expression = match expression.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            "@reboot" => return Err("Для запуска при старте используйте условие startup".to_string()),
+            other => other,
+        };
+
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        if fields.len() != CRON_FIELDS.len() {
+            return Err(format!(
+                "Cron-выражение должно состоять из 5 полей (минута, час, день месяца, месяц, день недели): {:?}",
+                expression
+            ));
+        }
+
+        let field = |index: usize, min: u32, max: u32, names: &[&str], first_name: u32| {
+            parse_cron_field(fields[index], min, max, names, first_name)
+                .map_err(|e| format!("Поле \"{}\" cron-выражения: {}", CRON_FIELDS[index], e))
+        };
+
+        let mut weekdays = field(4, 0, 7, &WEEKDAY_NAMES, 0)?;
+        // 7 - тоже воскресенье
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+
+        Ok(CronExpression {
+            minutes: field(0, 0, 59, &[], 0)?,
+            hours: field(1, 0, 23, &[], 0)?,
+            days: field(2, 1, 31, &[], 0)?,
+            months: field(3, 1, 12, &MONTH_NAMES, 1)?,
+            weekdays,
+            days_any: fields[2].starts_with('*'),
+            weekdays_any: fields[4].starts_with('*'),
+        })
+    }
+
+    fn matches_day(&self, date: NaiveDate) -> bool {
+        let day = self.days & (1 << date.day()) != 0;
+        let weekday = self.weekdays & (1 << date.weekday().num_days_from_sunday()) != 0;
+
+        match (self.days_any, self.weekdays_any) {
+            (true, _) => weekday,
+            (false, true) => day,
+            (false, false) => day || weekday,
+        }
+    }
+
+    // Ближайшее срабатывание строго после указанного времени (None - выражение не срабатывает)
+    // Время считается в часовом поясе after, в приложении - в местном
+    pub fn next_after<Tz: TimeZone>(&self, after: DateTime<Tz>) -> Option<DateTime<Tz>> {
+        let mut time = after.naive_local().with_second(0)?.with_nanosecond(0)? + ChronoDuration::minutes(1);
+
+        for _ in 0..MAX_CRON_STEPS {
+            if self.months & (1 << time.month()) == 0 {
+                let (year, month) = match time.month() {
+                    12 => (time.year() + 1, 1),
+                    month => (time.year(), month + 1),
+                };
+                time = NaiveDate::from_ymd_opt(year, month, 1)?.and_hms_opt(0, 0, 0)?;
+                continue;
+            }
+            if !self.matches_day(time.date()) {
+                time = time.date().succ_opt()?.and_hms_opt(0, 0, 0)?;
+                continue;
+            }
+            if self.hours & (1 << time.hour()) == 0 {
+                time = time.with_minute(0)? + ChronoDuration::hours(1);
+                continue;
+            }
+            if self.minutes & (1 << time.minute()) == 0 {
+                time += ChronoDuration::minutes(1);
+                continue;
+            }
+
+            // При переводе часов время может отсутствовать или повторяться
+            match after.timezone().from_local_datetime(&time) {
+                LocalResult::Single(local) | LocalResult::Ambiguous(local, _) if local > after => return Some(local),
+                _ => time += ChronoDuration::minutes(1),
+            }
+        }
+
+        None
+    }
+}
+
+// Условие запуска по расписанию
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ScheduleTrigger {
+    Cron { expression: String },
+    Interval { seconds: u64 },
+    // Один раз при запуске приложения
+    Startup,
+    // Порт на localhost начал принимать подключения
+    PortOpen { port: u16 },
+    // Порт на localhost перестал принимать подключения
+    PortClose { port: u16 },
+}
+
+impl ScheduleTrigger {
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            ScheduleTrigger::Cron { expression } => {
+                CronExpression::parse(expression)?
+                    .next_after(Local::now())
+                    .map(|_| ())
+                    .ok_or_else(|| format!("Cron-выражение {:?} никогда не срабатывает", expression))
+            }
+            ScheduleTrigger::Interval { seconds: 0 } => Err("Интервал должен быть больше нуля".to_string()),
+            ScheduleTrigger::PortOpen { port: 0 } | ScheduleTrigger::PortClose { port: 0 } => {
+                Err("Некорректный номер порта".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn port(&self) -> Option<u16> {
+        match self {
+            ScheduleTrigger::PortOpen { port } | ScheduleTrigger::PortClose { port } => Some(*port),
+            _ => None,
+        }
+    }
+
+    // Следующий запуск по времени (для условий по порту - None)
+    // Условие startup срабатывает только при первой проверке после запуска приложения
+    fn due_after(&self, now: DateTime<Local>, startup: bool) -> Option<DateTime<Local>> {
+        match self {
+            ScheduleTrigger::Cron { expression } => CronExpression::parse(expression).ok()?.next_after(now),
+            ScheduleTrigger::Interval { seconds } => Some(now + ChronoDuration::seconds(*seconds as i64)),
+            ScheduleTrigger::Startup => startup.then_some(now),
+            ScheduleTrigger::PortOpen { .. } | ScheduleTrigger::PortClose { .. } => None,
+        }
+    }
+}
+
+// Расписание с данными для создания или изменения (id не задан - новое расписание)
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScheduleDraft {
+    #[serde(default)]
+    pub id: Option<String>,
+    pub script_id: String,
+    pub trigger: ScheduleTrigger,
+    // Аргументы, окружение и рабочая директория запуска
+    #[serde(default)]
+    pub options: ScriptRunOptions,
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScriptSchedule {
+    pub id: String,
+    pub script_id: String,
+    pub script_name: String,
+    pub trigger: ScheduleTrigger,
+    pub options: ScriptRunOptions,
+    pub timeout_secs: Option<u64>,
+    pub enabled: bool,
+    // Следующий запуск по времени (заполняет планировщик)
+    pub next_run_at: Option<String>,
+    pub last_run_at: Option<String>,
+    pub last_status: Option<RunStatus>,
+    // id последнего запуска в истории запусков
+    pub last_history_id: Option<i64>,
+    pub last_error: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+// Событие script-schedule-failed: запуск по расписанию завершился неудачно
+#[derive(Debug, Clone, Serialize)]
+pub struct ScheduleFailureEvent {
+    pub schedule_id: String,
+    pub script_id: String,
+    pub script_name: String,
+    // id запуска в реестре (None - скрипт не удалось запустить)
+    pub run_id: Option<String>,
+    pub history_id: Option<i64>,
+    pub status: RunStatus,
+    pub exit_code: Option<i32>,
+    pub error: Option<String>,
+}
+
+fn json_column<T: DeserializeOwned>(value: String) -> rusqlite::Result<T> {
+    serde_json::from_str(&value).map_err(|e| rusqlite::Error::FromSqlConversionFailure(
+        0, rusqlite::types::Type::Text, Box::new(e),
+    ))
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<String, String> {
+    serde_json::to_string(value).map_err(|e| format!("Ошибка сериализации расписания: {}", e))
+}
+
+const SCHEDULE_COLUMNS: &str = "sc.id, sc.script_id, s.name, sc.trigger_spec, sc.options, sc.timeout_secs, sc.enabled,
+    sc.next_run_at, sc.last_run_at, sc.last_status, sc.last_history_id, sc.last_error, sc.created_at, sc.updated_at";
+
+fn schedule_from_row(row: &Row) -> rusqlite::Result<ScriptSchedule> {
+    Ok(ScriptSchedule {
+        id: row.get(0)?,
+        script_id: row.get(1)?,
+        script_name: row.get(2)?,
+        trigger: json_column(row.get(3)?)?,
+        options: json_column(row.get(4)?)?,
+        timeout_secs: row.get(5)?,
+        enabled: row.get(6)?,
+        next_run_at: row.get(7)?,
+        last_run_at: row.get(8)?,
+        last_status: row.get::<_, Option<String>>(9)?.map(|status| RunStatus::parse(&status)),
+        last_history_id: row.get(10)?,
+        last_error: row.get(11)?,
+        created_at: row.get(12)?,
+        updated_at: row.get(13)?,
+    })
+}
+
+fn load_schedules(conn: &Connection) -> Result<Vec<ScriptSchedule>, String> {
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT {} FROM script_schedules sc JOIN scripts s ON s.id = sc.script_id ORDER BY sc.created_at, sc.id",
+        SCHEDULE_COLUMNS
+    )).map_err(|e| format!("Ошибка подготовки запроса: {}", e))?;
+
+    let schedules = stmt.query_map([], schedule_from_row)
+        .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+        .map_err(|e| format!("Ошибка выполнения запроса: {}", e))?;
+    Ok(schedules)
+}
+
+fn load_schedule(conn: &Connection, id: &str) -> Result<ScriptSchedule, String> {
+    conn.prepare_cached(&format!(
+        "SELECT {} FROM script_schedules sc JOIN scripts s ON s.id = sc.script_id WHERE sc.id = ?",
+        SCHEDULE_COLUMNS
+    ))
+    .and_then(|mut stmt| stmt.query_row(params![id], schedule_from_row).optional())
+    .map_err(|e| format!("Ошибка выполнения запроса: {}", e))?
+    .ok_or_else(|| format!("Расписание {} не найдено", id))
+}
+
+// Принимает ли порт на localhost подключения
+async fn probe_port(port: u16) -> bool {
+    for host in ["127.0.0.1", "::1"] {
+        let connect = tokio::net::TcpStream::connect((host, port));
+        if let Ok(Ok(_)) = tokio::time::timeout(PORT_PROBE_TIMEOUT, connect).await {
+            return true;
+        }
+    }
+    false
+}
+
+// Состояние расписания в планировщике
+struct ScheduleRuntime {
+    // Условие, для которого посчитано состояние (при изменении расписания оно сбрасывается)
+    trigger: ScheduleTrigger,
+    next_due: Option<DateTime<Local>>,
+    // Последнее известное состояние порта (None - еще не проверялся)
+    port_open: Option<bool>,
+}
+
+struct Scheduler {
+    app: AppHandle,
+    db: DbState,
+    runs: ScriptRunState,
+    schedules: HashMap<String, ScheduleRuntime>,
+    // Расписания, скрипт которых еще выполняется: повторно они не запускаются
+    running: Arc<Mutex<HashSet<String>>>,
+    // Первая проверка после запуска приложения
+    startup: bool,
+    last_port_poll: Option<Instant>,
+}
+
+impl Scheduler {
+    async fn tick(&mut self) -> Result<(), String> {
+        let schedules: Vec<ScriptSchedule> = self.db.run(|conn| load_schedules(conn)).await?
+            .into_iter()
+            .filter(|schedule| schedule.enabled)
+            .collect();
+        let now = Local::now();
+        self.schedules.retain(|id, _| schedules.iter().any(|schedule| &schedule.id == id));
+
+        // Порты проверяются реже остальных условий
+        let mut ports = HashMap::new();
+        let poll_ports = match self.last_port_poll {
+            Some(last_poll) => last_poll.elapsed() >= PORT_POLL_INTERVAL,
+            None => true,
+        };
+        if poll_ports {
+            for port in schedules.iter().filter_map(|schedule| schedule.trigger.port()) {
+                if let std::collections::hash_map::Entry::Vacant(entry) = ports.entry(port) {
+                    entry.insert(probe_port(port).await);
+                }
+            }
+            self.last_port_poll = Some(Instant::now());
+        }
+
+        let mut due_schedules = Vec::new();
+        let mut next_runs = Vec::new();
+        for schedule in schedules {
+            let startup = self.startup;
+            let runtime = self.schedules.entry(schedule.id.clone()).or_insert_with(|| ScheduleRuntime {
+                trigger: schedule.trigger.clone(),
+                next_due: schedule.trigger.due_after(now, startup),
+                port_open: None,
+            });
+            if runtime.trigger != schedule.trigger {
+                *runtime = ScheduleRuntime {
+                    trigger: schedule.trigger.clone(),
+                    next_due: schedule.trigger.due_after(now, false),
+                    port_open: None,
+                };
+            }
+
+            let due = match &schedule.trigger {
+                ScheduleTrigger::PortOpen { port } | ScheduleTrigger::PortClose { port } => {
+                    let wanted = matches!(schedule.trigger, ScheduleTrigger::PortOpen { .. });
+                    match ports.get(port) {
+                        // Срабатывает только смена состояния, а не исходное состояние порта
+                        Some(&open) => runtime.port_open.replace(open) == Some(!wanted) && open == wanted,
+                        None => false,
+                    }
+                }
+                _ => runtime.next_due.is_some_and(|due| due <= now),
+            };
+            if due {
+                runtime.next_due = schedule.trigger.due_after(now, false);
+            }
+
+            let next_run_at = runtime.next_due.map(|due| utc_timestamp(due.with_timezone(&Utc)));
+            if next_run_at != schedule.next_run_at {
+                next_runs.push((schedule.id.clone(), next_run_at));
+            }
+            if due {
+                due_schedules.push(schedule);
+            }
+        }
+        self.startup = false;
+
+        if !next_runs.is_empty() {
+            self.db.run(move |conn| {
+                for (id, next_run_at) in &next_runs {
+                    conn.prepare_cached("UPDATE script_schedules SET next_run_at = ? WHERE id = ?")
+                        .and_then(|mut stmt| stmt.execute(params![next_run_at, id]))
+                        .map_err(|e| format!("Не удалось сохранить время следующего запуска: {}", e))?;
+                }
+                Ok(())
+            }).await?;
+        }
+
+        for schedule in due_schedules {
+            self.launch(schedule);
+        }
+        Ok(())
+    }
+
+    fn launch(&self, schedule: ScriptSchedule) {
+        if !self.running.lock().unwrap().insert(schedule.id.clone()) {
+            println!("[Scheduler] Пропуск запуска по расписанию {}: предыдущий запуск еще выполняется", schedule.id);
+            return;
+        }
+
+        let (app, db, runs, running) = (self.app.clone(), self.db.clone(), self.runs.clone(), self.running.clone());
+        tauri::async_runtime::spawn(async move {
+            run_scheduled(app, &db, &runs, &schedule).await;
+            running.lock().unwrap().remove(&schedule.id);
+        });
+    }
+}
+
+// Запуск скрипта по расписанию и сохранение итога
+async fn run_scheduled(app: AppHandle, db: &DbState, runs: &ScriptRunState, schedule: &ScriptSchedule) {
+    println!("[Scheduler] Запуск скрипта {} по расписанию {}", schedule.script_name, schedule.id);
+    let started_at = utc_timestamp(Utc::now());
+
+    let outcome = async {
+        let script_id = schedule.script_id.clone();
+        let script = db.run(move |conn| load_script(conn, &script_id)).await?;
+        let options = ScriptRunOptions { script_id: Some(script.id.clone()), ..schedule.options.clone() };
+
+        let (run_id, task) = launch_script(
+            app.clone(), runs, db, script.content, script.language, schedule.timeout_secs, options,
+        ).await?;
+        let event = task.await
+            .map_err(|e| format!("Ошибка ожидания завершения скрипта: {}", e))?;
+        Ok::<_, String>((run_id, event))
+    }.await;
+
+    let failure = match outcome {
+        Ok((run_id, event)) => ScheduleFailureEvent {
+            schedule_id: schedule.id.clone(),
+            script_id: schedule.script_id.clone(),
+            script_name: schedule.script_name.clone(),
+            status: event.run_status(),
+            run_id: Some(run_id),
+            history_id: event.history_id,
+            exit_code: event.exit_code,
+            error: event.error,
+        },
+        Err(error) => ScheduleFailureEvent {
+            schedule_id: schedule.id.clone(),
+            script_id: schedule.script_id.clone(),
+            script_name: schedule.script_name.clone(),
+            run_id: None,
+            history_id: None,
+            status: RunStatus::Error,
+            exit_code: None,
+            error: Some(error),
+        },
+    };
+
+    let (id, status, history_id, error) = (
+        schedule.id.clone(), failure.status, failure.history_id, failure.error.clone(),
+    );
+    let saved = db.run(move |conn| {
+        conn.prepare_cached(
+            "UPDATE script_schedules SET last_run_at = ?, last_status = ?, last_history_id = ?, last_error = ? WHERE id = ?",
+        )
+        .and_then(|mut stmt| stmt.execute(params![started_at, status.as_str(), history_id, error, id]))
+        .map_err(|e| format!("Не удалось сохранить итог запуска по расписанию: {}", e))
+    }).await;
+    if let Err(e) = saved {
+        eprintln!("[Scheduler] {}", e);
+    }
+
+    if failure.status != RunStatus::Success {
+        eprintln!(
+            "[Scheduler] Запуск скрипта {} по расписанию {} завершился неудачно: {:?} (код {:?})",
+            schedule.script_name, schedule.id, failure.status, failure.exit_code
+        );
+        if let Err(e) = app.emit("script-schedule-failed", failure) {
+            eprintln!("[Scheduler] Ошибка отправки события о неудачном запуске: {}", e);
+        }
+    }
+}
+
+// Запуск фоновой задачи планировщика скриптов
+pub fn start_script_scheduler(app_handle: AppHandle, db: DbState, runs: ScriptRunState) {
+    println!("[Scheduler] Запуск планировщика скриптов");
+
+    tauri::async_runtime::spawn(async move {
+        let mut scheduler = Scheduler {
+            app: app_handle,
+            db,
+            runs,
+            schedules: HashMap::new(),
+            running: Arc::new(Mutex::new(HashSet::new())),
+            startup: true,
+            last_port_poll: None,
+        };
+
+        loop {
+            if let Err(e) = scheduler.tick().await {
+                eprintln!("[Scheduler] Ошибка проверки расписаний: {}", e);
+            }
+            tokio::time::sleep(SCHEDULER_TICK).await;
+        }
+    });
+}
+
+// Расписание хранится открытым текстом (и попадает в архив данных), поэтому значения секретных
+// параметров в нем не сохраняются: такой параметр объявляется optional и задается в файле окружения
+fn reject_secret_values(script: &str, params: &BTreeMap<String, Value>, env: &BTreeMap<String, String>) -> Result<(), String> {
+    let secrets: Vec<String> = parse_parameters(script)?
+        .into_iter()
+        .filter(|parameter| parameter.secret)
+        .filter(|parameter| params.contains_key(&parameter.name) || env.contains_key(&parameter.name))
+        .map(|parameter| parameter.name)
+        .collect();
+
+    if secrets.is_empty() {
+        return Ok(());
+    }
+    Err(format!(
+        "Значения секретных параметров не сохраняются в расписании ({}): объявите их optional и задайте в файле окружения",
+        secrets.join(", ")
+    ))
+}
+
+#[tauri::command]
+pub async fn list_script_schedules(db: State<'_, DbState>) -> Result<Vec<ScriptSchedule>, String> {
+    db.run(|conn| load_schedules(conn)).await
+}
+
+// Создание или изменение расписания
+#[tauri::command]
+pub async fn save_script_schedule(db: State<'_, DbState>, schedule: ScheduleDraft) -> Result<ScriptSchedule, String> {
+    schedule.trigger.validate()?;
+    if let Some(profile) = &schedule.options.sandbox {
+        check_sandbox(profile)?;
+    }
+    let trigger = to_json(&schedule.trigger)?;
+    let params = schedule.options.params.clone();
+    let env = schedule.options.env.clone();
+    let options = to_json(&ScriptRunOptions { script_id: None, ..schedule.options })?;
+
+    db.run(move |conn| {
+        // Запуск по расписанию нельзя дополнить параметрами, поэтому они проверяются заранее
+        let script = load_script(conn, &schedule.script_id)?;
+        reject_secret_values(&script.content, &params, &env)?;
+        resolve_parameters(&script.content, &params)?;
+        let now = utc_timestamp(Utc::now());
+
+        let id = match schedule.id {
+            Some(id) => {
+                let updated = conn.prepare_cached(
+                    "UPDATE script_schedules SET script_id = ?, trigger_spec = ?, options = ?, timeout_secs = ?,
+                     enabled = ?, updated_at = ? WHERE id = ?",
+                )
+                .and_then(|mut stmt| stmt.execute(params![
+                    schedule.script_id, trigger, options, schedule.timeout_secs, schedule.enabled, now, id
+                ]))
+                .map_err(|e| format!("Не удалось сохранить расписание: {}", e))?;
+                if updated == 0 {
+                    return Err(format!("Расписание {} не найдено", id));
+                }
+                id
+            }
+            None => {
+                let id = Uuid::new_v4().to_string();
+                conn.prepare_cached(
+                    "INSERT INTO script_schedules (id, script_id, trigger_spec, options, timeout_secs, enabled, created_at, updated_at)
+                     VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+                )
+                .and_then(|mut stmt| stmt.execute(params![
+                    id, schedule.script_id, trigger, options, schedule.timeout_secs, schedule.enabled, now, now
+                ]))
+                .map_err(|e| format!("Не удалось сохранить расписание: {}", e))?;
+                id
+            }
+        };
+
+        println!("[Scheduler] Сохранено расписание {} для скрипта {}", id, schedule.script_id);
+        load_schedule(conn, &id)
+    }).await
+}
+
+#[tauri::command]
+pub async fn delete_script_schedule(db: State<'_, DbState>, id: String) -> Result<(), String> {
+    db.run(move |conn| {
+        let deleted = conn.prepare_cached("DELETE FROM script_schedules WHERE id = ?")
+            .and_then(|mut stmt| stmt.execute(params![id]))
+            .map_err(|e| format!("Ошибка удаления расписания: {}", e))?;
+        if deleted == 0 {
+            return Err(format!("Расписание {} не найдено", id));
+        }
+
+        println!("[Scheduler] Удалено расписание {}", id);
+        Ok(())
+    }).await
+}
+
+// Ближайшие срабатывания cron-выражения (местное время в RFC 3339) для проверки выражения в интерфейсе
+#[tauri::command]
+pub fn preview_cron_expression(expression: String, count: Option<usize>) -> Result<Vec<String>, String> {
+    let cron = CronExpression::parse(&expression)?;
+    let mut times = Vec::new();
+    let mut after = Local::now();
+
+    for _ in 0..count.unwrap_or(5).clamp(1, MAX_CRON_PREVIEW) {
+        match cron.next_after(after) {
+            Some(next) => {
+                times.push(next.to_rfc3339());
+                after = next;
+            }
+            None => break,
+        }
+    }
+
+    Ok(times)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{FixedOffset, NaiveDateTime};
+
+    fn naive(time: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn next(expression: &str, after: &str) -> Option<String> {
+        CronExpression::parse(expression).unwrap()
+            .next_after(naive(after).and_utc())
+            .map(|time| time.format("%Y-%m-%d %H:%M").to_string())
+    }
+
+    // Центральная Европа весной 2024 года: 31 марта в 02:00 часы переводятся на 03:00
+    #[derive(Debug, Clone, Copy)]
+    struct SpringForward;
+
+    impl SpringForward {
+        fn winter() -> FixedOffset {
+            FixedOffset::east_opt(3600).unwrap()
+        }
+
+        fn summer() -> FixedOffset {
+            FixedOffset::east_opt(2 * 3600).unwrap()
+        }
+    }
+
+    impl TimeZone for SpringForward {
+        type Offset = FixedOffset;
+
+        fn from_offset(_: &FixedOffset) -> Self {
+            SpringForward
+        }
+
+        fn offset_from_local_date(&self, local: &NaiveDate) -> LocalResult<FixedOffset> {
+            self.offset_from_local_datetime(&local.and_hms_opt(0, 0, 0).unwrap())
+        }
+
+        fn offset_from_local_datetime(&self, local: &NaiveDateTime) -> LocalResult<FixedOffset> {
+            if *local < naive("2024-03-31 02:00") {
+                LocalResult::Single(Self::winter())
+            } else if *local < naive("2024-03-31 03:00") {
+                LocalResult::None
+            } else {
+                LocalResult::Single(Self::summer())
+            }
+        }
+
+        fn offset_from_utc_date(&self, utc: &NaiveDate) -> FixedOffset {
+            self.offset_from_utc_datetime(&utc.and_hms_opt(0, 0, 0).unwrap())
+        }
+
+        fn offset_from_utc_datetime(&self, utc: &NaiveDateTime) -> FixedOffset {
+            if *utc < naive("2024-03-31 01:00") { Self::winter() } else { Self::summer() }
+        }
+    }
+
+    #[test]
+    fn steps() {
+        assert_eq!(next("*/15 * * * *", "2024-09-02 10:07").as_deref(), Some("2024-09-02 10:15"));
+        assert_eq!(next("*/15 * * * *", "2024-09-02 10:45").as_deref(), Some("2024-09-02 11:00"));
+        assert_eq!(next("5/10 * * * *", "2024-09-02 10:05").as_deref(), Some("2024-09-02 10:15"));
+        assert_eq!(next("5/10 * * * *", "2024-09-02 10:55").as_deref(), Some("2024-09-02 11:05"));
+        assert_eq!(next("0 9-17/4 * * *", "2024-09-02 13:00").as_deref(), Some("2024-09-02 17:00"));
+    }
+
+    #[test]
+    fn month_and_weekday_names() {
+        assert_eq!(
+            CronExpression::parse("0 9 * JAN-mar Mon-FRI").unwrap(),
+            CronExpression::parse("0 9 * 1-3 1-5").unwrap()
+        );
+        // 29.03.2024 - пятница, следующий будний день января-марта - 01.01.2025
+        assert_eq!(next("0 9 * jan-mar mon-fri", "2024-03-29 10:00").as_deref(), Some("2025-01-01 09:00"));
+    }
+
+    #[test]
+    fn seven_is_sunday() {
+        let sunday = CronExpression::parse("0 0 * * 0").unwrap();
+        assert_eq!(CronExpression::parse("0 0 * * 7").unwrap(), sunday);
+        assert_eq!(CronExpression::parse("0 0 * * sun").unwrap(), sunday);
+        assert_eq!(CronExpression::parse("0 0 * * 5-7").unwrap(), CronExpression::parse("0 0 * * 0,5,6").unwrap());
+        assert_eq!(next("0 0 * * 7", "2024-09-02 00:00").as_deref(), Some("2024-09-08 00:00"));
+    }
+
+    #[test]
+    fn day_of_month_or_day_of_week() {
+        // Заданы оба поля: подходит 10-е число или пятница
+        assert_eq!(next("0 12 10 * fri", "2024-09-01 00:00").as_deref(), Some("2024-09-06 12:00"));
+        assert_eq!(next("0 12 10 * fri", "2024-09-06 12:00").as_deref(), Some("2024-09-10 12:00"));
+        assert_eq!(next("0 12 10 * fri", "2024-09-10 12:00").as_deref(), Some("2024-09-13 12:00"));
+        // Одно из полей "*": учитывается только другое
+        assert_eq!(next("0 12 10 * *", "2024-09-01 00:00").as_deref(), Some("2024-09-10 12:00"));
+        assert_eq!(next("0 12 * * fri", "2024-09-07 00:00").as_deref(), Some("2024-09-13 12:00"));
+        // Как в vixie cron, поле, начинающееся с "*" (в том числе "*/10"), считается незаданным
+        assert_eq!(next("0 12 */10 * fri", "2024-09-07 00:00").as_deref(), Some("2024-09-13 12:00"));
+    }
+
+    #[test]
+    fn impossible_date_never_fires() {
+        assert_eq!(next("0 0 31 2 *", "2024-01-01 00:00"), None);
+        assert_eq!(next("0 0 30 2 *", "2024-01-01 00:00"), None);
+        assert_eq!(next("0 0 29 2 *", "2025-01-01 00:00").as_deref(), Some("2028-02-29 00:00"));
+    }
+
+    #[test]
+    fn invalid_expressions() {
+        for expression in ["* * * *", "60 * * * *", "*/0 * * * *", "0 0 5-1 * *", "0 0 0 * *", "0 0 * 13 *", "0 0 * * 8", "0 0 * foo *", "@reboot"] {
+            assert!(CronExpression::parse(expression).is_err(), "{}", expression);
+        }
+        assert_eq!(CronExpression::parse("@daily").unwrap(), CronExpression::parse("0 0 * * *").unwrap());
+    }
+
+    #[test]
+    fn spring_forward_gap_is_skipped() {
+        let at = |time: &str| SpringForward.from_local_datetime(&naive(time)).unwrap();
+
+        // 02:30 31 марта не существует: следующее срабатывание - 1 апреля
+        let daily = CronExpression::parse("30 2 * * *").unwrap();
+        let fired = daily.next_after(at("2024-03-30 03:00")).unwrap();
+        assert_eq!(fired.to_rfc3339(), "2024-04-01T02:30:00+02:00");
+
+        let half_hourly = CronExpression::parse("*/30 * * * *").unwrap();
+        let fired = half_hourly.next_after(at("2024-03-31 01:45")).unwrap();
+        assert_eq!(fired.to_rfc3339(), "2024-03-31T03:00:00+02:00");
+        assert_eq!(fired - at("2024-03-31 01:45"), ChronoDuration::minutes(15));
+    }
+}
@@ -0,0 +1,989 @@
+// Модуль демона постоянных терминальных сессий
+// Демон - это тот же исполняемый файл X-Avto, запущенный с флагом --session-daemon.
+// Он владеет PTY и общается с приложением по локальному сокету (Unix socket / named pipe)
+// JSON-сообщениями, по одному в строке. Сессии продолжают работать после закрытия окна,
+// а приложение при запуске подключается к ним заново, как tmux
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use portable_pty::{Child, ChildKiller, ExitStatus};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio::sync::broadcast;
+
+use crate::utils::shell_integration::{build_shell_command, default_shell, ShellKind};
+use crate::utils::terminal_backend::{BackendIo, BackendKind, LocalPtyBackend, TerminalBackend};
+
+// Флаг командной строки для запуска в режиме демона
+pub const DAEMON_FLAG: &str = "--session-daemon";
+
+// Объем сохраняемого вывода сессии для повторного подключения
+const SCROLLBACK_LIMIT: usize = 256 * 1024;
+
+// Через сколько демон без сессий завершает работу
+const IDLE_SHUTDOWN: Duration = Duration::from_secs(300);
+
+// Полный сброс терминала (RIS) перед повторной отправкой буфера отставшему клиенту
+const TERMINAL_RESET: &[u8] = b"\x1bc";
+
+// Каталог сокета доступен только владельцу: другой пользователь не может
+// ни подменить сокет, ни подключиться к чужим сессиям
+#[cfg(unix)]
+fn socket_dir() -> std::io::Result<std::path::PathBuf> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+
+    let uid = current_uid();
+    let dir = match std::env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+        Some(runtime_dir) => std::path::PathBuf::from(runtime_dir).join("x-avto"),
+        None => std::env::temp_dir().join(format!("x-avto-{}", uid)),
+    };
+
+    match std::fs::DirBuilder::new().mode(0o700).create(&dir) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+        Err(e) => return Err(e),
+    }
+
+    // symlink_metadata, чтобы не пойти по символической ссылке, подложенной в /tmp
+    let metadata = std::fs::symlink_metadata(&dir)?;
+    if !metadata.is_dir() || metadata.uid() != uid {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            format!("Каталог {:?} принадлежит другому пользователю", dir),
+        ));
+    }
+    if metadata.mode() & 0o077 != 0 {
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700))?;
+    }
+
+    Ok(dir)
+}
+
+// Путь к сокету демона
+#[cfg(unix)]
+fn socket_path() -> std::io::Result<std::path::PathBuf> {
+    Ok(socket_dir()?.join("sessions.sock"))
+}
+
+#[cfg(unix)]
+fn current_uid() -> u32 {
+    unsafe { libc::geteuid() }
+}
+
+// UID процесса на другом конце сокета
+#[cfg(unix)]
+fn peer_uid(stream: &impl std::os::unix::io::AsRawFd) -> std::io::Result<u32> {
+    let fd = stream.as_raw_fd();
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        let mut cred: libc::ucred = unsafe { std::mem::zeroed() };
+        let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+        let result = unsafe {
+            libc::getsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                libc::SO_PEERCRED,
+                &mut cred as *mut libc::ucred as *mut libc::c_void,
+                &mut len,
+            )
+        };
+        if result != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(cred.uid)
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    {
+        let mut uid: libc::uid_t = 0;
+        let mut gid: libc::gid_t = 0;
+        if unsafe { libc::getpeereid(fd, &mut uid, &mut gid) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(uid)
+    }
+}
+
+// Проверка, что на другом конце сокета процесс того же пользователя
+#[cfg(unix)]
+fn verify_peer(stream: &impl std::os::unix::io::AsRawFd) -> std::io::Result<()> {
+    let uid = peer_uid(stream)?;
+    if uid != current_uid() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            format!("Процесс на другом конце сокета принадлежит другому пользователю (uid {})", uid),
+        ));
+    }
+    Ok(())
+}
+
+// Именованный канал Windows: имя содержит SID пользователя, а DACL пускает только его
+#[cfg(windows)]
+mod pipe_security {
+    use std::io;
+    use std::os::windows::io::AsRawHandle;
+    use std::ptr;
+
+    use winapi::shared::minwindef::{DWORD, FALSE};
+    use winapi::shared::sddl::{ConvertSidToStringSidW, ConvertStringSecurityDescriptorToSecurityDescriptorW, SDDL_REVISION_1};
+    use winapi::um::handleapi::CloseHandle;
+    use winapi::um::minwinbase::SECURITY_ATTRIBUTES;
+    use winapi::um::processthreadsapi::{GetCurrentProcess, OpenProcess, OpenProcessToken};
+    use winapi::um::securitybaseapi::GetTokenInformation;
+    use winapi::um::winbase::{GetNamedPipeServerProcessId, LocalFree};
+    use winapi::um::winnt::{TokenUser, HANDLE, PROCESS_QUERY_LIMITED_INFORMATION, PSECURITY_DESCRIPTOR, TOKEN_QUERY, TOKEN_USER};
+
+    fn to_wide(value: &str) -> Vec<u16> {
+        value.encode_utf16().chain(std::iter::once(0)).collect()
+    }
+
+    // SID владельца токена в строковом виде (S-1-5-21-...)
+    fn token_user_sid(token: HANDLE) -> io::Result<String> {
+        unsafe {
+            let mut len: DWORD = 0;
+            GetTokenInformation(token, TokenUser, ptr::null_mut(), 0, &mut len);
+            if len == 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            // Буфер из u64, чтобы TOKEN_USER был выровнен
+            let mut buffer = vec![0u64; (len as usize).div_ceil(8)];
+            if GetTokenInformation(token, TokenUser, buffer.as_mut_ptr() as *mut _, len, &mut len) == FALSE {
+                return Err(io::Error::last_os_error());
+            }
+            let user = &*(buffer.as_ptr() as *const TOKEN_USER);
+
+            let mut sid_string = ptr::null_mut();
+            if ConvertSidToStringSidW(user.User.Sid, &mut sid_string) == FALSE {
+                return Err(io::Error::last_os_error());
+            }
+            let mut end = 0;
+            while *sid_string.add(end) != 0 {
+                end += 1;
+            }
+            let sid = String::from_utf16_lossy(std::slice::from_raw_parts(sid_string, end));
+            LocalFree(sid_string as *mut _);
+            Ok(sid)
+        }
+    }
+
+    fn process_user_sid(process: HANDLE) -> io::Result<String> {
+        unsafe {
+            let mut token: HANDLE = ptr::null_mut();
+            if OpenProcessToken(process, TOKEN_QUERY, &mut token) == FALSE {
+                return Err(io::Error::last_os_error());
+            }
+            let sid = token_user_sid(token);
+            CloseHandle(token);
+            sid
+        }
+    }
+
+    fn current_user_sid() -> io::Result<String> {
+        process_user_sid(unsafe { GetCurrentProcess() })
+    }
+
+    pub fn pipe_name() -> io::Result<String> {
+        Ok(format!(r"\\.\pipe\x-avto-sessions-{}", current_user_sid()?))
+    }
+
+    // Дескриптор безопасности с защищенным DACL: полный доступ только текущему пользователю
+    pub struct PipeSecurity {
+        descriptor: PSECURITY_DESCRIPTOR,
+    }
+
+    impl PipeSecurity {
+        pub fn new() -> io::Result<Self> {
+            let sddl = to_wide(&format!("D:P(A;;GA;;;{})", current_user_sid()?));
+            let mut descriptor: PSECURITY_DESCRIPTOR = ptr::null_mut();
+            let created = unsafe {
+                ConvertStringSecurityDescriptorToSecurityDescriptorW(
+                    sddl.as_ptr(),
+                    SDDL_REVISION_1 as DWORD,
+                    &mut descriptor,
+                    ptr::null_mut(),
+                )
+            };
+            if created == FALSE {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(PipeSecurity { descriptor })
+        }
+
+        pub fn attributes(&self) -> SECURITY_ATTRIBUTES {
+            SECURITY_ATTRIBUTES {
+                nLength: std::mem::size_of::<SECURITY_ATTRIBUTES>() as DWORD,
+                lpSecurityDescriptor: self.descriptor,
+                bInheritHandle: FALSE,
+            }
+        }
+    }
+
+    impl Drop for PipeSecurity {
+        fn drop(&mut self) {
+            unsafe {
+                LocalFree(self.descriptor);
+            }
+        }
+    }
+
+    // Отказ от подключения к каналу, созданному процессом другого пользователя
+    pub fn verify_server(pipe: &std::fs::File) -> io::Result<()> {
+        let mut server_pid: DWORD = 0;
+        let sid = unsafe {
+            if GetNamedPipeServerProcessId(pipe.as_raw_handle() as HANDLE, &mut server_pid) == FALSE {
+                return Err(io::Error::last_os_error());
+            }
+            let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, FALSE, server_pid);
+            if process.is_null() {
+                return Err(io::Error::last_os_error());
+            }
+            let sid = process_user_sid(process);
+            CloseHandle(process);
+            sid?
+        };
+
+        if sid != current_user_sid()? {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("Канал демона создан другим пользователем ({})", sid),
+            ));
+        }
+        Ok(())
+    }
+}
+
+// Запрос к демону
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum DaemonRequest {
+    Create {
+        shell: String,
+        tab_id: Option<i64>,
+        #[serde(default)]
+        cwd: Option<String>,
+    },
+    List,
+    Attach {
+        session_id: u32,
+    },
+    Input {
+        session_id: u32,
+        data: Vec<u8>,
+    },
+    Resize {
+        session_id: u32,
+        rows: u16,
+        cols: u16,
+    },
+    Kill {
+        session_id: u32,
+    },
+}
+
+// Ответ или событие демона
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum DaemonResponse {
+    Created { session: SessionInfo },
+    Sessions { sessions: Vec<SessionInfo> },
+    Output { data: Vec<u8> },
+    Exited,
+    Ok,
+    Error { message: String },
+}
+
+// Информация о постоянной сессии
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionInfo {
+    pub session_id: u32,
+    pub shell: String,
+    pub pid: Option<u32>,
+    pub tab_id: Option<i64>,
+    pub created_at: String,
+}
+
+// ---------------------------------------------------------------------------
+// Сторона демона
+// ---------------------------------------------------------------------------
+
+// Событие сессии для подключенных клиентов
+#[derive(Debug, Clone)]
+enum SessionEvent {
+    Output(Vec<u8>),
+    Exited,
+}
+
+struct DaemonSession {
+    info: SessionInfo,
+    backend: LocalPtyBackend,
+    scrollback: VecDeque<u8>,
+    events: broadcast::Sender<SessionEvent>,
+}
+
+struct DaemonState {
+    sessions: HashMap<u32, DaemonSession>,
+    next_id: u32,
+    last_activity: std::time::Instant,
+}
+
+type SharedDaemonState = Arc<Mutex<DaemonState>>;
+
+// Точка входа режима демона (вызывается из main до запуска Tauri)
+pub fn run_daemon() {
+    println!("[SessionDaemon] Запуск демона терминальных сессий");
+
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("[SessionDaemon] Не удалось создать runtime: {}", e);
+            return;
+        }
+    };
+
+    let state: SharedDaemonState = Arc::new(Mutex::new(DaemonState {
+        sessions: HashMap::new(),
+        next_id: 1,
+        last_activity: std::time::Instant::now(),
+    }));
+
+    // Завершаем демон, если сессий нет слишком долго
+    let idle_state = state.clone();
+    std::thread::spawn(move || loop {
+        std::thread::sleep(Duration::from_secs(30));
+        if let Ok(state) = idle_state.lock() {
+            if state.sessions.is_empty() && state.last_activity.elapsed() >= IDLE_SHUTDOWN {
+                println!("[SessionDaemon] Нет активных сессий, завершение работы");
+                #[cfg(unix)]
+                if let Ok(path) = socket_path() {
+                    let _ = std::fs::remove_file(path);
+                }
+                std::process::exit(0);
+            }
+        }
+    });
+
+    if let Err(e) = runtime.block_on(serve(state)) {
+        eprintln!("[SessionDaemon] Ошибка сервера: {}", e);
+    }
+}
+
+#[cfg(unix)]
+async fn serve(state: SharedDaemonState) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = socket_path()?;
+
+    // Удаляем сокет, оставшийся от аварийно завершенного демона
+    if path.exists() {
+        if std::os::unix::net::UnixStream::connect(&path).is_ok() {
+            println!("[SessionDaemon] Демон уже запущен");
+            return Ok(());
+        }
+        let _ = std::fs::remove_file(&path);
+    }
+
+    let listener = tokio::net::UnixListener::bind(&path)?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+    println!("[SessionDaemon] Ожидание подключений на {:?}", path);
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        if let Err(e) = verify_peer(&stream) {
+            eprintln!("[SessionDaemon] Подключение отклонено: {}", e);
+            continue;
+        }
+        tokio::spawn(handle_connection(stream, state.clone()));
+    }
+}
+
+#[cfg(windows)]
+async fn serve(state: SharedDaemonState) -> std::io::Result<()> {
+    use tokio::net::windows::named_pipe::{NamedPipeServer, ServerOptions};
+
+    let pipe_name = pipe_security::pipe_name()?;
+    let security = pipe_security::PipeSecurity::new()?;
+
+    let create = |first: bool| -> std::io::Result<NamedPipeServer> {
+        let mut attributes = security.attributes();
+        unsafe {
+            ServerOptions::new()
+                .first_pipe_instance(first)
+                .reject_remote_clients(true)
+                .create_with_security_attributes_raw(&pipe_name, &mut attributes as *mut _ as *mut std::ffi::c_void)
+        }
+    };
+
+    let mut server = create(true)?;
+    println!("[SessionDaemon] Ожидание подключений на {}", pipe_name);
+
+    loop {
+        server.connect().await?;
+        let connected = server;
+        server = create(false)?;
+        tokio::spawn(handle_connection(connected, state.clone()));
+    }
+}
+
+async fn send_response<W: AsyncWrite + Unpin>(writer: &mut W, response: &DaemonResponse) -> std::io::Result<()> {
+    let mut line = serde_json::to_vec(response)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    line.push(b'\n');
+    writer.write_all(&line).await?;
+    writer.flush().await
+}
+
+async fn handle_connection<S>(stream: S, state: SharedDaemonState)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (read_half, mut writer) = tokio::io::split(stream);
+    let mut lines = tokio::io::BufReader::new(read_half).lines();
+
+    while let Ok(Some(line)) = lines.next_line().await {
+        let request: DaemonRequest = match serde_json::from_str(&line) {
+            Ok(request) => request,
+            Err(e) => {
+                let _ = send_response(&mut writer, &DaemonResponse::Error { message: format!("Некорректный запрос: {}", e) }).await;
+                continue;
+            }
+        };
+
+        match request {
+            // После attach соединение используется только для потока вывода
+            DaemonRequest::Attach { session_id } => {
+                stream_session(session_id, &mut writer, &state).await;
+                return;
+            }
+            // Ввод и изменение размера не требуют ответа
+            DaemonRequest::Input { session_id, data } => {
+                if let Ok(mut state) = state.lock() {
+                    if let Some(session) = state.sessions.get_mut(&session_id) {
+                        let _ = session.backend.write_input(&data);
+                    }
+                }
+            }
+            DaemonRequest::Resize { session_id, rows, cols } => {
+                if let Ok(mut state) = state.lock() {
+                    if let Some(session) = state.sessions.get_mut(&session_id) {
+                        let _ = session.backend.resize(rows, cols);
+                    }
+                }
+            }
+            DaemonRequest::Create { shell, tab_id, cwd } => {
+                let response = match create_daemon_session(&state, shell, tab_id, cwd) {
+                    Ok(session) => DaemonResponse::Created { session },
+                    Err(message) => DaemonResponse::Error { message },
+                };
+                if send_response(&mut writer, &response).await.is_err() {
+                    return;
+                }
+            }
+            DaemonRequest::List => {
+                let sessions = state.lock()
+                    .map(|state| state.sessions.values().map(|s| s.info.clone()).collect())
+                    .unwrap_or_default();
+                if send_response(&mut writer, &DaemonResponse::Sessions { sessions }).await.is_err() {
+                    return;
+                }
+            }
+            DaemonRequest::Kill { session_id } => {
+                let response = match state.lock() {
+                    Ok(mut state) => match state.sessions.get_mut(&session_id) {
+                        Some(session) => {
+                            let _ = session.backend.shutdown();
+                            DaemonResponse::Ok
+                        }
+                        None => DaemonResponse::Error { message: format!("Сессия {} не найдена", session_id) },
+                    },
+                    Err(e) => DaemonResponse::Error { message: e.to_string() },
+                };
+                if send_response(&mut writer, &response).await.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+// Подписка на события сессии и снимок буфера под одной блокировкой,
+// чтобы не потерять и не продублировать вывод между ними
+fn subscribe_session(session_id: u32, state: &SharedDaemonState) -> Option<(broadcast::Receiver<SessionEvent>, Vec<u8>)> {
+    state.lock().ok().and_then(|state| {
+        state.sessions.get(&session_id).map(|session| {
+            (session.events.subscribe(), session.scrollback.iter().cloned().collect::<Vec<u8>>())
+        })
+    })
+}
+
+// Отправка накопленного и последующего вывода сессии клиенту
+async fn stream_session<W: AsyncWrite + Unpin>(session_id: u32, writer: &mut W, state: &SharedDaemonState) {
+    let (mut events, scrollback) = match subscribe_session(session_id, state) {
+        Some(subscription) => subscription,
+        None => {
+            let _ = send_response(writer, &DaemonResponse::Error { message: format!("Сессия {} не найдена", session_id) }).await;
+            return;
+        }
+    };
+
+    println!("[SessionDaemon] Клиент подключен к сессии {}", session_id);
+
+    if !scrollback.is_empty() && send_response(writer, &DaemonResponse::Output { data: scrollback }).await.is_err() {
+        return;
+    }
+
+    loop {
+        let response = match events.recv().await {
+            Ok(SessionEvent::Output(data)) => DaemonResponse::Output { data },
+            Ok(SessionEvent::Exited) | Err(broadcast::error::RecvError::Closed) => DaemonResponse::Exited,
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                // Клиент не успел забрать часть вывода: сбрасываем его терминал
+                // и заново отправляем буфер сессии, как при подключении
+                eprintln!("[SessionDaemon] Клиент сессии {} отстал на {} сообщений, повторная отправка буфера", session_id, skipped);
+                match subscribe_session(session_id, state) {
+                    Some((resubscribed, scrollback)) => {
+                        events = resubscribed;
+                        let mut data = TERMINAL_RESET.to_vec();
+                        data.extend_from_slice(&scrollback);
+                        DaemonResponse::Output { data }
+                    }
+                    None => DaemonResponse::Exited,
+                }
+            }
+        };
+        let exited = matches!(response, DaemonResponse::Exited);
+        if send_response(writer, &response).await.is_err() || exited {
+            break;
+        }
+    }
+
+    println!("[SessionDaemon] Клиент отключен от сессии {}", session_id);
+}
+
+fn create_daemon_session(
+    state: &SharedDaemonState,
+    shell: String,
+    tab_id: Option<i64>,
+    cwd: Option<String>,
+) -> Result<SessionInfo, String> {
+    let kind = ShellKind::detect(&shell);
+    let cmd = build_shell_command(&shell, kind, cwd.as_deref())?;
+    let (backend, io) = LocalPtyBackend::spawn(cmd)?;
+    let BackendIo { mut reader, mut child } = io;
+
+    let (events, _) = broadcast::channel(256);
+
+    let info = {
+        let mut state = state.lock().map_err(|e| e.to_string())?;
+        let session_id = state.next_id;
+        state.next_id += 1;
+        state.last_activity = std::time::Instant::now();
+
+        let info = SessionInfo {
+            session_id,
+            shell: shell.clone(),
+            pid: backend.process_id(),
+            tab_id,
+            created_at: chrono::Local::now().to_rfc3339(),
+        };
+        state.sessions.insert(session_id, DaemonSession {
+            info: info.clone(),
+            backend,
+            scrollback: VecDeque::new(),
+            events: events.clone(),
+        });
+        info
+    };
+
+    let session_id = info.session_id;
+    println!("[SessionDaemon] Создана сессия {} ({})", session_id, shell);
+
+    // Чтение вывода PTY: буфер для повторного подключения и рассылка клиентам
+    let reader_state = state.clone();
+    std::thread::spawn(move || {
+        let mut buffer = [0u8; 4096];
+        loop {
+            match reader.read(&mut buffer) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    let data = buffer[..n].to_vec();
+                    if let Ok(mut state) = reader_state.lock() {
+                        if let Some(session) = state.sessions.get_mut(&session_id) {
+                            session.scrollback.extend(data.iter().cloned());
+                            let excess = session.scrollback.len().saturating_sub(SCROLLBACK_LIMIT);
+                            session.scrollback.drain(..excess);
+                        }
+                    }
+                    let _ = events.send(SessionEvent::Output(data));
+                }
+            }
+        }
+
+        let _ = child.wait();
+        if let Ok(mut state) = reader_state.lock() {
+            state.sessions.remove(&session_id);
+            state.last_activity = std::time::Instant::now();
+        }
+        let _ = events.send(SessionEvent::Exited);
+        println!("[SessionDaemon] Сессия {} завершена", session_id);
+    });
+
+    Ok(info)
+}
+
+// ---------------------------------------------------------------------------
+// Сторона приложения
+// ---------------------------------------------------------------------------
+
+#[cfg(unix)]
+type ClientStream = std::os::unix::net::UnixStream;
+
+#[cfg(windows)]
+type ClientStream = std::fs::File;
+
+#[cfg(unix)]
+fn connect() -> std::io::Result<ClientStream> {
+    use std::os::unix::fs::MetadataExt;
+
+    let path = socket_path()?;
+    if std::fs::symlink_metadata(&path)?.uid() != current_uid() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            format!("Сокет {:?} принадлежит другому пользователю", path),
+        ));
+    }
+
+    let stream = std::os::unix::net::UnixStream::connect(&path)?;
+    verify_peer(&stream)?;
+    Ok(stream)
+}
+
+#[cfg(windows)]
+fn connect() -> std::io::Result<ClientStream> {
+    let pipe = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(pipe_security::pipe_name()?)?;
+    pipe_security::verify_server(&pipe)?;
+    Ok(pipe)
+}
+
+fn write_request(stream: &mut ClientStream, request: &DaemonRequest) -> Result<(), String> {
+    let mut line = serde_json::to_vec(request).map_err(|e| e.to_string())?;
+    line.push(b'\n');
+    stream.write_all(&line)
+        .and_then(|_| stream.flush())
+        .map_err(|e| format!("Ошибка отправки запроса демону: {}", e))
+}
+
+// Запрос с ожиданием одного ответа
+fn request(request: &DaemonRequest) -> Result<DaemonResponse, String> {
+    let mut stream = connect().map_err(|e| format!("Демон сессий недоступен: {}", e))?;
+    write_request(&mut stream, request)?;
+
+    let mut line = String::new();
+    BufReader::new(&mut stream)
+        .read_line(&mut line)
+        .map_err(|e| format!("Ошибка чтения ответа демона: {}", e))?;
+
+    match serde_json::from_str(&line).map_err(|e| format!("Некорректный ответ демона: {}", e))? {
+        DaemonResponse::Error { message } => Err(message),
+        response => Ok(response),
+    }
+}
+
+pub fn is_daemon_running() -> bool {
+    connect().is_ok()
+}
+
+// Запуск демона в фоне, если он еще не запущен
+pub fn ensure_daemon() -> Result<(), String> {
+    if is_daemon_running() {
+        return Ok(());
+    }
+
+    let exe = std::env::current_exe()
+        .map_err(|e| format!("Не удалось определить путь к приложению: {}", e))?;
+
+    let mut cmd = std::process::Command::new(exe);
+    cmd.arg(DAEMON_FLAG)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null());
+
+    #[cfg(unix)]
+    {
+        // Отдельная группа процессов, чтобы демон не получал сигналы окна приложения
+        use std::os::unix::process::CommandExt;
+        cmd.process_group(0);
+    }
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(0x00000008 | 0x08000000); // DETACHED_PROCESS | CREATE_NO_WINDOW
+    }
+
+    cmd.spawn().map_err(|e| format!("Не удалось запустить демон сессий: {}", e))?;
+    println!("[SessionDaemon] Демон сессий запущен в фоне");
+
+    // Ждем, пока демон начнет принимать подключения
+    for _ in 0..50 {
+        if is_daemon_running() {
+            return Ok(());
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+
+    Err("Демон сессий не ответил после запуска".to_string())
+}
+
+pub fn create_session(shell: Option<String>, tab_id: Option<i64>, cwd: Option<String>) -> Result<SessionInfo, String> {
+    ensure_daemon()?;
+    match request(&DaemonRequest::Create {
+        shell: shell.unwrap_or_else(default_shell),
+        tab_id,
+        cwd,
+    })? {
+        DaemonResponse::Created { session } => Ok(session),
+        other => Err(format!("Неожиданный ответ демона: {:?}", other)),
+    }
+}
+
+// Список сессий (пустой, если демон не запущен)
+pub fn list_sessions() -> Result<Vec<SessionInfo>, String> {
+    if !is_daemon_running() {
+        return Ok(Vec::new());
+    }
+    match request(&DaemonRequest::List)? {
+        DaemonResponse::Sessions { sessions } => Ok(sessions),
+        other => Err(format!("Неожиданный ответ демона: {:?}", other)),
+    }
+}
+
+pub fn kill_session(session_id: u32) -> Result<(), String> {
+    request(&DaemonRequest::Kill { session_id }).map(|_| ())
+}
+
+// Подключение к сессии демона как к бэкенду терминала
+pub fn attach_session(info: &SessionInfo) -> Result<(DaemonBackend, BackendIo), String> {
+    let mut output = connect().map_err(|e| format!("Демон сессий недоступен: {}", e))?;
+    write_request(&mut output, &DaemonRequest::Attach { session_id: info.session_id })?;
+
+    let input = connect().map_err(|e| format!("Демон сессий недоступен: {}", e))?;
+    let exit = Arc::new(SessionExit::default());
+
+    let backend = DaemonBackend {
+        session_id: info.session_id,
+        pid: info.pid,
+        input,
+    };
+    let io = BackendIo {
+        reader: Box::new(DaemonOutputReader {
+            lines: BufReader::new(output),
+            pending: Vec::new(),
+            offset: 0,
+            exit: exit.clone(),
+        }),
+        child: Box::new(DaemonChild { session_id: info.session_id, pid: info.pid, exit }),
+    };
+
+    Ok((backend, io))
+}
+
+// Бэкенд терминала, работающий через демон сессий
+pub struct DaemonBackend {
+    session_id: u32,
+    pid: Option<u32>,
+    input: ClientStream,
+}
+
+impl TerminalBackend for DaemonBackend {
+    fn kind(&self) -> BackendKind {
+        BackendKind::Persistent
+    }
+
+    fn write_input(&mut self, data: &[u8]) -> Result<(), String> {
+        write_request(&mut self.input, &DaemonRequest::Input {
+            session_id: self.session_id,
+            data: data.to_vec(),
+        })
+    }
+
+    fn resize(&mut self, rows: u16, cols: u16) -> Result<(), String> {
+        write_request(&mut self.input, &DaemonRequest::Resize {
+            session_id: self.session_id,
+            rows,
+            cols,
+        })
+    }
+
+    fn process_id(&self) -> Option<u32> {
+        self.pid
+    }
+
+    fn session_id(&self) -> Option<u32> {
+        Some(self.session_id)
+    }
+
+    fn shutdown(&mut self) -> Result<(), String> {
+        kill_session(self.session_id)
+    }
+}
+
+// Чтение потока вывода сессии из JSON-сообщений демона
+struct DaemonOutputReader {
+    lines: BufReader<ClientStream>,
+    pending: Vec<u8>,
+    offset: usize,
+    exit: Arc<SessionExit>,
+}
+
+impl Read for DaemonOutputReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.offset >= self.pending.len() {
+            let mut line = String::new();
+            if self.lines.read_line(&mut line)? == 0 {
+                return Ok(0);
+            }
+
+            match serde_json::from_str(&line) {
+                Ok(DaemonResponse::Output { data }) => {
+                    self.pending = data;
+                    self.offset = 0;
+                }
+                Ok(DaemonResponse::Exited) => {
+                    self.exit.set(StreamState::Exited);
+                    return Ok(0);
+                }
+                Ok(DaemonResponse::Error { message }) => {
+                    return Err(std::io::Error::other(message));
+                }
+                _ => continue,
+            }
+        }
+
+        let n = buf.len().min(self.pending.len() - self.offset);
+        buf[..n].copy_from_slice(&self.pending[self.offset..self.offset + n]);
+        self.offset += n;
+        Ok(n)
+    }
+}
+
+// Поток вывода закрыт без сообщения о завершении сессии (или читатель уничтожен)
+impl Drop for DaemonOutputReader {
+    fn drop(&mut self) {
+        self.exit.set(StreamState::Closed);
+    }
+}
+
+// Состояние потока вывода сессии, по которому DaemonChild узнает о завершении
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StreamState {
+    Open,
+    // Демон сообщил о завершении сессии
+    Exited,
+    // Поток оборвался без Exited: судьбу сессии нужно уточнить у демона
+    Closed,
+}
+
+#[derive(Debug)]
+struct SessionExit {
+    state: Mutex<StreamState>,
+    changed: Condvar,
+}
+
+impl Default for SessionExit {
+    fn default() -> Self {
+        Self { state: Mutex::new(StreamState::Open), changed: Condvar::new() }
+    }
+}
+
+impl SessionExit {
+    // Exited окончательно, Closed не перезаписывает его
+    fn set(&self, state: StreamState) {
+        let mut current = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if *current != StreamState::Exited {
+            *current = state;
+        }
+        self.changed.notify_all();
+    }
+
+    fn get(&self) -> StreamState {
+        *self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn wait_not_open(&self) -> StreamState {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        while *state == StreamState::Open {
+            state = self.changed.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+        *state
+    }
+}
+
+// Через сколько повторять запрос к демону, если поток оборвался, а демон не ответил
+const DAEMON_RETRY: Duration = Duration::from_secs(2);
+
+// Процесс сессии на стороне демона: о завершении сообщает поток вывода (Exited)
+#[derive(Debug)]
+struct DaemonChild {
+    session_id: u32,
+    pid: Option<u32>,
+    exit: Arc<SessionExit>,
+}
+
+impl DaemonChild {
+    // Поток оборвался без Exited: сессия завершена, только если демон ответил и ее нет.
+    // Ошибка связи с демоном не означает завершения
+    fn session_gone(&self) -> bool {
+        list_sessions()
+            .map(|sessions| !sessions.iter().any(|s| s.session_id == self.session_id))
+            .unwrap_or(false)
+    }
+
+    fn exited(&self, state: StreamState) -> bool {
+        match state {
+            StreamState::Open => false,
+            StreamState::Exited => true,
+            StreamState::Closed => self.session_gone(),
+        }
+    }
+}
+
+impl Child for DaemonChild {
+    fn try_wait(&mut self) -> std::io::Result<Option<ExitStatus>> {
+        Ok(self.exited(self.exit.get()).then(|| ExitStatus::with_exit_code(0)))
+    }
+
+    fn wait(&mut self) -> std::io::Result<ExitStatus> {
+        while !self.exited(self.exit.wait_not_open()) {
+            std::thread::sleep(DAEMON_RETRY);
+        }
+        Ok(ExitStatus::with_exit_code(0))
+    }
+
+    fn process_id(&self) -> Option<u32> {
+        self.pid
+    }
+
+    #[cfg(windows)]
+    fn as_raw_handle(&self) -> Option<std::os::windows::io::RawHandle> {
+        None
+    }
+}
+
+impl ChildKiller for DaemonChild {
+    fn kill(&mut self) -> std::io::Result<()> {
+        kill_session(self.session_id)
+            .map_err(std::io::Error::other)
+    }
+
+    fn clone_killer(&self) -> Box<dyn ChildKiller + Send + Sync> {
+        Box::new(DaemonChild { session_id: self.session_id, pid: self.pid, exit: self.exit.clone() })
+    }
+}
@@ -0,0 +1,886 @@
+// Модуль интеграции с оболочками (shell integration)
+// Внедряет в bash, zsh и PowerShell хуки, которые выводят последовательности OSC 133 / OSC 633,
+// разбирает их из потока вывода PTY и собирает по ним полноценные записи о выполненных командах
+
+use chrono::{DateTime, Local};
+use portable_pty::CommandBuilder;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+// Максимальный объем сохраняемого вывода одной команды (в байтах)
+const MAX_CAPTURED_OUTPUT: usize = 64 * 1024;
+
+// Максимальная длина незавершенной OSC-последовательности, которую переносим между чтениями
+const MAX_PENDING_SEQUENCE: usize = 8 * 1024;
+
+// Тип оболочки, запущенной в терминале
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShellKind {
+    PowerShell,
+    Bash,
+    Zsh,
+    Other,
+}
+
+impl ShellKind {
+    // Определение типа оболочки по имени исполняемого файла
+    pub fn detect(program: &str) -> Self {
+        let name = PathBuf::from(program)
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        match name.as_str() {
+            "powershell" | "pwsh" => ShellKind::PowerShell,
+            "bash" => ShellKind::Bash,
+            "zsh" => ShellKind::Zsh,
+            _ => ShellKind::Other,
+        }
+    }
+}
+
+// Оболочка по умолчанию для текущей платформы
+pub fn default_shell() -> String {
+    if cfg!(target_os = "windows") {
+        "powershell.exe".to_string()
+    } else {
+        std::env::var("SHELL").unwrap_or_else(|_| "/bin/bash".to_string())
+    }
+}
+
+// Личная директория пользователя для файлов интеграции
+// Общий /tmp не подходит: другой пользователь может заранее создать в нем каталог
+// или подложить символические ссылки, и оболочка выполнит чужой код
+#[cfg(unix)]
+fn integration_dir() -> Result<PathBuf, String> {
+    let uid = unsafe { libc::geteuid() };
+    let base = match std::env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+        Some(runtime_dir) => PathBuf::from(runtime_dir).join("x-avto"),
+        None => std::env::temp_dir().join(format!("x-avto-{}", uid)),
+    };
+    let dir = base.join("shell-integration");
+
+    private_dir(&base, uid)?;
+    private_dir(&dir, uid)?;
+    Ok(dir)
+}
+
+// Временная директория Windows и так принадлежит пользователю
+#[cfg(not(unix))]
+fn integration_dir() -> Result<PathBuf, String> {
+    let dir = std::env::temp_dir().join("x-avto-shell-integration");
+    fs::create_dir_all(&dir)
+        .map_err(|e| format!("Не удалось создать директорию интеграции: {}", e))?;
+    Ok(dir)
+}
+
+// Создание каталога с правами 0700 и проверка, что он принадлежит пользователю
+#[cfg(unix)]
+fn private_dir(dir: &Path, uid: u32) -> Result<(), String> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+
+    match fs::DirBuilder::new().mode(0o700).create(dir) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+        Err(e) => return Err(format!("Не удалось создать директорию интеграции: {}", e)),
+    }
+
+    // symlink_metadata, чтобы не пойти по подложенной символической ссылке
+    let metadata = fs::symlink_metadata(dir)
+        .map_err(|e| format!("Не удалось проверить директорию интеграции: {}", e))?;
+    if !metadata.is_dir() || metadata.uid() != uid {
+        return Err(format!("Директория интеграции {:?} принадлежит другому пользователю", dir));
+    }
+    if metadata.mode() & 0o077 != 0 {
+        fs::set_permissions(dir, fs::Permissions::from_mode(0o700))
+            .map_err(|e| format!("Не удалось изменить права директории интеграции: {}", e))?;
+    }
+    Ok(())
+}
+
+// Запись файла интеграции: сначала во временный файл рядом, затем атомарная замена
+// Уже запущенные оболочки не увидят наполовину записанный файл
+fn write_integration_file(path: &Path, contents: &[u8]) -> Result<(), String> {
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let mut file = tempfile::NamedTempFile::new_in(dir)
+        .map_err(|e| format!("Не удалось создать скрипт интеграции: {}", e))?;
+    file.write_all(contents)
+        .map_err(|e| format!("Не удалось записать скрипт интеграции: {}", e))?;
+    file.persist(path)
+        .map_err(|e| format!("Не удалось записать скрипт интеграции: {}", e.error))?;
+    Ok(())
+}
+
+const POWERSHELL_INTEGRATION: &str = r#"
+function global:__XAvtoEscape([string]$value) {
+    if ($null -eq $value) { return '' }
+    $builder = New-Object System.Text.StringBuilder
+    foreach ($ch in $value.ToCharArray()) {
+        if ($ch -eq '\') { [void]$builder.Append('\\') }
+        elseif ($ch -eq ';') { [void]$builder.Append('\x3b') }
+        elseif ([int]$ch -lt 0x20) { [void]$builder.Append(('\x{0:x2}' -f [int]$ch)) }
+        else { [void]$builder.Append($ch) }
+    }
+    $builder.ToString()
+}
+
+$global:__XAvtoOriginalPrompt = $function:prompt
+
+function global:prompt {
+    $ok = $?
+    $code = if ($ok) { 0 } elseif ($global:LASTEXITCODE) { $global:LASTEXITCODE } else { 1 }
+    $esc = [char]27
+    $bel = [char]7
+    $result = "$esc]633;D;$code$bel$esc]633;P;Cwd=$(__XAvtoEscape $PWD.ProviderPath)$bel$esc]633;A$bel"
+    $result += & $global:__XAvtoOriginalPrompt
+    $result += "$esc]633;B$bel"
+    $result
+}
+
+if (Get-Module PSReadLine) {
+    Set-PSReadLineKeyHandler -Chord Enter -ScriptBlock {
+        $line = $null
+        $cursor = $null
+        [Microsoft.PowerShell.PSConsoleReadLine]::GetBufferState([ref]$line, [ref]$cursor)
+        $esc = [char]27
+        $bel = [char]7
+        [Console]::Write("$esc]633;E;$(__XAvtoEscape $line)$bel$esc]633;C$bel")
+        [Microsoft.PowerShell.PSConsoleReadLine]::AcceptLine()
+    }
+}
+"#;
+
+// Текст команды bash берется из истории (history 1), а не из $BASH_COMMAND,
+// который содержит только первую простую команду строки (make из make && make install).
+// Чтобы каждая прочитанная строка попадала в историю, HISTCONTROL и HISTIGNORE убираются
+// и выполняются хуком: строка, которую история отбросила бы (пробел в начале, HISTIGNORE),
+// удаляется из истории и не записывается; повтор удаляется из истории, но записывается.
+// При выключенной истории команды не записываются
+const BASH_INTEGRATION: &str = r#"
+if [ -f ~/.bashrc ]; then . ~/.bashrc; fi
+
+__xavto_escape() {
+    local s="${1//\\/\\\\}"
+    s="${s//;/\\x3b}"
+    s="${s//$'\n'/\\x0a}"
+    printf '%s' "$s"
+}
+
+__xavto_at_prompt=0
+__xavto_in_command=0
+__xavto_last_history=
+__xavto_prev_debug=
+__xavto_ignorespace=0
+__xavto_ignoredups=0
+__xavto_erasedups=0
+__xavto_histignore=
+
+__xavto_adjust_history() {
+    local item kept=() IFS=:
+    for item in $HISTCONTROL; do
+        case "$item" in
+            ignorespace) __xavto_ignorespace=1 ;;
+            ignoredups) __xavto_ignoredups=1 ;;
+            ignoreboth) __xavto_ignorespace=1; __xavto_ignoredups=1 ;;
+            erasedups) __xavto_erasedups=1 ;;
+            *) kept+=("$item") ;;
+        esac
+    done
+    HISTCONTROL="${kept[*]}"
+    if [ -n "$HISTIGNORE" ]; then
+        __xavto_histignore=$HISTIGNORE
+        HISTIGNORE=
+    fi
+}
+
+__xavto_history_entry() {
+    [[ -o history ]] || return
+    LC_ALL=C HISTTIMEFORMAT= builtin history 1
+}
+
+__xavto_entry_number() {
+    local number=${1#"${1%%[0-9]*}"}
+    printf '%s' "${number%%[!0-9]*}"
+}
+
+__xavto_entry_text() {
+    local text=${1#"${1%%[0-9]*}"}
+    text=${text#"$(__xavto_entry_number "$1")"}
+    printf '%s' "${text:2}"
+}
+
+__xavto_history_ignored() {
+    local cmd=$1 previous=$2 pattern IFS=:
+    [ "$__xavto_ignorespace" = 1 ] && [ "${cmd:0:1}" = " " ] && return 0
+    for pattern in $__xavto_histignore; do
+        if [ "$pattern" = "&" ]; then
+            [ "$cmd" = "$previous" ] && return 0
+        elif [[ "$cmd" == $pattern ]]; then
+            return 0
+        fi
+    done
+    return 1
+}
+
+__xavto_erase_dups() {
+    local cmd=$1 current=$2 line text number numbers=()
+    while IFS= read -r line; do
+        text=${line#"${line%%[0-9]*}"}
+        number=${text%%[!0-9]*}
+        text=${text#"$number"}
+        [ -n "$number" ] && [ "$number" != "$current" ] && [ "${text:2}" = "$cmd" ] || continue
+        numbers=("$number" "${numbers[@]}")
+    done < <(LC_ALL=C HISTTIMEFORMAT= builtin history)
+    for number in "${numbers[@]}"; do
+        builtin history -d "$number"
+    done
+}
+
+__xavto_preexec() {
+    [ "$__xavto_at_prompt" = 1 ] || return
+    [ -n "$COMP_LINE" ] && return
+    case "$BASH_COMMAND" in __xavto_*) return ;; esac
+    __xavto_at_prompt=0
+    local entry number cmd previous
+    entry=$(__xavto_history_entry)
+    [ -n "$entry" ] && [ "$entry" != "$__xavto_last_history" ] || return
+    number=$(__xavto_entry_number "$entry")
+    cmd=$(__xavto_entry_text "$entry")
+    previous=$(__xavto_entry_text "$__xavto_last_history")
+    if __xavto_history_ignored "$cmd" "$previous"; then
+        builtin history -d "$number"
+        return
+    fi
+    if [ "$__xavto_ignoredups" = 1 ] && [ -n "$__xavto_last_history" ] && [ "$cmd" = "$previous" ]; then
+        builtin history -d "$number"
+    elif [ "$__xavto_erasedups" = 1 ]; then
+        __xavto_erase_dups "$cmd" "$number"
+    fi
+    __xavto_in_command=1
+    printf '\e]633;E;%s\a\e]133;C\a' "$(__xavto_escape "$cmd")"
+}
+
+__xavto_status() {
+    return "$1"
+}
+
+__xavto_debug() {
+    local code=$?
+    __xavto_preexec
+    if [ -n "$__xavto_prev_debug" ]; then
+        __xavto_status "$code"
+        eval "$__xavto_prev_debug"
+    fi
+}
+
+__xavto_save_debug_trap() {
+    eval "set -- $1"
+    case "$3" in *__xavto_*) ;; *) __xavto_prev_debug=$3 ;; esac
+}
+
+__xavto_precmd() {
+    local code=$?
+    __xavto_at_prompt=0
+    if [ "$__xavto_in_command" = 1 ]; then
+        printf '\e]133;D;%s\a' "$code"
+    fi
+    __xavto_in_command=0
+    printf '\e]633;P;Cwd=%s\a' "$(__xavto_escape "$PWD")"
+}
+
+__xavto_prompt_ready() {
+    __xavto_adjust_history
+    __xavto_last_history=$(__xavto_history_entry)
+    __xavto_at_prompt=1
+}
+
+PROMPT_COMMAND=$'__xavto_precmd\n'"$PROMPT_COMMAND"$'\n__xavto_prompt_ready'
+PS1="\[\e]133;A\a\]$PS1\[\e]133;B\a\]"
+__xavto_save_debug_trap "$(builtin trap -p DEBUG)"
+trap '__xavto_debug' DEBUG
+"#;
+
+const ZSH_ENV: &str = r#"
+if [ -f "${XAVTO_USER_ZDOTDIR:-$HOME}/.zshenv" ]; then
+    source "${XAVTO_USER_ZDOTDIR:-$HOME}/.zshenv"
+fi
+"#;
+
+const ZSH_INTEGRATION: &str = r#"
+ZDOTDIR="${XAVTO_USER_ZDOTDIR:-$HOME}"
+if [ -f "$ZDOTDIR/.zshrc" ]; then source "$ZDOTDIR/.zshrc"; fi
+
+__xavto_escape() {
+    local s="${1//\\/\\\\}"
+    s="${s//;/\\x3b}"
+    s="${s//$'\n'/\\x0a}"
+    print -rn -- "$s"
+}
+
+__xavto_in_command=0
+
+__xavto_preexec() {
+    # Строка с пробелом в начале не попадает в историю, ее не записываем и мы
+    [[ -o histignorespace && "$1" == " "* ]] && return
+    __xavto_in_command=1
+    printf '\e]633;E;%s\a\e]133;C\a' "$(__xavto_escape "$1")"
+}
+
+__xavto_precmd() {
+    local code=$?
+    if [[ "$__xavto_in_command" = 1 ]]; then
+        printf '\e]133;D;%s\a' "$code"
+    fi
+    __xavto_in_command=0
+    printf '\e]633;P;Cwd=%s\a' "$(__xavto_escape "$PWD")"
+}
+
+preexec_functions=(__xavto_preexec $preexec_functions)
+precmd_functions=(__xavto_precmd $precmd_functions)
+"#;
+
+// Команда запуска оболочки с внедренными хуками интеграции
+pub fn build_shell_command(program: &str, kind: ShellKind, cwd: Option<&str>) -> Result<CommandBuilder, String> {
+    let mut cmd = CommandBuilder::new(program);
+
+    // Рабочая директория панели (например, при восстановлении сохраненной раскладки)
+    if let Some(cwd) = cwd.filter(|c| !c.is_empty()) {
+        if !std::path::Path::new(cwd).is_dir() {
+            return Err(format!("Директория не найдена: {}", cwd));
+        }
+        cmd.cwd(cwd);
+    }
+
+    match kind {
+        ShellKind::PowerShell => {
+            // Для PowerShell используем UTF-8 с BOM, чтобы Windows правильно распознавала кириллицу
+            let script_path = integration_dir()?.join("xavto-integration.ps1");
+            let mut script = vec![0xEF, 0xBB, 0xBF];
+            script.extend_from_slice(POWERSHELL_INTEGRATION.as_bytes());
+            write_integration_file(&script_path, &script)?;
+
+            cmd.args([
+                "-NoExit".to_string(),
+                "-Command".to_string(),
+                format!(
+                    "& {{$OutputEncoding = [Console]::OutputEncoding = [Console]::InputEncoding = [System.Text.Encoding]::UTF8; chcp 65001 | Out-Null; . '{}'; Clear-Host; Write-Host ('Терминал X-Avto #' + [string]$PID + ' готов к работе!') -ForegroundColor Green; Write-Host}}",
+                    script_path.to_string_lossy().replace('\'', "''")
+                ),
+            ]);
+        }
+        ShellKind::Bash => {
+            let rc_path = integration_dir()?.join("xavto-bashrc");
+            write_integration_file(&rc_path, BASH_INTEGRATION.as_bytes())?;
+
+            cmd.arg("--rcfile");
+            cmd.arg(rc_path.as_os_str());
+            cmd.arg("-i");
+        }
+        ShellKind::Zsh => {
+            let zdotdir = integration_dir()?.join("zsh");
+            #[cfg(unix)]
+            private_dir(&zdotdir, unsafe { libc::geteuid() })?;
+            #[cfg(not(unix))]
+            fs::create_dir_all(&zdotdir)
+                .map_err(|e| format!("Не удалось создать директорию интеграции: {}", e))?;
+            write_integration_file(&zdotdir.join(".zshenv"), ZSH_ENV.as_bytes())?;
+            write_integration_file(&zdotdir.join(".zshrc"), ZSH_INTEGRATION.as_bytes())?;
+
+            // Запоминаем пользовательский ZDOTDIR, чтобы подгрузить его настройки из нашего .zshrc
+            if let Ok(user_zdotdir) = std::env::var("ZDOTDIR") {
+                cmd.env("XAVTO_USER_ZDOTDIR", user_zdotdir);
+            }
+            cmd.env("ZDOTDIR", zdotdir.as_os_str());
+            cmd.arg("-i");
+        }
+        ShellKind::Other => {}
+    }
+
+    if !cfg!(target_os = "windows") {
+        cmd.env("TERM", "xterm-256color");
+    }
+
+    Ok(cmd)
+}
+
+// Событие интеграции, извлеченное из потока вывода
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShellEvent {
+    // A - начало приглашения
+    PromptStart,
+    // B - конец приглашения, начало ввода команды
+    CommandStart,
+    // C - команда запущена, далее идет ее вывод
+    CommandExecuted,
+    // D[;код] - команда завершена
+    CommandFinished(Option<i32>),
+    // 633;E;строка - текст выполняемой команды
+    CommandLine(String),
+    // 633;P;Cwd=путь - текущая директория
+    Cwd(String),
+}
+
+// Фрагмент разобранного вывода: обычный текст или событие интеграции
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShellOutput {
+    Text(String),
+    Event(ShellEvent),
+}
+
+// Разбор значения, экранированного хуками (\\ и \xNN)
+fn unescape_value(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            result.push(ch);
+            continue;
+        }
+
+        match chars.peek() {
+            Some('\\') => {
+                chars.next();
+                result.push('\\');
+            }
+            Some('x') => {
+                chars.next();
+                let hex: String = chars.by_ref().take(2).collect();
+                match u8::from_str_radix(&hex, 16) {
+                    Ok(byte) => result.push(byte as char),
+                    Err(_) => {
+                        result.push_str("\\x");
+                        result.push_str(&hex);
+                    }
+                }
+            }
+            _ => result.push('\\'),
+        }
+    }
+
+    result
+}
+
+// Разбор тела OSC-последовательности (без ESC ] и терминатора)
+fn parse_osc_body(body: &str) -> Option<ShellEvent> {
+    let mut parts = body.splitn(3, ';');
+    let code = parts.next()?;
+    if code != "133" && code != "633" {
+        return None;
+    }
+
+    let kind = parts.next()?;
+    let rest = parts.next();
+
+    match kind {
+        "A" => Some(ShellEvent::PromptStart),
+        "B" => Some(ShellEvent::CommandStart),
+        "C" => Some(ShellEvent::CommandExecuted),
+        "D" => Some(ShellEvent::CommandFinished(
+            rest.and_then(|r| r.split(';').next()).and_then(|c| c.trim().parse().ok()),
+        )),
+        "E" if code == "633" => Some(ShellEvent::CommandLine(
+            unescape_value(rest.map(|r| r.split(';').next().unwrap_or("")).unwrap_or("")),
+        )),
+        "P" if code == "633" => {
+            let property = rest?;
+            property
+                .strip_prefix("Cwd=")
+                .map(|cwd| ShellEvent::Cwd(unescape_value(cwd)))
+        }
+        _ => None,
+    }
+}
+
+// Потоковый разборщик OSC 133 / OSC 633
+// Незавершенная последовательность в конце фрагмента переносится в следующий вызов
+#[derive(Default)]
+pub struct OscParser {
+    pending: String,
+}
+
+impl OscParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn feed(&mut self, chunk: &str) -> Vec<ShellOutput> {
+        let mut data = std::mem::take(&mut self.pending);
+        data.push_str(chunk);
+
+        let mut items = Vec::new();
+        let mut text = String::new();
+        let mut rest = data.as_str();
+
+        while let Some(pos) = rest.find('\x1b') {
+            text.push_str(&rest[..pos]);
+            let tail = &rest[pos..];
+
+            // Одиночный ESC в конце фрагмента может оказаться началом OSC
+            if tail.len() == 1 {
+                self.pending.push_str(tail);
+                rest = "";
+                break;
+            }
+
+            if !tail.starts_with("\x1b]") {
+                text.push('\x1b');
+                rest = &tail[1..];
+                continue;
+            }
+
+            let body_start = &tail[2..];
+            let terminator = body_start
+                .find('\x07')
+                .map(|i| (i, 1))
+                .into_iter()
+                .chain(body_start.find("\x1b\\").map(|i| (i, 2)))
+                .min_by_key(|(i, _)| *i);
+
+            match terminator {
+                Some((end, term_len)) => {
+                    let body = &body_start[..end];
+                    let sequence_len = 2 + end + term_len;
+                    match parse_osc_body(body) {
+                        Some(event) => {
+                            if !text.is_empty() {
+                                items.push(ShellOutput::Text(std::mem::take(&mut text)));
+                            }
+                            items.push(ShellOutput::Event(event));
+                        }
+                        // Прочие OSC (заголовок окна и т.п.) оставляем в тексте как есть
+                        None => text.push_str(&tail[..sequence_len]),
+                    }
+                    rest = &tail[sequence_len..];
+                }
+                None => {
+                    if tail.len() > MAX_PENDING_SEQUENCE {
+                        text.push_str(tail);
+                    } else {
+                        self.pending.push_str(tail);
+                    }
+                    rest = "";
+                    break;
+                }
+            }
+        }
+
+        text.push_str(rest);
+        if !text.is_empty() {
+            items.push(ShellOutput::Text(text));
+        }
+
+        items
+    }
+}
+
+// Удаление управляющих ANSI-последовательностей из захваченного вывода
+pub fn strip_ansi(input: &str) -> String {
+    let mut result = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\x1b' => match chars.peek() {
+                // CSI: ESC [ ... финальный байт 0x40-0x7E
+                Some('[') => {
+                    chars.next();
+                    for c in chars.by_ref() {
+                        if ('\x40'..='\x7e').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+                // OSC: ESC ] ... BEL или ESC \
+                Some(']') => {
+                    chars.next();
+                    while let Some(c) = chars.next() {
+                        if c == '\x07' {
+                            break;
+                        }
+                        if c == '\x1b' && chars.peek() == Some(&'\\') {
+                            chars.next();
+                            break;
+                        }
+                    }
+                }
+                // ESC, промежуточные байты 0x20-0x2F (например, ESC ( B), финальный байт
+                Some(_) => {
+                    for c in chars.by_ref() {
+                        if !('\x20'..='\x2f').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+                None => {}
+            },
+            '\r' => {
+                if chars.peek() != Some(&'\n') {
+                    result.push('\n');
+                }
+            }
+            '\x07' | '\x08' => {}
+            _ => result.push(ch),
+        }
+    }
+
+    result
+}
+
+// Выполненная команда, собранная по событиям интеграции
+#[derive(Debug, Clone)]
+pub struct CapturedCommand {
+    pub command: String,
+    pub cwd: Option<String>,
+    pub started_at: DateTime<Local>,
+    pub finished_at: DateTime<Local>,
+    pub exit_code: Option<i32>,
+    pub output: String,
+}
+
+struct RunningCommand {
+    command: String,
+    cwd: Option<String>,
+    started_at: DateTime<Local>,
+    output: String,
+    truncated: bool,
+}
+
+// Отслеживание жизненного цикла команд в одном терминале
+#[derive(Default)]
+pub struct CommandTracker {
+    cwd: Option<String>,
+    pending_line: Option<String>,
+    current: Option<RunningCommand>,
+}
+
+impl CommandTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cwd(&self) -> Option<&str> {
+        self.cwd.as_deref()
+    }
+
+    pub fn process(&mut self, items: Vec<ShellOutput>) -> Vec<CapturedCommand> {
+        let mut finished = Vec::new();
+
+        for item in items {
+            match item {
+                ShellOutput::Text(text) => {
+                    if let Some(current) = self.current.as_mut() {
+                        Self::append_output(current, &text);
+                    }
+                }
+                ShellOutput::Event(ShellEvent::CommandLine(line)) => {
+                    match self.current.as_mut() {
+                        Some(current) if current.command.is_empty() => current.command = line,
+                        _ => self.pending_line = Some(line),
+                    }
+                }
+                ShellOutput::Event(ShellEvent::Cwd(cwd)) => {
+                    self.cwd = Some(cwd);
+                }
+                ShellOutput::Event(ShellEvent::CommandExecuted) => {
+                    let line = self.pending_line.take();
+                    match self.current.as_mut() {
+                        // Повторный C (например, продолжение многострочного ввода) - обновляем текст
+                        Some(current) => {
+                            if let Some(line) = line {
+                                current.command = line;
+                            }
+                        }
+                        None => {
+                            self.current = Some(RunningCommand {
+                                command: line.unwrap_or_default(),
+                                cwd: self.cwd.clone(),
+                                started_at: Local::now(),
+                                output: String::new(),
+                                truncated: false,
+                            });
+                        }
+                    }
+                }
+                ShellOutput::Event(ShellEvent::CommandFinished(exit_code)) => {
+                    let now = Local::now();
+                    let running = self.current.take().or_else(|| {
+                        // Хук завершения пришел без C - записываем команду без вывода
+                        self.pending_line.take().map(|command| RunningCommand {
+                            command,
+                            cwd: self.cwd.clone(),
+                            started_at: now,
+                            output: String::new(),
+                            truncated: false,
+                        })
+                    });
+
+                    if let Some(running) = running {
+                        if running.command.trim().is_empty() {
+                            continue;
+                        }
+
+                        let mut output = strip_ansi(&running.output);
+                        if running.truncated {
+                            output.push_str("\n[вывод обрезан]");
+                        }
+
+                        finished.push(CapturedCommand {
+                            command: running.command.trim().to_string(),
+                            cwd: running.cwd,
+                            started_at: running.started_at,
+                            finished_at: now,
+                            exit_code,
+                            output: output.trim_end().to_string(),
+                        });
+                    }
+                }
+                ShellOutput::Event(ShellEvent::PromptStart)
+                | ShellOutput::Event(ShellEvent::CommandStart) => {}
+            }
+        }
+
+        finished
+    }
+
+    fn append_output(current: &mut RunningCommand, text: &str) {
+        if current.truncated {
+            return;
+        }
+
+        let available = MAX_CAPTURED_OUTPUT.saturating_sub(current.output.len());
+        if text.len() <= available {
+            current.output.push_str(text);
+            return;
+        }
+
+        // Обрезаем по границе символа
+        let mut cut = available;
+        while cut > 0 && !text.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        current.output.push_str(&text[..cut]);
+        current.truncated = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed_all(parser: &mut OscParser, chunks: &[&str]) -> Vec<ShellOutput> {
+        chunks.iter().flat_map(|chunk| parser.feed(chunk)).collect()
+    }
+
+    #[test]
+    fn osc_split_across_chunks() {
+        let mut parser = OscParser::new();
+        let items = feed_all(&mut parser, &["out\x1b", "]633;E;ls\\x3b", " pwd\x07\x1b]13", "3;C\x1b", "\\rest"]);
+
+        assert_eq!(items, vec![
+            ShellOutput::Text("out".into()),
+            ShellOutput::Event(ShellEvent::CommandLine("ls; pwd".into())),
+            ShellOutput::Event(ShellEvent::CommandExecuted),
+            ShellOutput::Text("rest".into()),
+        ]);
+    }
+
+    #[test]
+    fn osc_foreign_sequences_stay_in_text() {
+        let mut parser = OscParser::new();
+        let items = feed_all(&mut parser, &["\x1b]0;title\x07\x1b[1mA\x1b]133;D;2\x07"]);
+
+        assert_eq!(items, vec![
+            ShellOutput::Text("\x1b]0;title\x07\x1b[1mA".into()),
+            ShellOutput::Event(ShellEvent::CommandFinished(Some(2))),
+        ]);
+    }
+
+    #[test]
+    fn osc_unterminated_sequence_is_flushed_as_text() {
+        let mut parser = OscParser::new();
+        let long = format!("\x1b]633;E;{}", "x".repeat(MAX_PENDING_SEQUENCE));
+        let items = parser.feed(&long);
+
+        assert_eq!(items, vec![ShellOutput::Text(long)]);
+        assert!(parser.feed("\x07").iter().all(|item| matches!(item, ShellOutput::Text(_))));
+    }
+
+    #[test]
+    fn strip_ansi_removes_sequences() {
+        assert_eq!(strip_ansi("\x1b[1;31mred\x1b[0m\r\nnext\rover\x07"), "red\nnext\nover");
+        assert_eq!(strip_ansi("a\x1b]0;title\x1b\\b\x1b(Bc"), "abc");
+    }
+
+    #[test]
+    fn tracker_collects_command_from_split_chunks() {
+        let mut parser = OscParser::new();
+        let mut tracker = CommandTracker::new();
+        let chunks = [
+            "\x1b]633;P;Cwd=/tmp\x07\x1b]133;A\x07$ \x1b]133;B\x07",
+            "\x1b]633;E;make && make install\x07\x1b]1",
+            "33;C\x07\x1b[32mbuilt\x1b",
+            "[0m\r\n\x1b]133;D;0\x07",
+        ];
+
+        let mut finished = Vec::new();
+        for chunk in chunks {
+            finished.extend(tracker.process(parser.feed(chunk)));
+        }
+
+        assert_eq!(finished.len(), 1);
+        assert_eq!(finished[0].command, "make && make install");
+        assert_eq!(finished[0].cwd.as_deref(), Some("/tmp"));
+        assert_eq!(finished[0].exit_code, Some(0));
+        assert_eq!(finished[0].output, "built");
+        assert_eq!(tracker.cwd(), Some("/tmp"));
+    }
+
+    #[test]
+    fn tracker_skips_empty_commands_and_truncates_output() {
+        let mut tracker = CommandTracker::new();
+        let empty = tracker.process(vec![
+            ShellOutput::Event(ShellEvent::CommandExecuted),
+            ShellOutput::Event(ShellEvent::CommandFinished(Some(0))),
+        ]);
+        assert!(empty.is_empty());
+
+        let finished = tracker.process(vec![
+            ShellOutput::Event(ShellEvent::CommandLine("cat big".into())),
+            ShellOutput::Event(ShellEvent::CommandExecuted),
+            ShellOutput::Text("я".repeat(MAX_CAPTURED_OUTPUT)),
+            ShellOutput::Event(ShellEvent::CommandFinished(None)),
+        ]);
+        assert_eq!(finished.len(), 1);
+        assert!(finished[0].output.ends_with("[вывод обрезан]"));
+        assert!(finished[0].output.len() <= MAX_CAPTURED_OUTPUT + "\n[вывод обрезан]".len());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn integration_file_replaces_planted_symlink() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let dir = tempfile::tempdir().unwrap();
+        let private = dir.path().join("private");
+        fs::create_dir(&private).unwrap();
+        fs::set_permissions(&private, fs::Permissions::from_mode(0o777)).unwrap();
+        private_dir(&private, unsafe { libc::geteuid() }).unwrap();
+        assert_eq!(fs::metadata(&private).unwrap().permissions().mode() & 0o777, 0o700);
+
+        let victim = dir.path().join("victim");
+        fs::write(&victim, "keep").unwrap();
+        let rc = private.join("xavto-bashrc");
+        symlink(&victim, &rc).unwrap();
+
+        write_integration_file(&rc, b"hooks").unwrap();
+        assert_eq!(fs::read_to_string(&victim).unwrap(), "keep");
+        assert!(fs::symlink_metadata(&rc).unwrap().file_type().is_file());
+        assert_eq!(fs::read_to_string(&rc).unwrap(), "hooks");
+
+        let link = dir.path().join("link");
+        symlink(&private, &link).unwrap();
+        assert!(private_dir(&link, unsafe { libc::geteuid() }).is_err());
+    }
+}
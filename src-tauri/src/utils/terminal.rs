@@ -1,28 +1,92 @@
 use tauri::Emitter;
 use tauri::Manager;
+use serde::Serialize;
 
-use portable_pty::{native_pty_system, CommandBuilder, PtySize};
 use std::{
-    io::{Read, Write},
-    sync::Arc,
-    collections::HashMap
+    io::Read,
+    sync::{atomic::{AtomicBool, Ordering}, Arc},
+    collections::{HashMap, HashSet}
 };
 use tauri::{
-    async_runtime::{spawn, Mutex},
+    async_runtime::{spawn, spawn_blocking, Mutex},
     AppHandle, State,
 };
 
+use crate::utils::db::{get_setting, insert_terminal_command, set_setting, DbState, TerminalCommandRecord};
+use crate::utils::input_guard::{paste_mode_change, wrap_paste, InputGuard, InputGuardConfig, InputLine, InputResult};
+use crate::utils::session_daemon::{self, SessionInfo};
+use crate::utils::shell_integration::{build_shell_command, default_shell, CapturedCommand, CommandTracker, OscParser, ShellKind};
+use crate::utils::terminal_backend::{
+    available_serial_ports, BackendIo, BackendKind, LocalPtyBackend, SerialBackend, SerialConfig, SshBackend, SshConfig, TerminalBackend,
+};
+use crate::utils::terminal_encoding::{OutputMode, SharedCodec, TerminalCodec};
+use crate::utils::terminal_search::{Scrollback, SharedScrollback};
+use crate::utils::terminal_watch::{new_watch_set, LineMatcher, OutputWatch, WatchInfo, WatchMode, WatchSet};
+
+// Ключ настроек защиты ввода в app_settings
+const INPUT_GUARD_SETTING: &str = "input_guard";
+
 // Структура для хранения данных отдельного терминального процесса
 struct TerminalProcess {
-    master: Box<dyn portable_pty::MasterPty + Send>,
-    writer: Box<dyn Write + Send>,
+    backend: Box<dyn TerminalBackend>,
     terminal_id: u32,
+    shell: ShellKind,
+    // Вкладка, в историю которой записываются выполненные команды
+    tab_id: Option<i64>,
+    // Наблюдатели за выводом (проверяются в задаче чтения)
+    watches: WatchSet,
+    // Кодировка ввода/вывода и режим отправки вывода
+    codec: SharedCodec,
+    // Текст вывода для поиска
+    scrollback: SharedScrollback,
+    // Набираемая строка для проверки опасных команд
+    input_line: InputLine,
+    // Оболочка включила режим bracketed paste
+    bracketed_paste: Arc<AtomicBool>,
+}
+
+impl TerminalProcess {
+    // Запись текста в терминал в его кодировке
+    fn write_text(&mut self, text: &str) -> Result<(), String> {
+        let bytes = self.codec.lock()
+            .map_err(|e| format!("Ошибка блокировки мьютекса: {}", e))?
+            .encode(text);
+        self.backend.write_input(&bytes)?;
+        self.input_line.commit(text);
+        Ok(())
+    }
+    
+    // Запись ввода с проверкой опасных команд (force - без проверки, после подтверждения)
+    fn write_guarded(&mut self, input: &str, guard: &InputGuard, force: bool) -> Result<InputResult, String> {
+        if !force {
+            if let Some((pattern, command)) = self.input_line.check(input, guard) {
+                println!("Terminal {} input requires confirmation (pattern {})", self.terminal_id, pattern);
+                return Ok(InputResult::ConfirmationRequired {
+                    pattern: pattern.to_string(),
+                    command,
+                });
+            }
+        }
+        
+        self.write_text(input)?;
+        Ok(InputResult::Sent)
+    }
+}
+
+// Именованная группа терминалов для широковещательного ввода
+#[derive(Debug, Clone, Default)]
+struct TerminalGroup {
+    members: Vec<u32>,
+    // Участники, временно исключенные из рассылки
+    excluded: HashSet<u32>,
 }
 
 // Состояние для хранения всех терминальных процессов
 pub struct PtyState {
     terminals: Arc<Mutex<HashMap<u32, TerminalProcess>>>,
     next_id: Arc<Mutex<u32>>,
+    groups: Arc<Mutex<HashMap<String, TerminalGroup>>>,
+    input_guard: Arc<Mutex<InputGuard>>,
 }
 
 impl PtyState {
@@ -30,79 +94,286 @@ impl PtyState {
         PtyState {
             terminals: Arc::new(Mutex::new(HashMap::new())),
             next_id: Arc::new(Mutex::new(1)),
+            groups: Arc::new(Mutex::new(HashMap::new())),
+            input_guard: Arc::new(Mutex::new(InputGuard::default())),
         }
     }
+    
+    // Получение нового ID для терминала
+    async fn allocate_id(&self) -> u32 {
+        let mut next_id = self.next_id.lock().await;
+        let id = *next_id;
+        *next_id += 1;
+        id
+    }
+    
+    // PID процессов, обслуживающих терминалы (для статистики)
+    pub(crate) async fn process_ids(&self) -> Vec<(u32, Option<u32>)> {
+        let terminals = self.terminals.lock().await;
+        let mut process_ids: Vec<_> = terminals.values()
+            .map(|t| (t.terminal_id, t.backend.process_id()))
+            .collect();
+        process_ids.sort_by_key(|(terminal_id, _)| *terminal_id);
+        process_ids
+    }
+    
+    // Буферы прокрутки запущенных терминалов с привязкой к вкладкам
+    pub(crate) async fn scrollbacks(&self) -> Vec<(u32, Option<i64>, SharedScrollback)> {
+        let terminals = self.terminals.lock().await;
+        let mut scrollbacks: Vec<_> = terminals.values()
+            .map(|t| (t.terminal_id, t.tab_id, t.scrollback.clone()))
+            .collect();
+        scrollbacks.sort_by_key(|(terminal_id, _, _)| *terminal_id);
+        scrollbacks
+    }
+    
+    // Терминал, уже подключенный к сессии демона
+    pub(crate) async fn terminal_for_session(&self, session_id: u32) -> Option<u32> {
+        let terminals = self.terminals.lock().await;
+        terminals.values()
+            .find(|t| t.backend.session_id() == Some(session_id))
+            .map(|t| t.terminal_id)
+    }
+    
+    // ID сессии демона, которую обслуживает терминал
+    pub(crate) async fn session_for_terminal(&self, terminal_id: u32) -> Option<u32> {
+        let terminals = self.terminals.lock().await;
+        terminals.get(&terminal_id).and_then(|t| t.backend.session_id())
+    }
+}
+
+// Результат отправки ввода в отдельный терминал при рассылке
+#[derive(Debug, Clone, Serialize)]
+pub struct BroadcastResult {
+    pub terminal_id: u32,
+    pub ok: bool,
+    pub error: Option<String>,
+    // Результат проверки защиты ввода (ConfirmationRequired - ввод в этот терминал не отправлен)
+    pub result: Option<InputResult>,
+}
+
+// Информация о группе терминалов для клиента
+#[derive(Debug, Clone, Serialize)]
+pub struct TerminalGroupInfo {
+    pub name: String,
+    pub members: Vec<u32>,
+    pub excluded: Vec<u32>,
+}
+
+// Информация об активном терминале для клиента
+#[derive(Debug, Clone, Serialize)]
+pub struct TerminalInfo {
+    pub terminal_id: u32,
+    pub kind: BackendKind,
+    pub tab_id: Option<i64>,
+    // ID сессии в демоне для постоянных терминалов
+    pub session_id: Option<u32>,
 }
 
 #[tauri::command]
 pub async fn resize_pty(state: State<'_, PtyState>, terminal_id: u32, rows: u16, cols: u16) -> Result<(), String> {
-    let terminals = state.terminals.lock().await;
+    let mut terminals = state.terminals.lock().await;
     
-    if let Some(terminal) = terminals.get(&terminal_id) {
-        terminal.master
-            .resize(PtySize {
-                rows,
-                cols,
-                pixel_width: 0,
-                pixel_height: 0,
-            })
-            .map_err(|e| e.to_string())?;
-        
-        Ok(())
+    if let Some(terminal) = terminals.get_mut(&terminal_id) {
+        terminal.backend.resize(rows, cols)
     } else {
         Err(format!("Терминал с ID {} не найден", terminal_id))
     }
 }
 
+// Преобразование команды, собранной интеграцией с оболочкой, в запись истории
+fn captured_to_record(tab_id: i64, captured: &CapturedCommand) -> TerminalCommandRecord {
+    TerminalCommandRecord {
+        id: None,
+        terminal_tab_id: tab_id,
+        command: captured.command.clone(),
+        time: captured.started_at.format("%H:%M:%S").to_string(),
+        status: Some(match captured.exit_code {
+            Some(0) => "success".to_string(),
+            _ => "error".to_string(),
+        }),
+        exit_code: captured.exit_code,
+        output: Some(captured.output.clone()),
+        cwd: captured.cwd.clone(),
+        started_at: Some(captured.started_at.to_rfc3339()),
+        finished_at: Some(captured.finished_at.to_rfc3339()),
+    }
+}
+
 #[tauri::command]
-pub async fn start_process(state: State<'_, PtyState>, app: AppHandle) -> Result<u32, String> {
+pub async fn start_process(
+    state: State<'_, PtyState>,
+    app: AppHandle,
+    shell: Option<String>,
+    tab_id: Option<i64>,
+    encoding: Option<String>,
+    cwd: Option<String>,
+    persistent: Option<bool>,
+) -> Result<u32, String> {
     println!("Starting new terminal process...");
     
+    let codec = TerminalCodec::new(encoding.as_deref())?;
+    
+    // Постоянная сессия принадлежит демону и переживает перезапуск приложения
+    if persistent.unwrap_or(false) {
+        let info = spawn_blocking(move || session_daemon::create_session(shell, tab_id, cwd))
+            .await
+            .map_err(|e| e.to_string())??;
+        return attach_persistent_session(&state, &app, &info, codec).await;
+    }
+    
     // Получаем новый ID для терминала
-    let terminal_id = {
-        let mut next_id = state.next_id.lock().await;
-        let id = *next_id;
-        *next_id += 1;
-        id
-    };
+    let terminal_id = state.allocate_id().await;
     
     println!("Assigned terminal ID: {}", terminal_id);
     
-    let pty_system = native_pty_system();
+    // Запускаем оболочку с внедренными хуками интеграции (OSC 133 / OSC 633)
+    let program = shell.unwrap_or_else(default_shell);
+    let shell_kind = ShellKind::detect(&program);
+    let cmd = build_shell_command(&program, shell_kind, cwd.as_deref())?;
+    
+    let (backend, io) = LocalPtyBackend::spawn(cmd)?;
+    
+    println!("Shell process {} ({:?}) spawned for terminal {}", program, shell_kind, terminal_id);
     
-    let pair = pty_system
-        .openpty(PtySize {
-            rows: 24,
-            cols: 80,
-            pixel_width: 0,
-            pixel_height: 0,
-        })
-        .map_err(|e| e.to_string())?;
+    register_terminal(&state, &app, terminal_id, Box::new(backend), io, codec, shell_kind, tab_id).await;
+    
+    println!("Terminal {} process setup complete", terminal_id);
+    Ok(terminal_id)
+}
+
+// Подключение сессии демона как терминала
+pub(crate) async fn attach_persistent_session(
+    state: &PtyState,
+    app: &AppHandle,
+    info: &SessionInfo,
+    codec: TerminalCodec,
+) -> Result<u32, String> {
+    let (backend, io) = session_daemon::attach_session(info)?;
+    let terminal_id = state.allocate_id().await;
+    let shell_kind = ShellKind::detect(&info.shell);
+    
+    register_terminal(state, app, terminal_id, Box::new(backend), io, codec, shell_kind, info.tab_id).await;
+    
+    println!("Persistent session {} ({}) attached to terminal {}", info.session_id, info.shell, terminal_id);
+    Ok(terminal_id)
+}
+
+// Список сессий демона (пустой, если демон не запущен)
+#[tauri::command]
+pub async fn list_persistent_sessions() -> Result<Vec<SessionInfo>, String> {
+    spawn_blocking(session_daemon::list_sessions)
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+// Повторное подключение ко всем сессиям демона при запуске приложения
+// Уже подключенные сессии пропускаются
+#[tauri::command]
+pub async fn reconnect_persistent_sessions(state: State<'_, PtyState>, app: AppHandle) -> Result<Vec<TerminalInfo>, String> {
+    let sessions = spawn_blocking(session_daemon::list_sessions)
+        .await
+        .map_err(|e| e.to_string())??;
+    
+    let attached: HashSet<u32> = state.terminals.lock().await
+        .values()
+        .filter_map(|t| t.backend.session_id())
+        .collect();
+    
+    let mut reconnected = Vec::new();
+    for info in sessions.iter().filter(|s| !attached.contains(&s.session_id)) {
+        match attach_persistent_session(&state, &app, info, TerminalCodec::new(None)?).await {
+            Ok(terminal_id) => reconnected.push(TerminalInfo {
+                terminal_id,
+                kind: BackendKind::Persistent,
+                tab_id: info.tab_id,
+                session_id: Some(info.session_id),
+            }),
+            Err(e) => eprintln!("Error reconnecting to persistent session {}: {}", info.session_id, e),
+        }
+    }
+    
+    println!("Reconnected to {} persistent session(s)", reconnected.len());
+    Ok(reconnected)
+}
 
-    let mut cmd = CommandBuilder::new("powershell.exe");
+// Открытие последовательной консоли как терминала
+#[tauri::command]
+pub async fn start_serial_session(
+    state: State<'_, PtyState>,
+    app: AppHandle,
+    config: SerialConfig,
+    tab_id: Option<i64>,
+    encoding: Option<String>,
+) -> Result<u32, String> {
+    println!("Opening serial port {} ({} baud)...", config.port, config.baud_rate);
     
-    // Упрощаем команду PowerShell для более надежной работы
-    cmd.args([
-        "-NoExit", 
-        "-Command", 
-        "& {$OutputEncoding = [Console]::OutputEncoding = [Console]::InputEncoding = [System.Text.Encoding]::UTF8; chcp 65001 | Out-Null; Clear-Host; Write-Host ('Терминал X-Avto #' + [string]$PID + ' готов к работе!') -ForegroundColor Green; Write-Host}"
-    ]);
+    let codec = TerminalCodec::new(encoding.as_deref())?;
+    let (backend, io) = SerialBackend::open(&config)?;
+    let terminal_id = state.allocate_id().await;
     
-    let mut child = pair.slave.spawn_command(cmd).map_err(|e| e.to_string())?;
+    register_terminal(&state, &app, terminal_id, Box::new(backend), io, codec, ShellKind::Other, tab_id).await;
     
-    println!("PowerShell process spawned for terminal {}", terminal_id);
+    println!("Serial session on {} attached to terminal {}", config.port, terminal_id);
+    Ok(terminal_id)
+}
+
+// Открытие SSH-сессии как терминала
+#[tauri::command]
+pub async fn start_ssh_session(
+    state: State<'_, PtyState>,
+    app: AppHandle,
+    config: SshConfig,
+    tab_id: Option<i64>,
+    encoding: Option<String>,
+) -> Result<u32, String> {
+    println!("Starting SSH session to {}...", config.host);
+    
+    let codec = TerminalCodec::new(encoding.as_deref())?;
+    let (backend, io) = SshBackend::connect(&config)?;
+    let terminal_id = state.allocate_id().await;
+    
+    register_terminal(&state, &app, terminal_id, Box::new(backend), io, codec, ShellKind::Other, tab_id).await;
+    
+    println!("SSH session to {} attached to terminal {}", config.host, terminal_id);
+    Ok(terminal_id)
+}
 
-    let master = pair.master;
-    let mut reader = master.try_clone_reader().map_err(|e| e.to_string())?;
-    let writer = master.take_writer().map_err(|e| e.to_string())?;
+#[tauri::command]
+pub async fn list_serial_ports() -> Result<Vec<String>, String> {
+    available_serial_ports()
+}
 
+// Регистрация терминала в состоянии и запуск задач чтения вывода и ожидания процесса
+async fn register_terminal(
+    state: &PtyState,
+    app: &AppHandle,
+    terminal_id: u32,
+    backend: Box<dyn TerminalBackend>,
+    io: BackendIo,
+    codec: TerminalCodec,
+    shell: ShellKind,
+    tab_id: Option<i64>,
+) {
+    let BackendIo { mut reader, mut child } = io;
+    let watches = new_watch_set();
+    let codec = codec.shared();
+    let scrollback = Scrollback::shared();
+    let bracketed_paste = Arc::new(AtomicBool::new(false));
+    
     // Добавляем новый терминал в хранилище
     {
         let mut terminals = state.terminals.lock().await;
         terminals.insert(terminal_id, TerminalProcess {
-            master,
-            writer,
+            backend,
             terminal_id,
+            shell,
+            tab_id,
+            watches: watches.clone(),
+            codec: codec.clone(),
+            scrollback: scrollback.clone(),
+            input_line: InputLine::default(),
+            bracketed_paste: bracketed_paste.clone(),
         });
     }
 
@@ -112,6 +383,9 @@ pub async fn start_process(state: State<'_, PtyState>, app: AppHandle) -> Result
     spawn(async move {
         println!("Starting read thread for terminal {}", terminal_id);
         let mut buffer = [0u8; 4096];
+        let mut osc_parser = OscParser::new();
+        let mut tracker = CommandTracker::new();
+        let mut matcher = LineMatcher::new(terminal_id);
         
         // Небольшая задержка перед первым чтением, чтобы PowerShell успел инициализироваться
         tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
@@ -123,14 +397,45 @@ pub async fn start_process(state: State<'_, PtyState>, app: AppHandle) -> Result
                     break;
                 },
                 Ok(n) => {
-                    let output = String::from_utf8_lossy(&buffer[..n]).to_string();
+                    // Декодер переносит незавершенные многобайтовые символы в следующее чтение
+                    let (output, mode) = match codec.lock() {
+                        Ok(mut codec) => (codec.decode(&buffer[..n]), codec.mode()),
+                        Err(_) => (String::from_utf8_lossy(&buffer[..n]).to_string(), OutputMode::Text),
+                    };
                     println!("Terminal {} output received, length: {} bytes", terminal_id, n);
                     
                     // Отправка вывода в клиент с указанием ID терминала
-                    match app_handle.emit("pty-output", (terminal_id, output.clone())) {
+                    let emitted = match mode {
+                        OutputMode::Text if output.is_empty() => Ok(()),
+                        OutputMode::Text => app_handle.emit("pty-output", (terminal_id, output.clone())),
+                        OutputMode::Raw => app_handle.emit("pty-output-raw", (terminal_id, buffer[..n].to_vec())),
+                    };
+                    match emitted {
                         Ok(_) => println!("Successfully emitted terminal {} output to client", terminal_id),
                         Err(e) => eprintln!("Error emitting output from terminal {}: {}", terminal_id, e),
                     }
+                    
+                    if let Ok(mut scrollback) = scrollback.lock() {
+                        scrollback.push(&output);
+                    }
+                    
+                    if let Some(enabled) = paste_mode_change(&output) {
+                        bracketed_paste.store(enabled, Ordering::SeqCst);
+                    }
+                    
+                    // Разбираем события интеграции и сохраняем завершенные команды в историю
+                    let finished = tracker.process(osc_parser.feed(&output));
+                    if !finished.is_empty() {
+                        record_captured_commands(&app_handle, terminal_id, finished).await;
+                    }
+                    
+                    // Проверяем наблюдатели за выводом
+                    for found in matcher.feed(&output, &watches) {
+                        println!("Terminal {} output matched watch {} ({})", terminal_id, found.watch_id, found.pattern);
+                        if let Err(e) = app_handle.emit("pty-match", found) {
+                            eprintln!("Error emitting match from terminal {}: {}", terminal_id, e);
+                        }
+                    }
                 },
                 Err(e) => {
                     eprintln!("Error reading from terminal {}: {}", terminal_id, e);
@@ -141,56 +446,135 @@ pub async fn start_process(state: State<'_, PtyState>, app: AppHandle) -> Result
         
         println!("Terminal {} reader thread exited", terminal_id);
         
-        // Удаляем терминал из списка при завершении работы
+        // Удаляем терминал из списка и из групп при завершении работы
         if let Some(state) = app_handle.try_state::<PtyState>() {
-            let mut terminals = state.terminals.blocking_lock();
+            let mut terminals = state.terminals.lock().await;
             terminals.remove(&terminal_id);
+            drop(terminals);
+            
+            let mut groups = state.groups.lock().await;
+            for group in groups.values_mut() {
+                group.members.retain(|id| *id != terminal_id);
+                group.excluded.remove(&terminal_id);
+            }
             println!("Terminal {} removed from state", terminal_id);
         }
     });
 
     // Поток для ожидания завершения процесса
-    spawn(async move {
+    spawn_blocking(move || {
         match child.wait() {
             Ok(status) => println!("Terminal {} process exited with status: {:?}", terminal_id, status),
             Err(e) => eprintln!("Error waiting for terminal {} process: {}", terminal_id, e),
         }
     });
+}
 
-    println!("Terminal {} process setup complete", terminal_id);
-    Ok(terminal_id)
+// Отправка захваченных команд в клиент и запись их в БД
+async fn record_captured_commands(app: &AppHandle, terminal_id: u32, commands: Vec<CapturedCommand>) {
+    let tab_id = match app.try_state::<PtyState>() {
+        Some(state) => state.terminals.lock().await.get(&terminal_id).and_then(|t| t.tab_id),
+        None => None,
+    };
+    
+    for captured in commands {
+        let mut record = captured_to_record(tab_id.unwrap_or_default(), &captured);
+        
+        // Команды без привязки к вкладке только отправляются клиенту
+        if let Some(tab_id) = tab_id {
+            if let Some(db) = app.try_state::<DbState>() {
+                let saved = record.clone();
+                match db.run(move |conn| insert_terminal_command(conn, &saved)).await {
+                    Ok(id) => record.id = Some(id),
+                    Err(e) => eprintln!("Error saving command from terminal {} (tab {}): {}", terminal_id, tab_id, e),
+                }
+            }
+        }
+        
+        println!("Terminal {} command finished: {:?} (exit code {:?})", terminal_id, record.command, record.exit_code);
+        if let Err(e) = app.emit("pty-command", (terminal_id, record)) {
+            eprintln!("Error emitting command from terminal {}: {}", terminal_id, e);
+        }
+    }
 }
 
+// Привязка терминала к вкладке, в историю которой записываются команды
 #[tauri::command]
-pub async fn send_input(state: State<'_, PtyState>, terminal_id: u32, input: String) -> Result<(), String> {
+pub async fn bind_terminal_tab(state: State<'_, PtyState>, terminal_id: u32, tab_id: Option<i64>) -> Result<(), String> {
     let mut terminals = state.terminals.lock().await;
     
     if let Some(terminal) = terminals.get_mut(&terminal_id) {
-        terminal.writer
-            .write_all(input.as_bytes())
-            .map_err(|e| format!("Failed to write to PTY: {}", e))?;
-        terminal.writer
-            .flush()
-            .map_err(|e| format!("Failed to flush PTY: {}", e))?;
+        terminal.tab_id = tab_id;
         Ok(())
     } else {
         Err(format!("Терминал с ID {} не найден", terminal_id))
     }
 }
 
+// Отправка ввода в терминал
+// paste - многострочная вставка (оборачивается в bracketed paste, если оболочка его поддерживает),
+// force - отправка без проверки опасных команд (после подтверждения пользователем)
+#[tauri::command]
+pub async fn send_input(
+    state: State<'_, PtyState>,
+    terminal_id: u32,
+    input: String,
+    paste: Option<bool>,
+    force: Option<bool>,
+) -> Result<InputResult, String> {
+    let mut terminals = state.terminals.lock().await;
+    
+    let terminal = terminals.get_mut(&terminal_id)
+        .ok_or_else(|| format!("Терминал с ID {} не найден", terminal_id))?;
+    
+    let input = if paste.unwrap_or(false)
+        && input.contains(['\r', '\n'])
+        && terminal.bracketed_paste.load(Ordering::SeqCst)
+    {
+        wrap_paste(&input)
+    } else {
+        input
+    };
+    
+    let guard = state.input_guard.lock().await;
+    terminal.write_guarded(&input, &guard, force.unwrap_or(false))
+}
+
+// Восстановление сохраненных настроек защиты ввода при запуске приложения и после импорта данных.
+// Без сохраненной настройки действуют настройки по умолчанию
+pub async fn load_input_guard_config(state: &PtyState, db: &DbState) -> Result<(), String> {
+    let config: Option<InputGuardConfig> = db.run(|conn| get_setting(conn, INPUT_GUARD_SETTING)).await?;
+    *state.input_guard.lock().await = InputGuard::new(config.unwrap_or_default())?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_input_guard_config(state: State<'_, PtyState>) -> Result<InputGuardConfig, String> {
+    Ok(state.input_guard.lock().await.config().clone())
+}
+
+#[tauri::command]
+pub async fn set_input_guard_config(state: State<'_, PtyState>, db: State<'_, DbState>, config: InputGuardConfig) -> Result<(), String> {
+    let guard = InputGuard::new(config)?;
+    let saved = guard.config().clone();
+    db.run(move |conn| set_setting(conn, INPUT_GUARD_SETTING, &saved)).await?;
+    println!("Input guard {} with {} pattern(s)", if guard.config().enabled { "enabled" } else { "disabled" }, guard.config().patterns.len());
+    *state.input_guard.lock().await = guard;
+    Ok(())
+}
+
 #[tauri::command]
 pub async fn change_directory(state: State<'_, PtyState>, terminal_id: u32, path: String) -> Result<(), String> {
     let mut terminals = state.terminals.lock().await;
     
     if let Some(terminal) = terminals.get_mut(&terminal_id) {
-        let command = format!("Set-Location {}\r\n", path.replace("/", "\\")); // PowerShell использует \
-        terminal.writer
-            .write_all(command.as_bytes())
-            .map_err(|e| format!("Failed to change directory: {}", e))?;
-        terminal.writer
-            .flush()
-            .map_err(|e| format!("Failed to flush PTY: {}", e))?;
-        Ok(())
+        let command = match terminal.shell {
+            ShellKind::PowerShell => format!("Set-Location {}\r\n", path.replace("/", "\\")), // PowerShell использует \
+            _ => format!("cd '{}'\r", path.replace('\'', "'\\''")),
+        };
+        terminal
+            .write_text(&command)
+            .map_err(|e| format!("Failed to change directory: {}", e))
     } else {
         Err(format!("Терминал с ID {} не найден", terminal_id))
     }
@@ -202,13 +586,9 @@ pub async fn clear_terminal(state: State<'_, PtyState>, terminal_id: u32) -> Res
     
     if let Some(terminal) = terminals.get_mut(&terminal_id) {
         // Очистка экрана в PowerShell (ANSI escape sequence)
-        terminal.writer
-            .write_all("\x1b[2J\x1b[1;1H".as_bytes()) // Очищает экран и перемещает курсор в начало
-            .map_err(|e| format!("Failed to clear terminal: {}", e))?;
-        terminal.writer
-            .flush()
-            .map_err(|e| format!("Failed to flush PTY: {}", e))?;
-        Ok(())
+        terminal.backend
+            .write_input("\x1b[2J\x1b[1;1H".as_bytes()) // Очищает экран и перемещает курсор в начало
+            .map_err(|e| format!("Failed to clear terminal: {}", e))
     } else {
         Err(format!("Терминал с ID {} не найден", terminal_id))
     }
@@ -222,9 +602,10 @@ pub async fn close_terminal_process(state: State<'_, PtyState>, terminal_id: u32
     let mut terminals = state.terminals.lock().await;
     
     if let Some(mut terminal) = terminals.remove(&terminal_id) {
-        // Отправка команды выхода в PowerShell
-        let _ = terminal.writer.write_all("exit\r\n".as_bytes());
-        let _ = terminal.writer.flush();
+        // Завершение сессии (для оболочки - команда выхода)
+        if let Err(e) = terminal.backend.shutdown() {
+            eprintln!("Error shutting down terminal {}: {}", terminal_id, e);
+        }
         
         println!("Процесс терминала {} успешно закрыт", terminal_id);
         Ok(())
@@ -239,4 +620,242 @@ pub async fn close_terminal_process(state: State<'_, PtyState>, terminal_id: u32
 pub async fn get_active_terminals(state: State<'_, PtyState>) -> Result<Vec<u32>, String> {
     let terminals = state.terminals.lock().await;
     Ok(terminals.keys().cloned().collect())
-} 
\ No newline at end of file
+}
+
+// Список активных терминалов с типом бэкенда
+#[tauri::command]
+pub async fn get_terminal_sessions(state: State<'_, PtyState>) -> Result<Vec<TerminalInfo>, String> {
+    let terminals = state.terminals.lock().await;
+    Ok(terminals.values().map(|t| TerminalInfo {
+        terminal_id: t.terminal_id,
+        kind: t.backend.kind(),
+        tab_id: t.tab_id,
+        session_id: t.backend.session_id(),
+    }).collect())
+}
+
+// Отправка одного и того же ввода в несколько терминалов
+// Ошибка в одном терминале не прерывает отправку в остальные, а опасная команда
+// проверяется по строке каждого терминала отдельно, как в send_input
+async fn broadcast_input(state: &PtyState, terminal_ids: &[u32], input: &str, force: bool) -> Vec<BroadcastResult> {
+    let mut terminals = state.terminals.lock().await;
+    let guard = state.input_guard.lock().await;
+    let mut results = Vec::with_capacity(terminal_ids.len());
+    let mut seen = HashSet::new();
+    
+    for &terminal_id in terminal_ids {
+        if !seen.insert(terminal_id) {
+            continue;
+        }
+        
+        let result = match terminals.get_mut(&terminal_id) {
+            Some(terminal) => terminal.write_guarded(input, &guard, force),
+            None => Err(format!("Терминал с ID {} не найден", terminal_id)),
+        };
+        
+        if let Err(e) = &result {
+            eprintln!("Broadcast to terminal {} failed: {}", terminal_id, e);
+        }
+        
+        results.push(BroadcastResult {
+            terminal_id,
+            ok: matches!(result, Ok(InputResult::Sent)),
+            error: result.as_ref().err().cloned(),
+            result: result.ok(),
+        });
+    }
+    
+    results
+}
+
+#[tauri::command]
+pub async fn send_input_many(
+    state: State<'_, PtyState>,
+    terminal_ids: Vec<u32>,
+    input: String,
+    force: Option<bool>,
+) -> Result<Vec<BroadcastResult>, String> {
+    Ok(broadcast_input(&state, &terminal_ids, &input, force.unwrap_or(false)).await)
+}
+
+// Отправка ввода всем участникам группы, кроме исключенных
+#[tauri::command]
+pub async fn send_input_group(
+    state: State<'_, PtyState>,
+    name: String,
+    input: String,
+    exclude: Option<Vec<u32>>,
+    force: Option<bool>,
+) -> Result<Vec<BroadcastResult>, String> {
+    let targets: Vec<u32> = {
+        let groups = state.groups.lock().await;
+        let group = groups.get(&name)
+            .ok_or_else(|| format!("Группа терминалов {} не найдена", name))?;
+        let exclude = exclude.unwrap_or_default();
+        group.members.iter()
+            .filter(|id| !group.excluded.contains(id) && !exclude.contains(id))
+            .cloned()
+            .collect()
+    };
+    
+    Ok(broadcast_input(&state, &targets, &input, force.unwrap_or(false)).await)
+}
+
+// Создание группы терминалов (существующая группа с тем же именем заменяется)
+#[tauri::command]
+pub async fn create_terminal_group(state: State<'_, PtyState>, name: String, terminal_ids: Vec<u32>) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("Имя группы не может быть пустым".to_string());
+    }
+    
+    let mut members = Vec::new();
+    for id in terminal_ids {
+        if !members.contains(&id) {
+            members.push(id);
+        }
+    }
+    
+    let mut groups = state.groups.lock().await;
+    groups.insert(name.clone(), TerminalGroup { members, excluded: HashSet::new() });
+    println!("Создана группа терминалов {}", name);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn delete_terminal_group(state: State<'_, PtyState>, name: String) -> Result<(), String> {
+    let mut groups = state.groups.lock().await;
+    groups.remove(&name)
+        .map(|_| ())
+        .ok_or_else(|| format!("Группа терминалов {} не найдена", name))
+}
+
+#[tauri::command]
+pub async fn add_terminal_to_group(state: State<'_, PtyState>, name: String, terminal_id: u32) -> Result<(), String> {
+    let mut groups = state.groups.lock().await;
+    let group = groups.entry(name).or_default();
+    if !group.members.contains(&terminal_id) {
+        group.members.push(terminal_id);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn remove_terminal_from_group(state: State<'_, PtyState>, name: String, terminal_id: u32) -> Result<(), String> {
+    let mut groups = state.groups.lock().await;
+    let group = groups.get_mut(&name)
+        .ok_or_else(|| format!("Группа терминалов {} не найдена", name))?;
+    group.members.retain(|id| *id != terminal_id);
+    group.excluded.remove(&terminal_id);
+    Ok(())
+}
+
+// Временное исключение участника группы из рассылки без удаления из группы
+#[tauri::command]
+pub async fn set_group_member_excluded(
+    state: State<'_, PtyState>,
+    name: String,
+    terminal_id: u32,
+    excluded: bool,
+) -> Result<(), String> {
+    let mut groups = state.groups.lock().await;
+    let group = groups.get_mut(&name)
+        .ok_or_else(|| format!("Группа терминалов {} не найдена", name))?;
+    
+    if !group.members.contains(&terminal_id) {
+        return Err(format!("Терминал {} не входит в группу {}", terminal_id, name));
+    }
+    
+    if excluded {
+        group.excluded.insert(terminal_id);
+    } else {
+        group.excluded.remove(&terminal_id);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_terminal_groups(state: State<'_, PtyState>) -> Result<Vec<TerminalGroupInfo>, String> {
+    let groups = state.groups.lock().await;
+    let mut result: Vec<TerminalGroupInfo> = groups.iter().map(|(name, group)| TerminalGroupInfo {
+        name: name.clone(),
+        members: group.members.clone(),
+        excluded: group.excluded.iter().cloned().collect(),
+    }).collect();
+    result.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(result)
+}
+
+// Регистрация наблюдателя за выводом терминала
+#[tauri::command]
+pub async fn add_output_watch(
+    state: State<'_, PtyState>,
+    terminal_id: u32,
+    pattern: String,
+    mode: Option<WatchMode>,
+    case_insensitive: Option<bool>,
+) -> Result<u32, String> {
+    let watch = OutputWatch::new(&pattern, mode.unwrap_or_default(), case_insensitive.unwrap_or(false))?;
+    let watch_id = watch.id;
+    
+    let terminals = state.terminals.lock().await;
+    let terminal = terminals.get(&terminal_id)
+        .ok_or_else(|| format!("Терминал с ID {} не найден", terminal_id))?;
+    terminal.watches.lock()
+        .map_err(|e| format!("Ошибка блокировки мьютекса: {}", e))?
+        .push(watch);
+    
+    println!("Terminal {}: added output watch {} for {:?}", terminal_id, watch_id, pattern);
+    Ok(watch_id)
+}
+
+#[tauri::command]
+pub async fn remove_output_watch(state: State<'_, PtyState>, terminal_id: u32, watch_id: u32) -> Result<(), String> {
+    let terminals = state.terminals.lock().await;
+    let terminal = terminals.get(&terminal_id)
+        .ok_or_else(|| format!("Терминал с ID {} не найден", terminal_id))?;
+    let mut watches = terminal.watches.lock()
+        .map_err(|e| format!("Ошибка блокировки мьютекса: {}", e))?;
+    
+    let before = watches.len();
+    watches.retain(|w| w.id != watch_id);
+    if watches.len() == before {
+        return Err(format!("Наблюдатель {} не найден", watch_id));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn list_output_watches(state: State<'_, PtyState>, terminal_id: u32) -> Result<Vec<WatchInfo>, String> {
+    let terminals = state.terminals.lock().await;
+    let terminal = terminals.get(&terminal_id)
+        .ok_or_else(|| format!("Терминал с ID {} не найден", terminal_id))?;
+    let watches = terminal.watches.lock()
+        .map_err(|e| format!("Ошибка блокировки мьютекса: {}", e))?;
+    Ok(watches.iter().map(WatchInfo::from).collect())
+}
+
+// Смена кодировки вывода и ввода терминала
+#[tauri::command]
+pub async fn set_terminal_encoding(state: State<'_, PtyState>, terminal_id: u32, encoding: String) -> Result<String, String> {
+    let terminals = state.terminals.lock().await;
+    let terminal = terminals.get(&terminal_id)
+        .ok_or_else(|| format!("Терминал с ID {} не найден", terminal_id))?;
+    let mut codec = terminal.codec.lock()
+        .map_err(|e| format!("Ошибка блокировки мьютекса: {}", e))?;
+    
+    codec.set_encoding(&encoding)?;
+    println!("Terminal {} encoding set to {}", terminal_id, codec.encoding_name());
+    Ok(codec.encoding_name().to_string())
+}
+
+// Переключение между текстовым выводом и сырыми байтами
+#[tauri::command]
+pub async fn set_terminal_output_mode(state: State<'_, PtyState>, terminal_id: u32, mode: OutputMode) -> Result<(), String> {
+    let terminals = state.terminals.lock().await;
+    let terminal = terminals.get(&terminal_id)
+        .ok_or_else(|| format!("Терминал с ID {} не найден", terminal_id))?;
+    terminal.codec.lock()
+        .map_err(|e| format!("Ошибка блокировки мьютекса: {}", e))?
+        .set_mode(mode);
+    Ok(())
+}
@@ -0,0 +1,638 @@
+// Модуль бэкендов терминала
+// Общий интерфейс для локального PTY, последовательного порта, SSH-сессии
+// и постоянной сессии демона (см. session_daemon):
+// все бэкенды принимают ввод через send_input, меняют размер через resize_pty
+// и отдают вывод в общую задачу чтения, которая отправляет событие pty-output
+
+use portable_pty::{native_pty_system, Child, ChildKiller, CommandBuilder, ExitStatus, MasterPty, PtySize, PtySystem};
+use serde::{Deserialize, Serialize};
+use serial2::{CharSize, FlowControl, Parity, SerialPort, Settings, StopBits};
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+// Интервал, с которым чтение из последовательного порта проверяет, не закрыта ли сессия
+const SERIAL_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// Максимальное ожидание записи в последовательный порт (например, при XOFF)
+const SERIAL_WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+// Тип бэкенда терминала
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    Local,
+    Serial,
+    Ssh,
+    Persistent,
+}
+
+// Общий контракт для всех бэкендов терминала
+pub trait TerminalBackend: Send {
+    fn kind(&self) -> BackendKind;
+
+    // Запись ввода пользователя
+    fn write_input(&mut self, data: &[u8]) -> Result<(), String>;
+
+    // Изменение размера терминала
+    fn resize(&mut self, rows: u16, cols: u16) -> Result<(), String>;
+
+    // PID локального процесса, обслуживающего сессию (оболочка или ssh-клиент)
+    fn process_id(&self) -> Option<u32> {
+        None
+    }
+
+    // ID сессии в демоне сессий (только для постоянных терминалов)
+    fn session_id(&self) -> Option<u32> {
+        None
+    }
+
+    // Завершение сессии
+    fn shutdown(&mut self) -> Result<(), String>;
+}
+
+// Потоки, которые бэкенд передает задаче чтения
+pub struct BackendIo {
+    pub reader: Box<dyn Read + Send>,
+    pub child: Box<dyn Child + Send + Sync>,
+}
+
+// Дескрипторы PTY, общие для всех бэкендов
+struct PtyHandles {
+    master: Box<dyn MasterPty + Send>,
+    writer: Box<dyn Write + Send>,
+    killer: Box<dyn ChildKiller + Send + Sync>,
+    process_id: Option<u32>,
+}
+
+impl PtyHandles {
+    fn open(pty_system: &dyn PtySystem, cmd: CommandBuilder) -> Result<(Self, BackendIo), String> {
+        let pair = pty_system
+            .openpty(PtySize {
+                rows: 24,
+                cols: 80,
+                pixel_width: 0,
+                pixel_height: 0,
+            })
+            .map_err(|e| e.to_string())?;
+
+        let child = pair.slave.spawn_command(cmd).map_err(|e| e.to_string())?;
+        let killer = child.clone_killer();
+        let process_id = child.process_id();
+
+        let master = pair.master;
+        let reader = master.try_clone_reader().map_err(|e| e.to_string())?;
+        let writer = master.take_writer().map_err(|e| e.to_string())?;
+
+        Ok((
+            PtyHandles {
+                master,
+                writer,
+                killer,
+                process_id,
+            },
+            BackendIo { reader, child },
+        ))
+    }
+
+    fn write_input(&mut self, data: &[u8]) -> Result<(), String> {
+        self.writer
+            .write_all(data)
+            .map_err(|e| format!("Failed to write to PTY: {}", e))?;
+        self.writer
+            .flush()
+            .map_err(|e| format!("Failed to flush PTY: {}", e))
+    }
+
+    fn resize(&mut self, rows: u16, cols: u16) -> Result<(), String> {
+        self.master
+            .resize(PtySize {
+                rows,
+                cols,
+                pixel_width: 0,
+                pixel_height: 0,
+            })
+            .map_err(|e| e.to_string())
+    }
+}
+
+// Локальный PTY с оболочкой
+pub struct LocalPtyBackend {
+    pty: PtyHandles,
+}
+
+impl LocalPtyBackend {
+    pub fn spawn(cmd: CommandBuilder) -> Result<(Self, BackendIo), String> {
+        let pty_system = native_pty_system();
+        let (pty, io) = PtyHandles::open(pty_system.as_ref(), cmd)?;
+        Ok((LocalPtyBackend { pty }, io))
+    }
+}
+
+impl TerminalBackend for LocalPtyBackend {
+    fn kind(&self) -> BackendKind {
+        BackendKind::Local
+    }
+
+    fn write_input(&mut self, data: &[u8]) -> Result<(), String> {
+        self.pty.write_input(data)
+    }
+
+    fn resize(&mut self, rows: u16, cols: u16) -> Result<(), String> {
+        self.pty.resize(rows, cols)
+    }
+
+    fn process_id(&self) -> Option<u32> {
+        self.pty.process_id
+    }
+
+    fn shutdown(&mut self) -> Result<(), String> {
+        // Отправка команды выхода в оболочку
+        self.pty.write_input("exit\r\n".as_bytes())
+    }
+}
+
+// Четность последовательного порта
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SerialParity {
+    #[default]
+    None,
+    Odd,
+    Even,
+}
+
+// Управление потоком последовательного порта
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SerialFlowControl {
+    #[default]
+    None,
+    XonXoff,
+    RtsCts,
+}
+
+fn default_baud_rate() -> u32 {
+    115200
+}
+
+fn default_data_bits() -> u8 {
+    8
+}
+
+fn default_stop_bits() -> u8 {
+    1
+}
+
+// Параметры подключения к последовательному порту
+#[derive(Debug, Clone, Deserialize)]
+pub struct SerialConfig {
+    // Имя порта (COM3, /dev/ttyUSB0 или ведомая сторона псевдотерминала)
+    pub port: String,
+    #[serde(default = "default_baud_rate")]
+    pub baud_rate: u32,
+    #[serde(default)]
+    pub parity: SerialParity,
+    #[serde(default)]
+    pub flow_control: SerialFlowControl,
+    #[serde(default = "default_data_bits")]
+    pub data_bits: u8,
+    #[serde(default = "default_stop_bits")]
+    pub stop_bits: u8,
+}
+
+// Открытый последовательный порт и признак закрытия сессии
+// Порт закрывается, когда освобождаются все ссылки на него: бэкенд, поток чтения и ожидание сессии
+#[derive(Debug)]
+struct SerialLink {
+    port: SerialPort,
+    closed: Mutex<bool>,
+    closed_signal: Condvar,
+}
+
+impl SerialLink {
+    fn close(&self) {
+        *self.closed.lock().unwrap_or_else(|e| e.into_inner()) = true;
+        self.closed_signal.notify_all();
+    }
+
+    fn is_closed(&self) -> bool {
+        *self.closed.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+// Чтение из порта с периодической проверкой закрытия сессии
+// После закрытия возвращает EOF, и задача чтения терминала завершается сама
+struct SerialReader {
+    link: Arc<SerialLink>,
+}
+
+impl Read for SerialReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.link.is_closed() {
+                return Ok(0);
+            }
+            match self.link.port.read(buf) {
+                Err(e) if matches!(e.kind(), io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted) => continue,
+                result => return result,
+            }
+        }
+    }
+}
+
+impl Drop for SerialReader {
+    fn drop(&mut self) {
+        // Ошибка чтения (например, отключение USB-адаптера) тоже завершает сессию
+        self.link.close();
+    }
+}
+
+// У последовательного порта нет процесса: ожидание завершается вместе с сессией
+#[derive(Debug)]
+struct SerialSession {
+    link: Arc<SerialLink>,
+}
+
+impl Child for SerialSession {
+    fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        Ok(self.link.is_closed().then(|| ExitStatus::with_exit_code(0)))
+    }
+
+    fn wait(&mut self) -> io::Result<ExitStatus> {
+        let mut closed = self.link.closed.lock().unwrap_or_else(|e| e.into_inner());
+        while !*closed {
+            closed = self.link.closed_signal.wait(closed).unwrap_or_else(|e| e.into_inner());
+        }
+        Ok(ExitStatus::with_exit_code(0))
+    }
+
+    fn process_id(&self) -> Option<u32> {
+        None
+    }
+
+    #[cfg(windows)]
+    fn as_raw_handle(&self) -> Option<std::os::windows::io::RawHandle> {
+        None
+    }
+}
+
+impl ChildKiller for SerialSession {
+    fn kill(&mut self) -> io::Result<()> {
+        self.link.close();
+        Ok(())
+    }
+
+    fn clone_killer(&self) -> Box<dyn ChildKiller + Send + Sync> {
+        Box::new(SerialSession {
+            link: self.link.clone(),
+        })
+    }
+}
+
+// Сессия последовательной консоли
+pub struct SerialBackend {
+    link: Arc<SerialLink>,
+}
+
+impl SerialBackend {
+    pub fn open(config: &SerialConfig) -> Result<(Self, BackendIo), String> {
+        let parity = match config.parity {
+            SerialParity::None => Parity::None,
+            SerialParity::Odd => Parity::Odd,
+            SerialParity::Even => Parity::Even,
+        };
+        let flow_control = match config.flow_control {
+            SerialFlowControl::None => FlowControl::None,
+            SerialFlowControl::XonXoff => FlowControl::XonXoff,
+            SerialFlowControl::RtsCts => FlowControl::RtsCts,
+        };
+        let char_size = match config.data_bits {
+            5 => CharSize::Bits5,
+            6 => CharSize::Bits6,
+            7 => CharSize::Bits7,
+            8 => CharSize::Bits8,
+            other => return Err(format!("Неподдерживаемое число бит данных: {}", other)),
+        };
+        let stop_bits = match config.stop_bits {
+            1 => StopBits::One,
+            2 => StopBits::Two,
+            other => return Err(format!("Неподдерживаемое число стоп-битов: {}", other)),
+        };
+
+        let mut port = SerialPort::open(&config.port, |mut settings: Settings| {
+            settings.set_raw();
+            settings.set_baud_rate(config.baud_rate)?;
+            settings.set_parity(parity);
+            settings.set_flow_control(flow_control);
+            settings.set_char_size(char_size);
+            settings.set_stop_bits(stop_bits);
+            Ok(settings)
+        })
+        .map_err(|e| format!("Не удалось открыть порт {}: {}", config.port, e))?;
+        port.set_read_timeout(SERIAL_POLL_INTERVAL)
+            .and_then(|_| port.set_write_timeout(SERIAL_WRITE_TIMEOUT))
+            .map_err(|e| format!("Не удалось настроить порт {}: {}", config.port, e))?;
+
+        let link = Arc::new(SerialLink {
+            port,
+            closed: Mutex::new(false),
+            closed_signal: Condvar::new(),
+        });
+        let io = BackendIo {
+            reader: Box::new(SerialReader { link: link.clone() }),
+            child: Box::new(SerialSession { link: link.clone() }),
+        };
+        Ok((SerialBackend { link }, io))
+    }
+}
+
+impl TerminalBackend for SerialBackend {
+    fn kind(&self) -> BackendKind {
+        BackendKind::Serial
+    }
+
+    fn write_input(&mut self, data: &[u8]) -> Result<(), String> {
+        if self.link.is_closed() {
+            return Err("Последовательный порт закрыт".to_string());
+        }
+        self.link.port
+            .write_all(data)
+            .and_then(|_| self.link.port.flush())
+            .map_err(|e| format!("Не удалось записать в последовательный порт: {}", e))
+    }
+
+    fn resize(&mut self, _rows: u16, _cols: u16) -> Result<(), String> {
+        // У последовательного порта нет понятия размера
+        Ok(())
+    }
+
+    fn shutdown(&mut self) -> Result<(), String> {
+        // Поток чтения замечает закрытие за SERIAL_POLL_INTERVAL и отпускает порт
+        self.link.close();
+        Ok(())
+    }
+}
+
+// Параметры SSH-подключения
+#[derive(Debug, Clone, Deserialize)]
+pub struct SshConfig {
+    pub host: String,
+    #[serde(default)]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub user: Option<String>,
+    #[serde(default)]
+    pub identity_file: Option<String>,
+    // Дополнительные опции в формате ssh -o (например, StrictHostKeyChecking=no)
+    #[serde(default)]
+    pub options: Vec<String>,
+    // Путь к ssh-клиенту, если он не в PATH
+    #[serde(default)]
+    pub program: Option<String>,
+}
+
+// SSH-сессия через системный ssh-клиент, запущенный в локальном PTY
+pub struct SshBackend {
+    pty: PtyHandles,
+}
+
+impl SshBackend {
+    pub fn connect(config: &SshConfig) -> Result<(Self, BackendIo), String> {
+        let host = config.host.trim();
+        if host.is_empty() || host.starts_with('-') {
+            return Err(format!("Некорректный адрес хоста: {}", config.host));
+        }
+
+        let mut cmd = CommandBuilder::new(config.program.as_deref().unwrap_or("ssh"));
+        cmd.arg("-tt");
+        if let Some(port) = config.port {
+            cmd.arg("-p");
+            cmd.arg(port.to_string());
+        }
+        if let Some(identity_file) = &config.identity_file {
+            cmd.arg("-i");
+            cmd.arg(identity_file);
+        }
+        for option in &config.options {
+            cmd.arg("-o");
+            cmd.arg(option);
+        }
+        match &config.user {
+            Some(user) if !user.is_empty() => cmd.arg(format!("{}@{}", user, host)),
+            _ => cmd.arg(host),
+        }
+
+        if !cfg!(target_os = "windows") {
+            cmd.env("TERM", "xterm-256color");
+        }
+
+        let pty_system = native_pty_system();
+        let (pty, io) = PtyHandles::open(pty_system.as_ref(), cmd)
+            .map_err(|e| format!("Не удалось запустить ssh-клиент: {}", e))?;
+        Ok((SshBackend { pty }, io))
+    }
+}
+
+impl TerminalBackend for SshBackend {
+    fn kind(&self) -> BackendKind {
+        BackendKind::Ssh
+    }
+
+    fn write_input(&mut self, data: &[u8]) -> Result<(), String> {
+        self.pty.write_input(data)
+    }
+
+    fn resize(&mut self, rows: u16, cols: u16) -> Result<(), String> {
+        // ssh-клиент сам передает новый размер на удаленную сторону
+        self.pty.resize(rows, cols)
+    }
+
+    fn process_id(&self) -> Option<u32> {
+        self.pty.process_id
+    }
+
+    fn shutdown(&mut self) -> Result<(), String> {
+        self.pty.killer.kill().map_err(|e| e.to_string())
+    }
+}
+
+// Список доступных последовательных портов
+pub fn available_serial_ports() -> Result<Vec<String>, String> {
+    serial2::SerialPort::available_ports()
+        .map(|ports| {
+            ports
+                .into_iter()
+                .map(|p| p.to_string_lossy().to_string())
+                .collect()
+        })
+        .map_err(|e| format!("Не удалось получить список портов: {}", e))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::time::Instant;
+
+    const TEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+    // Чтение в отдельном потоке, чтобы тест не зависал на блокирующем read
+    fn spawn_reader(mut reader: Box<dyn Read + Send>) -> mpsc::Receiver<Option<Vec<u8>>> {
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut buffer = [0u8; 1024];
+            while let Ok(n) = reader.read(&mut buffer) {
+                if n == 0 || tx.send(Some(buffer[..n].to_vec())).is_err() {
+                    break;
+                }
+            }
+            // Читатель освобождается до сигнала EOF, чтобы тест видел закрытый порт
+            drop(reader);
+            let _ = tx.send(None);
+        });
+        rx
+    }
+
+    fn read_until(rx: &mpsc::Receiver<Option<Vec<u8>>>, needle: &str) -> String {
+        let deadline = Instant::now() + TEST_TIMEOUT;
+        let mut output = String::new();
+        while !output.contains(needle) {
+            let left = deadline.saturating_duration_since(Instant::now());
+            match rx.recv_timeout(left) {
+                Ok(Some(chunk)) => output.push_str(&String::from_utf8_lossy(&chunk)),
+                _ => panic!("Не дождались {:?}, получено: {:?}", needle, output),
+            }
+        }
+        output
+    }
+
+    fn wait_eof(rx: &mpsc::Receiver<Option<Vec<u8>>>) {
+        let deadline = Instant::now() + TEST_TIMEOUT;
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            match rx.recv_timeout(left) {
+                Ok(Some(_)) => continue,
+                Ok(None) => return,
+                Err(_) => panic!("Поток чтения не завершился после shutdown"),
+            }
+        }
+    }
+
+    fn wait_child(mut child: Box<dyn Child + Send + Sync>) {
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let finished = child.wait().is_ok();
+            drop(child);
+            let _ = tx.send(finished);
+        });
+        assert_eq!(rx.recv_timeout(TEST_TIMEOUT), Ok(true), "Ожидание сессии не завершилось");
+    }
+
+    // Открытые дескрипторы процесса, указывающие на заданный путь
+    fn open_descriptors(path: &std::path::Path) -> usize {
+        std::fs::read_dir("/proc/self/fd")
+            .map(|entries| {
+                entries
+                    .filter_map(|e| e.ok())
+                    .filter(|e| std::fs::read_link(e.path()).map(|p| p == path).unwrap_or(false))
+                    .count()
+            })
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn serial_backend_exchanges_data_and_releases_port_on_shutdown() {
+        // Псевдотерминал вместо реального порта: ведомая сторона играет роль /dev/ttyUSB0
+        let pair = native_pty_system().openpty(PtySize::default()).expect("openpty");
+        let port = pair.master.tty_name().expect("tty name");
+        let device = spawn_reader(pair.master.try_clone_reader().expect("reader"));
+        let mut device_writer = pair.master.take_writer().expect("writer");
+
+        let config = SerialConfig {
+            port: port.to_string_lossy().to_string(),
+            baud_rate: 9600,
+            parity: SerialParity::None,
+            flow_control: SerialFlowControl::None,
+            data_bits: 8,
+            stop_bits: 1,
+        };
+        let (mut backend, io) = SerialBackend::open(&config).expect("open serial");
+        assert_eq!(backend.kind(), BackendKind::Serial);
+        drop(pair.slave);
+        let terminal = spawn_reader(io.reader);
+
+        backend.write_input(b"AT\r").expect("write");
+        read_until(&device, "AT");
+
+        device_writer.write_all(b"OK\r\n").expect("device write");
+        read_until(&terminal, "OK");
+
+        backend.shutdown().expect("shutdown");
+        wait_eof(&terminal);
+        wait_child(io.child);
+        assert!(backend.write_input(b"x").is_err());
+
+        drop(backend);
+        assert_eq!(open_descriptors(&port), 0, "Порт остался открытым после shutdown");
+    }
+
+    #[test]
+    fn serial_backend_rejects_unsupported_settings() {
+        let config = SerialConfig {
+            port: "/dev/null".to_string(),
+            baud_rate: 9600,
+            parity: SerialParity::None,
+            flow_control: SerialFlowControl::None,
+            data_bits: 9,
+            stop_bits: 1,
+        };
+        assert!(SerialBackend::open(&config).is_err());
+    }
+
+    #[test]
+    fn ssh_backend_passes_options_and_terminates_client() {
+        // Подставной ssh-клиент вместо sshd: печатает аргументы и повторяет ввод
+        let dir = std::env::temp_dir().join(format!("x-avto-ssh-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("temp dir");
+        let program = dir.join("ssh");
+        std::fs::write(&program, "#!/bin/sh\necho \"ARGS:$*\"\nexec cat\n").expect("fake ssh");
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&program, std::fs::Permissions::from_mode(0o755)).expect("chmod");
+        }
+
+        let config = SshConfig {
+            host: "example.org".to_string(),
+            port: Some(2222),
+            user: Some("deploy".to_string()),
+            identity_file: Some("/keys/id_ed25519".to_string()),
+            options: vec!["StrictHostKeyChecking=no".to_string()],
+            program: Some(program.to_string_lossy().to_string()),
+        };
+        let (mut backend, io) = SshBackend::connect(&config).expect("connect");
+        assert!(backend.process_id().is_some());
+        let output = spawn_reader(io.reader);
+
+        read_until(&output, "ARGS:-tt -p 2222 -i /keys/id_ed25519 -o StrictHostKeyChecking=no deploy@example.org");
+        backend.write_input(b"ping\r").expect("write");
+        read_until(&output, "ping");
+
+        backend.shutdown().expect("shutdown");
+        wait_child(io.child);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn ssh_backend_rejects_option_like_host() {
+        let config = SshConfig {
+            host: "-oProxyCommand=touch /tmp/pwned".to_string(),
+            port: None,
+            user: None,
+            identity_file: None,
+            options: Vec::new(),
+            program: Some("/bin/false".to_string()),
+        };
+        assert!(SshBackend::connect(&config).is_err());
+    }
+}
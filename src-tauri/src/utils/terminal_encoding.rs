@@ -0,0 +1,102 @@
+// Модуль кодировок терминала
+// Потоковый декодер сохраняет незавершенные многобайтовые последовательности между чтениями,
+// поэтому символы на границе буфера не портятся; поддерживаются кодировки encoding_rs (UTF-8, CP866, Windows-1251 и др.)
+
+use encoding_rs::{CoderResult, Decoder, Encoding, UTF_8};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+// Режим отправки вывода клиенту
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputMode {
+    // Декодированный текст в событии pty-output
+    #[default]
+    Text,
+    // Исходные байты в событии pty-output-raw, декодирование на стороне xterm.js
+    Raw,
+}
+
+// Кодек терминала: декодирование вывода и кодирование ввода
+pub struct TerminalCodec {
+    encoding: &'static Encoding,
+    decoder: Decoder,
+    mode: OutputMode,
+}
+
+// Кодек, общий для команд и задачи чтения
+pub type SharedCodec = Arc<Mutex<TerminalCodec>>;
+
+// Поиск кодировки по имени (utf-8, cp866, windows-1251, koi8-r и т.д.)
+fn resolve_encoding(label: &str) -> Result<&'static Encoding, String> {
+    Encoding::for_label(label.trim().as_bytes())
+        .ok_or_else(|| format!("Неизвестная кодировка: {}", label))
+}
+
+impl TerminalCodec {
+    pub fn new(label: Option<&str>) -> Result<Self, String> {
+        let encoding = match label {
+            Some(label) => resolve_encoding(label)?,
+            None => UTF_8,
+        };
+
+        Ok(TerminalCodec {
+            encoding,
+            decoder: encoding.new_decoder_without_bom_handling(),
+            mode: OutputMode::Text,
+        })
+    }
+
+    pub fn shared(self) -> SharedCodec {
+        Arc::new(Mutex::new(self))
+    }
+
+    pub fn encoding_name(&self) -> &'static str {
+        self.encoding.name()
+    }
+
+    // Смена кодировки (незавершенная последовательность старой кодировки отбрасывается)
+    pub fn set_encoding(&mut self, label: &str) -> Result<(), String> {
+        self.encoding = resolve_encoding(label)?;
+        self.decoder = self.encoding.new_decoder_without_bom_handling();
+        Ok(())
+    }
+
+    pub fn mode(&self) -> OutputMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: OutputMode) {
+        self.mode = mode;
+    }
+
+    // Декодирование очередного фрагмента вывода
+    pub fn decode(&mut self, bytes: &[u8]) -> String {
+        let capacity = self
+            .decoder
+            .max_utf8_buffer_length(bytes.len())
+            .unwrap_or(bytes.len() * 3);
+        let mut output = String::with_capacity(capacity);
+        let mut input = bytes;
+
+        loop {
+            let (result, read, _had_errors) = self.decoder.decode_to_string(input, &mut output, false);
+            input = &input[read..];
+            match result {
+                CoderResult::InputEmpty => break,
+                CoderResult::OutputFull => output.reserve(input.len() * 3 + 16),
+            }
+        }
+
+        output
+    }
+
+    // Кодирование ввода пользователя в кодировку терминала
+    pub fn encode(&self, text: &str) -> Vec<u8> {
+        if self.encoding == UTF_8 {
+            return text.as_bytes().to_vec();
+        }
+        let (bytes, _, _) = self.encoding.encode(text);
+        bytes.into_owned()
+    }
+}
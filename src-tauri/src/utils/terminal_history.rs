@@ -0,0 +1,497 @@
+// Модуль обслуживания истории команд терминала
+// Политика хранения ограничивает возраст записей, число записей на вкладку и размер
+// сохраненного вывода (длинный вывод обрезается, остается его конец). Политика хранится
+// в настройках приложения и применяется при запуске, после изменения и затем раз в час.
+// Аналитика считается запросами SQL по terminal_commands.
+// Общая история всех вкладок (поиск в стиле Ctrl+R) ищет команды нечетким совпадением
+// и ранжирует их с учетом частоты и давности использования (frecency)
+
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tauri::{AppHandle, Manager, State};
+
+use crate::utils::db::{get_setting, set_setting, to_utc_timestamp, utc_timestamp, DbState};
+
+// Ключ политики хранения в app_settings
+const RETENTION_SETTING: &str = "history_retention";
+
+// Интервал фонового применения политики
+const RETENTION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+// Пометка в начале обрезанного вывода
+const TRUNCATED_MARKER: &str = "[... вывод обрезан ...]\n";
+
+// Политика хранения истории; None - без ограничения
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    #[serde(default)]
+    pub max_age_days: Option<u32>,
+    #[serde(default)]
+    pub max_rows_per_tab: Option<u32>,
+    #[serde(default)]
+    pub max_output_bytes: Option<u32>,
+}
+
+impl RetentionPolicy {
+    fn validate(&self) -> Result<(), String> {
+        if self.max_age_days == Some(0) {
+            return Err("Срок хранения должен быть не меньше одного дня".to_string());
+        }
+        if self.max_rows_per_tab == Some(0) {
+            return Err("Число команд на вкладку должно быть больше нуля".to_string());
+        }
+        if let Some(max) = self.max_output_bytes {
+            if (max as usize) <= TRUNCATED_MARKER.len() {
+                return Err(format!("Размер вывода должен быть больше {} байт", TRUNCATED_MARKER.len()));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Результат применения политики
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RetentionReport {
+    pub deleted_by_age: usize,
+    pub deleted_by_count: usize,
+    pub truncated_outputs: usize,
+}
+
+// Конец вывода не длиннее max_bytes байт (с пометкой об обрезке), по границе символа UTF-8
+fn truncate_output(output: &str, max_bytes: usize) -> String {
+    let keep = max_bytes.saturating_sub(TRUNCATED_MARKER.len());
+    let mut start = output.len() - keep.min(output.len());
+    while !output.is_char_boundary(start) {
+        start += 1;
+    }
+    format!("{}{}", TRUNCATED_MARKER, &output[start..])
+}
+
+fn apply_retention(conn: &mut Connection, policy: &RetentionPolicy) -> Result<RetentionReport, String> {
+    let mut report = RetentionReport::default();
+    let tx = conn.transaction()
+        .map_err(|e| format!("Ошибка создания транзакции: {}", e))?;
+
+    if let Some(days) = policy.max_age_days {
+        let cutoff = utc_timestamp(chrono::Utc::now() - chrono::Duration::days(days as i64));
+        report.deleted_by_age = tx.execute("DELETE FROM terminal_commands WHERE recorded_at < ?", params![cutoff])
+            .map_err(|e| format!("Ошибка удаления старых команд: {}", e))?;
+    }
+
+    if let Some(max_rows) = policy.max_rows_per_tab {
+        report.deleted_by_count = tx.execute(
+            "DELETE FROM terminal_commands WHERE id IN (
+                SELECT id FROM (
+                    SELECT id, ROW_NUMBER() OVER (PARTITION BY terminal_tab_id ORDER BY id DESC) AS position
+                    FROM terminal_commands
+                ) WHERE position > ?
+            )",
+            params![max_rows],
+        ).map_err(|e| format!("Ошибка удаления лишних команд: {}", e))?;
+    }
+
+    if let Some(max_bytes) = policy.max_output_bytes {
+        let oversized: Vec<(i64, String)> = {
+            let mut stmt = tx.prepare("SELECT id, output FROM terminal_commands WHERE length(CAST(output AS BLOB)) > ?")
+                .map_err(|e| format!("Ошибка подготовки запроса: {}", e))?;
+            let rows = stmt.query_map(params![max_bytes], |row| Ok((row.get(0)?, row.get(1)?)))
+                .map_err(|e| format!("Ошибка выполнения запроса: {}", e))?;
+            rows.collect::<Result<Vec<_>, _>>()
+                .map_err(|e| format!("Ошибка чтения строки: {}", e))?
+        };
+
+        let mut stmt = tx.prepare("UPDATE terminal_commands SET output = ? WHERE id = ?")
+            .map_err(|e| format!("Ошибка подготовки запроса: {}", e))?;
+        for (id, output) in &oversized {
+            stmt.execute(params![truncate_output(output, max_bytes as usize), id])
+                .map_err(|e| format!("Ошибка обрезки вывода команды {}: {}", id, e))?;
+        }
+        report.truncated_outputs = oversized.len();
+    }
+
+    tx.commit()
+        .map_err(|e| format!("Ошибка фиксации транзакции: {}", e))?;
+
+    Ok(report)
+}
+
+fn apply_saved_retention(conn: &mut Connection) -> Result<RetentionReport, String> {
+    let policy: RetentionPolicy = get_setting(conn, RETENTION_SETTING)?.unwrap_or_default();
+    apply_retention(conn, &policy)
+}
+
+#[tauri::command]
+pub async fn get_history_retention(db: State<'_, DbState>) -> Result<RetentionPolicy, String> {
+    db.run(|conn| Ok(get_setting(conn, RETENTION_SETTING)?.unwrap_or_default())).await
+}
+
+// Сохранение политики хранения и ее немедленное применение
+#[tauri::command]
+pub async fn set_history_retention(db: State<'_, DbState>, policy: RetentionPolicy) -> Result<RetentionReport, String> {
+    policy.validate()?;
+
+    let report = db.run(move |conn| {
+        set_setting(conn, RETENTION_SETTING, &policy)?;
+        apply_retention(conn, &policy)
+    }).await?;
+
+    println!("Политика хранения истории обновлена: {:?}", report);
+    Ok(report)
+}
+
+#[tauri::command]
+pub async fn apply_history_retention(db: State<'_, DbState>) -> Result<RetentionReport, String> {
+    db.run(apply_saved_retention).await
+}
+
+// Запуск фоновой задачи применения политики хранения
+pub fn start_history_retention_task(app_handle: AppHandle) {
+    println!("[History] Запуск фоновой задачи политики хранения истории");
+
+    tauri::async_runtime::spawn(async move {
+        loop {
+            if let Some(db) = app_handle.try_state::<DbState>() {
+                match db.run(apply_saved_retention).await {
+                    Ok(report) => {
+                        if report.deleted_by_age + report.deleted_by_count + report.truncated_outputs > 0 {
+                            println!("[History] Применена политика хранения: {:?}", report);
+                        }
+                    }
+                    Err(e) => eprintln!("[History] Ошибка применения политики хранения: {}", e),
+                }
+            }
+
+            tokio::time::sleep(RETENTION_INTERVAL).await;
+        }
+    });
+}
+
+// Фильтр аналитики
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AnalyticsFilter {
+    #[serde(default)]
+    pub tab_id: Option<i64>,
+    // Только команды, записанные после указанного времени (RFC 3339)
+    #[serde(default)]
+    pub since: Option<String>,
+    #[serde(default = "default_analytics_limit")]
+    pub limit: u32,
+}
+
+fn default_analytics_limit() -> u32 {
+    20
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandUsage {
+    pub command: String,
+    pub count: i64,
+    pub failures: i64,
+    pub last_used: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExitCodeStats {
+    // None - код возврата неизвестен (команда сохранена без интеграции с оболочкой)
+    pub exit_code: Option<i32>,
+    pub count: i64,
+    // Доля от всех команд, 0..1
+    pub rate: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PrefixStats {
+    // Первое слово команды (git, npm, cargo...)
+    pub prefix: String,
+    pub count: i64,
+    pub failure_rate: f64,
+    // Средняя длительность по командам с известным временем начала и завершения
+    pub avg_duration_secs: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandAnalytics {
+    pub total: i64,
+    pub failures: i64,
+    pub top_commands: Vec<CommandUsage>,
+    pub exit_codes: Vec<ExitCodeStats>,
+    pub prefixes: Vec<PrefixStats>,
+}
+
+// Отфильтрованная история как CTE; параметры ?1 - вкладка, ?2 - время начала выборки
+const FILTERED_COMMANDS: &str =
+    "WITH filtered AS (
+        SELECT trim(command) AS command, exit_code, started_at, finished_at, recorded_at,
+               CASE WHEN instr(trim(command), ' ') > 0
+                    THEN substr(trim(command), 1, instr(trim(command), ' ') - 1)
+                    ELSE trim(command) END AS prefix,
+               (exit_code IS NOT NULL AND exit_code != 0) AS failed
+        FROM terminal_commands
+        WHERE trim(command) != ''
+          AND (?1 IS NULL OR terminal_tab_id = ?1)
+          AND (?2 IS NULL OR recorded_at >= ?2)
+    )";
+
+fn command_analytics(conn: &Connection, filter: &AnalyticsFilter) -> Result<CommandAnalytics, String> {
+    let since = filter.since.as_deref().map(to_utc_timestamp).transpose()?;
+    let limit = filter.limit.max(1);
+    let query_error = |e: rusqlite::Error| format!("Ошибка выполнения запроса: {}", e);
+
+    let (total, failures): (i64, i64) = conn.query_row(
+        &format!("{} SELECT COUNT(*), COALESCE(SUM(failed), 0) FROM filtered", FILTERED_COMMANDS),
+        params![filter.tab_id, since],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ).map_err(query_error)?;
+
+    let top_commands = conn.prepare(&format!(
+        "{} SELECT command, COUNT(*) AS uses, SUM(failed), MAX(recorded_at) FROM filtered
+         GROUP BY command ORDER BY uses DESC, MAX(recorded_at) DESC LIMIT ?3",
+        FILTERED_COMMANDS
+    ))
+    .and_then(|mut stmt| {
+        stmt.query_map(params![filter.tab_id, since, limit], |row| {
+            Ok(CommandUsage {
+                command: row.get(0)?,
+                count: row.get(1)?,
+                failures: row.get(2)?,
+                last_used: row.get(3)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()
+    })
+    .map_err(query_error)?;
+
+    let exit_codes = conn.prepare(&format!(
+        "{} SELECT exit_code, COUNT(*) AS uses FROM filtered GROUP BY exit_code ORDER BY uses DESC",
+        FILTERED_COMMANDS
+    ))
+    .and_then(|mut stmt| {
+        stmt.query_map(params![filter.tab_id, since], |row| {
+            let count: i64 = row.get(1)?;
+            Ok(ExitCodeStats {
+                exit_code: row.get(0)?,
+                count,
+                rate: count as f64 / total.max(1) as f64,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()
+    })
+    .map_err(query_error)?;
+
+    let prefixes = conn.prepare(&format!(
+        "{} SELECT prefix, COUNT(*) AS uses, AVG(failed),
+                AVG((julianday(finished_at) - julianday(started_at)) * 86400.0)
+         FROM filtered GROUP BY prefix ORDER BY uses DESC LIMIT ?3",
+        FILTERED_COMMANDS
+    ))
+    .and_then(|mut stmt| {
+        stmt.query_map(params![filter.tab_id, since, limit], |row| {
+            Ok(PrefixStats {
+                prefix: row.get(0)?,
+                count: row.get(1)?,
+                failure_rate: row.get(2)?,
+                avg_duration_secs: row.get(3)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()
+    })
+    .map_err(query_error)?;
+
+    Ok(CommandAnalytics { total, failures, top_commands, exit_codes, prefixes })
+}
+
+#[tauri::command]
+pub async fn get_command_analytics(db: State<'_, DbState>, filter: Option<AnalyticsFilter>) -> Result<CommandAnalytics, String> {
+    let filter = filter.unwrap_or_else(|| AnalyticsFilter { limit: default_analytics_limit(), ..Default::default() });
+    db.run(move |conn| command_analytics(conn, &filter)).await
+}
+
+// Сколько последних различных команд рассматривается при нечетком поиске
+const HISTORY_CANDIDATES: u32 = 10_000;
+
+// Фильтр по результату выполнения
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryStatus {
+    Success,
+    Failure,
+}
+
+// Запрос к общей истории команд
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct HistoryQuery {
+    // Пустой запрос возвращает команды по убыванию frecency
+    #[serde(default)]
+    pub query: String,
+    // Рабочая директория или любая из ее поддиректорий
+    #[serde(default)]
+    pub cwd: Option<String>,
+    #[serde(default)]
+    pub status: Option<HistoryStatus>,
+    #[serde(default)]
+    pub tab_id: Option<i64>,
+    // Границы времени записи команды (RFC 3339)
+    #[serde(default)]
+    pub since: Option<String>,
+    #[serde(default)]
+    pub until: Option<String>,
+    #[serde(default = "default_history_limit")]
+    pub limit: u32,
+}
+
+fn default_history_limit() -> u32 {
+    50
+}
+
+// Команда из общей истории (повторы команды объединяются)
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryEntry {
+    pub command: String,
+    pub score: f64,
+    pub frecency: f64,
+    pub count: i64,
+    // Последний запуск: время, директория, код возврата и вкладка
+    pub last_used: Option<String>,
+    pub cwd: Option<String>,
+    pub exit_code: Option<i32>,
+    pub tab_id: i64,
+    // Позиции совпавших символов команды для подсветки
+    pub positions: Vec<usize>,
+}
+
+// Нечеткое совпадение: символы запроса встречаются в команде по порядку
+// Бонусы за совпадение подряд, в начале слова и в начале команды, штраф за пропуски.
+// Регистр учитывается, только если в запросе есть заглавные буквы
+fn fuzzy_match(query: &str, candidate: &str) -> Option<(f64, Vec<usize>)> {
+    let case_sensitive = query.chars().any(|c| c.is_uppercase());
+    let normalize = |c: char| if case_sensitive { c } else { c.to_lowercase().next().unwrap_or(c) };
+
+    let pattern: Vec<char> = query.chars().filter(|c| !c.is_whitespace()).map(normalize).collect();
+    if pattern.is_empty() {
+        return Some((0.0, Vec::new()));
+    }
+    let text: Vec<char> = candidate.chars().collect();
+    let lowered: Vec<char> = text.iter().map(|&c| normalize(c)).collect();
+
+    let is_boundary = |index: usize| {
+        index == 0 || matches!(text[index - 1], ' ' | '/' | '\\' | '-' | '_' | '.' | '=' | ':' | '"' | '\'')
+    };
+
+    // Перебираем возможные позиции первого символа и берем лучшее жадное совпадение
+    let mut best: Option<(f64, Vec<usize>)> = None;
+    for start in (0..lowered.len()).filter(|&i| lowered[i] == pattern[0]) {
+        let mut positions = vec![start];
+        let mut index = start + 1;
+        for &wanted in &pattern[1..] {
+            while index < lowered.len() && lowered[index] != wanted {
+                index += 1;
+            }
+            if index == lowered.len() {
+                break;
+            }
+            positions.push(index);
+            index += 1;
+        }
+        if positions.len() < pattern.len() {
+            break;
+        }
+
+        let mut score = 0.0;
+        for (n, &position) in positions.iter().enumerate() {
+            score += 1.0;
+            if is_boundary(position) {
+                score += 2.0;
+            }
+            if n > 0 {
+                let gap = position - positions[n - 1] - 1;
+                if gap == 0 {
+                    score += 3.0;
+                } else {
+                    score -= (gap as f64).min(10.0) * 0.2;
+                }
+            }
+        }
+        if start == 0 {
+            score += 3.0;
+        }
+        // Короткие команды с тем же совпадением выше длинных
+        score -= text.len() as f64 * 0.01;
+
+        match &best {
+            Some((best_score, _)) if *best_score >= score => {}
+            _ => best = Some((score, positions)),
+        }
+    }
+
+    best
+}
+
+fn query_history(conn: &Connection, query: &HistoryQuery) -> Result<Vec<HistoryEntry>, String> {
+    let since = query.since.as_deref().map(to_utc_timestamp).transpose()?;
+    let until = query.until.as_deref().map(to_utc_timestamp).transpose()?;
+    let status = query.status.map(|status| status == HistoryStatus::Success);
+
+    // Frecency: каждый запуск дает вес по давности (сутки, неделя, месяц, квартал, старше)
+    // Остальные колонки берутся из последнего запуска (строка с MAX(id))
+    let mut stmt = conn.prepare_cached(
+        "SELECT trim(command) AS text, COUNT(*),
+                SUM(CASE
+                    WHEN julianday('now') - julianday(recorded_at) < 1 THEN 100
+                    WHEN julianday('now') - julianday(recorded_at) < 7 THEN 70
+                    WHEN julianday('now') - julianday(recorded_at) < 30 THEN 50
+                    WHEN julianday('now') - julianday(recorded_at) < 90 THEN 30
+                    ELSE 10 END),
+                MAX(id), recorded_at, cwd, exit_code, terminal_tab_id
+         FROM terminal_commands
+         WHERE trim(command) != ''
+           AND (?1 IS NULL OR cwd = ?1 OR substr(cwd, 1, length(?1) + 1) IN (?1 || '/', ?1 || '\\'))
+           AND (?2 IS NULL OR (exit_code IS NOT NULL AND (exit_code = 0) = ?2))
+           AND (?3 IS NULL OR terminal_tab_id = ?3)
+           AND (?4 IS NULL OR recorded_at >= ?4)
+           AND (?5 IS NULL OR recorded_at <= ?5)
+         GROUP BY text
+         ORDER BY MAX(id) DESC
+         LIMIT ?6",
+    ).map_err(|e| format!("Ошибка подготовки запроса: {}", e))?;
+
+    let rows = stmt.query_map(
+        params![query.cwd, status, query.tab_id, since, until, HISTORY_CANDIDATES],
+        |row| {
+            Ok(HistoryEntry {
+                command: row.get(0)?,
+                count: row.get(1)?,
+                frecency: row.get(2)?,
+                last_used: row.get(4)?,
+                cwd: row.get(5)?,
+                exit_code: row.get(6)?,
+                tab_id: row.get(7)?,
+                score: 0.0,
+                positions: Vec::new(),
+            })
+        },
+    ).map_err(|e| format!("Ошибка выполнения запроса: {}", e))?;
+
+    let mut entries = Vec::new();
+    for row in rows {
+        let mut entry = row.map_err(|e| format!("Ошибка чтения строки: {}", e))?;
+        let (matched, positions) = match fuzzy_match(&query.query, &entry.command) {
+            Some(found) => found,
+            None => continue,
+        };
+        // Качество совпадения важнее, frecency упорядочивает близкие по качеству команды
+        entry.score = matched + entry.frecency.ln_1p();
+        entry.positions = positions;
+        entries.push(entry);
+    }
+
+    entries.sort_by(|a, b| b.score.total_cmp(&a.score));
+    entries.truncate(query.limit.max(1) as usize);
+    Ok(entries)
+}
+
+// Поиск по общей истории команд всех вкладок
+#[tauri::command]
+pub async fn query_command_history(db: State<'_, DbState>, query: HistoryQuery) -> Result<Vec<HistoryEntry>, String> {
+    db.run(move |conn| query_history(conn, &query)).await
+}
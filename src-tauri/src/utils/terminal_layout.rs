@@ -0,0 +1,250 @@
+// Модуль раскладок терминала
+// Вкладка хранит дерево разделений (горизонтальных и вертикальных) с панелями в листьях,
+// каждая панель описывает оболочку и рабочую директорию. Дерево сохраняется в SQLite
+// (таблица terminal_layouts), а restore_terminal_layout запускает все панели вкладки сразу.
+// Постоянные панели помнят ID своей сессии в демоне и при восстановлении подключаются к ней снова
+
+use serde::{Deserialize, Serialize};
+use tauri::async_runtime::spawn_blocking;
+use tauri::{AppHandle, State};
+
+use crate::utils::db::{self, DbState};
+use crate::utils::session_daemon::{self, SessionInfo};
+use crate::utils::terminal::{attach_persistent_session, start_process, PtyState};
+use crate::utils::terminal_encoding::TerminalCodec;
+
+// Максимальная глубина вложенности разделений
+const MAX_LAYOUT_DEPTH: usize = 16;
+
+// Направление разделения
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SplitDirection {
+    // Панели расположены слева направо
+    Horizontal,
+    // Панели расположены сверху вниз
+    Vertical,
+}
+
+// Настройки панели: профиль оболочки и рабочая директория
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaneConfig {
+    // ID панели на стороне клиента
+    pub pane_id: String,
+    // Оболочка (по умолчанию - оболочка системы)
+    #[serde(default)]
+    pub shell: Option<String>,
+    #[serde(default)]
+    pub cwd: Option<String>,
+    #[serde(default)]
+    pub encoding: Option<String>,
+    // Запуск через демон постоянных сессий
+    #[serde(default)]
+    pub persistent: bool,
+    // ID сессии демона, к которой подключена постоянная панель
+    #[serde(default)]
+    pub session_id: Option<u32>,
+}
+
+// Узел дерева раскладки
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum LayoutNode {
+    Split {
+        direction: SplitDirection,
+        // Доли дочерних узлов (пусто - поровну)
+        #[serde(default)]
+        sizes: Vec<f32>,
+        children: Vec<LayoutNode>,
+    },
+    Pane(PaneConfig),
+}
+
+impl LayoutNode {
+    // Проверка структуры дерева перед сохранением
+    pub fn validate(&self) -> Result<(), String> {
+        let mut pane_ids = std::collections::HashSet::new();
+        self.validate_node(0, &mut pane_ids)
+    }
+
+    fn validate_node<'a>(&'a self, depth: usize, pane_ids: &mut std::collections::HashSet<&'a str>) -> Result<(), String> {
+        if depth > MAX_LAYOUT_DEPTH {
+            return Err(format!("Слишком глубокая вложенность раскладки (больше {})", MAX_LAYOUT_DEPTH));
+        }
+
+        match self {
+            LayoutNode::Split { sizes, children, .. } => {
+                if children.len() < 2 {
+                    return Err("Разделение должно содержать не меньше двух панелей".to_string());
+                }
+                if !sizes.is_empty() && sizes.len() != children.len() {
+                    return Err("Количество размеров не совпадает с количеством панелей".to_string());
+                }
+                if sizes.iter().any(|size| !size.is_finite() || *size <= 0.0) {
+                    return Err("Размеры панелей должны быть положительными".to_string());
+                }
+                children.iter().try_for_each(|child| child.validate_node(depth + 1, pane_ids))
+            }
+            LayoutNode::Pane(pane) => {
+                if pane.pane_id.is_empty() {
+                    return Err("У панели не указан ID".to_string());
+                }
+                if !pane_ids.insert(&pane.pane_id) {
+                    return Err(format!("Повторяющийся ID панели: {}", pane.pane_id));
+                }
+                Ok(())
+            }
+        }
+    }
+
+    // Панели дерева в порядке обхода (слева направо, сверху вниз)
+    pub fn panes(&self) -> Vec<&PaneConfig> {
+        match self {
+            LayoutNode::Split { children, .. } => children.iter().flat_map(|child| child.panes()).collect(),
+            LayoutNode::Pane(pane) => vec![pane],
+        }
+    }
+
+    pub fn panes_mut(&mut self) -> Vec<&mut PaneConfig> {
+        match self {
+            LayoutNode::Split { children, .. } => children.iter_mut().flat_map(|child| child.panes_mut()).collect(),
+            LayoutNode::Pane(pane) => vec![pane],
+        }
+    }
+}
+
+// Результат запуска панели при восстановлении раскладки
+#[derive(Debug, Clone, Serialize)]
+pub struct RestoredPane {
+    pub pane_id: String,
+    pub terminal_id: Option<u32>,
+    pub session_id: Option<u32>,
+    pub error: Option<String>,
+}
+
+// Восстановленная раскладка: дерево и терминалы, запущенные для его панелей
+#[derive(Debug, Clone, Serialize)]
+pub struct RestoredLayout {
+    pub tab_id: i64,
+    pub root: LayoutNode,
+    pub panes: Vec<RestoredPane>,
+}
+
+#[tauri::command]
+pub async fn save_terminal_layout(db: State<'_, DbState>, tab_id: i64, root: LayoutNode) -> Result<(), String> {
+    root.validate()?;
+    let panes = root.panes().len();
+    db.run(move |conn| db::save_terminal_layout(conn, tab_id, &root)).await?;
+
+    println!("Сохранена раскладка вкладки {} ({} панелей)", tab_id, panes);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_terminal_layout(db: State<'_, DbState>, tab_id: i64) -> Result<Option<LayoutNode>, String> {
+    db.run(move |conn| db::get_terminal_layout(conn, tab_id)).await
+}
+
+#[tauri::command]
+pub async fn delete_terminal_layout(db: State<'_, DbState>, tab_id: i64) -> Result<(), String> {
+    db.run(move |conn| db::delete_terminal_layout(conn, tab_id)).await
+}
+
+// Запуск терминала панели: постоянная панель подключается к своей сессии, если демон ее еще держит,
+// иначе запускается новая оболочка. Возвращает ID терминала и ID сессии демона
+async fn restore_pane(
+    pty_state: &State<'_, PtyState>,
+    app: &AppHandle,
+    tab_id: i64,
+    pane: &PaneConfig,
+    sessions: &[SessionInfo],
+) -> Result<(u32, Option<u32>), String> {
+    let existing = pane
+        .session_id
+        .filter(|_| pane.persistent)
+        .and_then(|session_id| sessions.iter().find(|s| s.session_id == session_id));
+
+    if let Some(info) = existing {
+        // Сессия могла быть уже подключена через reconnect_persistent_sessions
+        if let Some(terminal_id) = pty_state.terminal_for_session(info.session_id).await {
+            return Ok((terminal_id, Some(info.session_id)));
+        }
+        let codec = TerminalCodec::new(pane.encoding.as_deref())?;
+        let terminal_id = attach_persistent_session(pty_state, app, info, codec).await?;
+        return Ok((terminal_id, Some(info.session_id)));
+    }
+
+    let terminal_id = start_process(
+        pty_state.clone(),
+        app.clone(),
+        pane.shell.clone(),
+        Some(tab_id),
+        pane.encoding.clone(),
+        pane.cwd.clone(),
+        Some(pane.persistent),
+    )
+    .await?;
+
+    Ok((terminal_id, pty_state.session_for_terminal(terminal_id).await))
+}
+
+// Запуск терминалов для всех панелей сохраненной раскладки
+// Ошибка запуска одной панели не прерывает восстановление остальных
+#[tauri::command]
+pub async fn restore_terminal_layout(
+    pty_state: State<'_, PtyState>,
+    db: State<'_, DbState>,
+    app: AppHandle,
+    tab_id: i64,
+) -> Result<RestoredLayout, String> {
+    let mut root = db
+        .run(move |conn| db::get_terminal_layout(conn, tab_id))
+        .await?
+        .ok_or_else(|| format!("Раскладка для вкладки {} не найдена", tab_id))?;
+
+    // Живые сессии демона нужны только раскладкам с постоянными панелями
+    let sessions = if root.panes().iter().any(|pane| pane.persistent && pane.session_id.is_some()) {
+        spawn_blocking(session_daemon::list_sessions)
+            .await
+            .map_err(|e| e.to_string())?
+            .unwrap_or_else(|e| {
+                eprintln!("Error listing persistent sessions for tab {}: {}", tab_id, e);
+                Vec::new()
+            })
+    } else {
+        Vec::new()
+    };
+
+    let mut panes = Vec::new();
+    let mut sessions_changed = false;
+    for pane in root.panes_mut() {
+        let result = restore_pane(&pty_state, &app, tab_id, pane, &sessions).await;
+
+        match &result {
+            Ok((_, session_id)) if pane.persistent && pane.session_id != *session_id => {
+                pane.session_id = *session_id;
+                sessions_changed = true;
+            }
+            Ok(_) => {}
+            Err(e) => eprintln!("Error restoring pane {} of tab {}: {}", pane.pane_id, tab_id, e),
+        }
+
+        panes.push(RestoredPane {
+            pane_id: pane.pane_id.clone(),
+            terminal_id: result.as_ref().ok().map(|(terminal_id, _)| *terminal_id),
+            session_id: result.as_ref().ok().and_then(|(_, session_id)| *session_id),
+            error: result.err(),
+        });
+    }
+
+    // Новые сессии запоминаются, чтобы следующее восстановление подключилось к ним, а не плодило оболочки
+    if sessions_changed {
+        let saved = root.clone();
+        if let Err(e) = db.run(move |conn| db::save_terminal_layout(conn, tab_id, &saved)).await {
+            eprintln!("Error saving session ids for layout of tab {}: {}", tab_id, e);
+        }
+    }
+
+    println!("Восстановлена раскладка вкладки {}: {} панелей", tab_id, panes.len());
+    Ok(RestoredLayout { tab_id, root, panes })
+}
@@ -0,0 +1,291 @@
+// Модуль поиска по выводу терминалов
+// Ищет одновременно в буфере прокрутки запущенных терминалов и в выводе команд из истории.
+// Для истории используется индекс FTS5 с триграммным токенизатором (terminal_commands_fts):
+// из запроса извлекаются обязательные подстроки, по ним отбираются кандидаты,
+// а точные позиции совпадений находятся регулярным выражением построчно
+
+use regex::{Regex, RegexBuilder};
+use regex_syntax::hir::{Hir, HirKind};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tauri::State;
+
+use crate::utils::db::{for_each_command_output, DbState};
+use crate::utils::shell_integration::strip_ansi;
+use crate::utils::terminal::PtyState;
+
+// Количество строк, хранимых в буфере прокрутки терминала
+const SCROLLBACK_LINES: usize = 10_000;
+
+// Максимальная длина незавершенной строки буфера
+const MAX_PARTIAL_LINE: usize = 16 * 1024;
+
+// Минимальная длина подстроки, которую может найти триграммный индекс
+const MIN_INDEXED_LITERAL: usize = 3;
+
+fn default_limit() -> usize {
+    500
+}
+
+fn default_true() -> bool {
+    true
+}
+
+// Буфер прокрутки терминала в виде текста без управляющих последовательностей
+pub struct Scrollback {
+    lines: VecDeque<String>,
+    // Номер первой строки буфера с момента запуска терминала
+    first_line: u64,
+    partial: String,
+}
+
+// Буфер прокрутки, общий для задачи чтения и команд поиска
+pub type SharedScrollback = Arc<Mutex<Scrollback>>;
+
+impl Scrollback {
+    pub fn shared() -> SharedScrollback {
+        Arc::new(Mutex::new(Scrollback {
+            lines: VecDeque::new(),
+            first_line: 0,
+            partial: String::new(),
+        }))
+    }
+
+    pub fn push(&mut self, chunk: &str) {
+        self.partial.push_str(&strip_ansi(chunk));
+
+        while let Some(pos) = self.partial.find('\n') {
+            let line: String = self.partial.drain(..=pos).collect();
+            self.lines.push_back(line.trim_end_matches(['\r', '\n']).to_string());
+        }
+
+        if self.partial.len() > MAX_PARTIAL_LINE {
+            let line = std::mem::take(&mut self.partial);
+            self.lines.push_back(line);
+        }
+
+        while self.lines.len() > SCROLLBACK_LINES {
+            self.lines.pop_front();
+            self.first_line += 1;
+        }
+    }
+
+    // Строки буфера с их номерами, включая незавершенную
+    fn numbered_lines(&self) -> impl Iterator<Item = (u64, &str)> {
+        self.lines
+            .iter()
+            .map(String::as_str)
+            .chain(std::iter::once(self.partial.as_str()).filter(|line| !line.is_empty()))
+            .enumerate()
+            .map(move |(index, line)| (self.first_line + index as u64, line))
+    }
+}
+
+// Параметры поиска
+#[derive(Debug, Clone, Deserialize)]
+pub struct SearchOptions {
+    pub query: String,
+    // Запрос - регулярное выражение (иначе - обычная подстрока)
+    #[serde(default)]
+    pub regex: bool,
+    #[serde(default)]
+    pub case_sensitive: bool,
+    // Искать в буфере прокрутки запущенных терминалов
+    #[serde(default = "default_true")]
+    pub scrollback: bool,
+    // Искать в выводе команд из истории
+    #[serde(default = "default_true")]
+    pub history: bool,
+    // Ограничение поиска одной вкладкой
+    #[serde(default)]
+    pub tab_id: Option<i64>,
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+}
+
+// Источник найденного совпадения
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchSource {
+    Scrollback,
+    History,
+}
+
+// Найденное совпадение
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchHit {
+    pub source: SearchSource,
+    // Терминал, в буфере которого найдено совпадение
+    pub terminal_id: Option<u32>,
+    // Команда из истории, в выводе которой найдено совпадение
+    pub command_id: Option<i64>,
+    pub tab_id: Option<i64>,
+    // Номер строки (для буфера - с момента запуска терминала, для истории - в выводе команды)
+    pub line: u64,
+    // Позиция совпадения в строке в символах
+    pub column: usize,
+    pub length: usize,
+    pub text: String,
+}
+
+// Ответ поиска
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchResult {
+    pub hits: Vec<SearchHit>,
+    // Поиск остановлен по достижении лимита
+    pub truncated: bool,
+    // История просматривалась с отбором по индексу FTS5
+    pub indexed: bool,
+}
+
+fn build_matcher(options: &SearchOptions) -> Result<Regex, String> {
+    if options.query.is_empty() {
+        return Err("Пустой поисковый запрос".to_string());
+    }
+
+    let pattern = if options.regex {
+        options.query.clone()
+    } else {
+        regex::escape(&options.query)
+    };
+
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!options.case_sensitive)
+        .build()
+        .map_err(|e| format!("Некорректное регулярное выражение: {}", e))
+}
+
+// Подстроки, которые обязательно входят в любое совпадение регулярного выражения
+fn required_literals(hir: &Hir, literals: &mut Vec<String>) {
+    match hir.kind() {
+        HirKind::Literal(literal) => literals.push(String::from_utf8_lossy(&literal.0).to_string()),
+        HirKind::Concat(parts) => parts.iter().for_each(|part| required_literals(part, literals)),
+        HirKind::Capture(capture) => required_literals(&capture.sub, literals),
+        HirKind::Repetition(repetition) if repetition.min > 0 => required_literals(&repetition.sub, literals),
+        _ => {}
+    }
+}
+
+// Запрос FTS5 для отбора кандидатов (None - индекс не поможет, нужен полный просмотр)
+fn fts_query(options: &SearchOptions) -> Option<String> {
+    let literals = if options.regex {
+        // Индекс регистронезависимый, поэтому подстроки берем без учета флага регистра
+        let hir = regex_syntax::Parser::new().parse(&options.query).ok()?;
+        let mut literals = Vec::new();
+        required_literals(&hir, &mut literals);
+        literals
+    } else {
+        vec![options.query.clone()]
+    };
+
+    let phrases: Vec<String> = literals
+        .iter()
+        .filter(|literal| literal.chars().count() >= MIN_INDEXED_LITERAL)
+        .map(|literal| format!("\"{}\"", literal.replace('"', "\"\"")))
+        .collect();
+
+    if phrases.is_empty() {
+        None
+    } else {
+        Some(phrases.join(" AND "))
+    }
+}
+
+// Поиск совпадений в строке; false - лимит исчерпан
+fn collect_line_hits(
+    matcher: &Regex,
+    line_text: &str,
+    limit: usize,
+    hits: &mut Vec<SearchHit>,
+    make_hit: impl Fn(usize, usize) -> SearchHit,
+) -> bool {
+    for found in matcher.find_iter(line_text) {
+        if hits.len() >= limit {
+            return false;
+        }
+        let column = line_text[..found.start()].chars().count();
+        let length = found.as_str().chars().count();
+        hits.push(make_hit(column, length));
+    }
+    true
+}
+
+#[tauri::command]
+pub async fn search_terminal_output(
+    pty_state: State<'_, PtyState>,
+    db: State<'_, DbState>,
+    options: SearchOptions,
+) -> Result<SearchResult, String> {
+    let matcher = build_matcher(&options)?;
+    let limit = options.limit.max(1);
+    let mut hits = Vec::new();
+    let mut truncated = false;
+
+    if options.scrollback {
+        'terminals: for (terminal_id, tab_id, scrollback) in pty_state.scrollbacks().await {
+            if options.tab_id.is_some() && tab_id != options.tab_id {
+                continue;
+            }
+            let scrollback = scrollback.lock()
+                .map_err(|e| format!("Ошибка блокировки мьютекса: {}", e))?;
+
+            for (line, text) in scrollback.numbered_lines() {
+                let more = collect_line_hits(&matcher, text, limit, &mut hits, |column, length| SearchHit {
+                    source: SearchSource::Scrollback,
+                    terminal_id: Some(terminal_id),
+                    command_id: None,
+                    tab_id,
+                    line,
+                    column,
+                    length,
+                    text: text.to_string(),
+                });
+                if !more {
+                    truncated = true;
+                    break 'terminals;
+                }
+            }
+        }
+    }
+
+    let fts = fts_query(&options);
+    let indexed = fts.is_some();
+
+    if options.history && !truncated {
+        // Поиск по истории выполняется на соединении из пула, не блокируя async runtime
+        let tab_filter = options.tab_id;
+        let (history_hits, history_truncated) = db.run(move |conn| {
+            let mut truncated = false;
+            for_each_command_output(conn, fts.as_deref(), tab_filter, |command_id, tab_id, output| {
+                for (line, text) in output.lines().enumerate() {
+                    let more = collect_line_hits(&matcher, text, limit, &mut hits, |column, length| SearchHit {
+                        source: SearchSource::History,
+                        terminal_id: None,
+                        command_id: Some(command_id),
+                        tab_id: Some(tab_id),
+                        line: line as u64,
+                        column,
+                        length,
+                        text: text.to_string(),
+                    });
+                    if !more {
+                        truncated = true;
+                        return false;
+                    }
+                }
+                true
+            })?;
+            Ok((hits, truncated))
+        }).await?;
+
+        hits = history_hits;
+        truncated = history_truncated;
+    }
+
+    println!(
+        "Поиск {:?}: найдено {} совпадений (индекс: {}, обрезано: {})",
+        options.query, hits.len(), indexed, truncated
+    );
+    Ok(SearchResult { hits, truncated, indexed })
+}
@@ -0,0 +1,217 @@
+// Модуль статистики терминалов
+// Собирает дерево процессов, запущенных из оболочки терминала (оболочка и все ее потомки),
+// с суммарной загрузкой CPU, памятью, временем работы и открытыми портами из кэша модуля портов.
+// Пока вкладка терминала активна, статистика всех терминалов периодически отправляется событием pty-stats
+
+use lazy_static::lazy_static;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use sysinfo::{Pid, ProcessRefreshKind, System};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::ports::core::get_ports_internal;
+use crate::ports::types::{Port, PortsCache, ProcessInfoCache};
+use crate::utils::terminal::PtyState;
+
+// Интервал отправки события pty-stats
+const STATS_INTERVAL: Duration = Duration::from_secs(2);
+
+lazy_static! {
+    static ref STATS_ACTIVE: AtomicBool = AtomicBool::new(false);
+    // Загрузка CPU считается между двумя обновлениями, поэтому System живет между вызовами
+    static ref STATS_SYSTEM: Mutex<Option<System>> = Mutex::new(None);
+}
+
+// Процесс из дерева терминала
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessStats {
+    pub pid: u32,
+    pub parent_pid: Option<u32>,
+    pub name: String,
+    pub cpu_usage: f32,
+    // Память в байтах
+    pub memory: u64,
+    pub runtime_secs: u64,
+}
+
+// Статистика терминала
+#[derive(Debug, Clone, Serialize)]
+pub struct TerminalStats {
+    pub terminal_id: u32,
+    pub root_pid: u32,
+    // Оболочка первой, затем потомки в порядке обхода в ширину
+    pub processes: Vec<ProcessStats>,
+    pub cpu_usage: f32,
+    pub memory: u64,
+    // Время работы оболочки
+    pub runtime_secs: u64,
+    pub ports: Vec<Port>,
+}
+
+// Команда для включения/выключения отправки pty-stats (вкладка терминала активна)
+#[tauri::command]
+pub fn set_terminal_stats_active(active: bool) {
+    println!("[TerminalStats] Установка активности статистики терминалов: {}", active);
+    STATS_ACTIVE.store(active, Ordering::SeqCst);
+}
+
+fn is_stats_active() -> bool {
+    STATS_ACTIVE.load(Ordering::SeqCst)
+}
+
+// Обновление списка процессов; при первом вызове делается второй замер для загрузки CPU
+fn refresh_processes() -> Result<std::sync::MutexGuard<'static, Option<System>>, String> {
+    let mut guard = STATS_SYSTEM.lock()
+        .map_err(|e| format!("Ошибка блокировки мьютекса: {}", e))?;
+
+    let refresh_kind = ProcessRefreshKind::new().with_cpu().with_memory();
+    match guard.as_mut() {
+        Some(system) => system.refresh_processes_specifics(refresh_kind),
+        None => {
+            let mut system = System::new();
+            system.refresh_processes_specifics(refresh_kind);
+            std::thread::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL);
+            system.refresh_processes_specifics(refresh_kind);
+            *guard = Some(system);
+        }
+    }
+
+    Ok(guard)
+}
+
+// Открытые порты из кэша модуля портов (или свежий замер, если кэш еще пуст)
+fn current_ports(app: &AppHandle) -> Vec<Port> {
+    if let Some(cache) = app.try_state::<PortsCache>() {
+        if let Ok(ports) = cache.0.lock() {
+            if !ports.is_empty() {
+                return ports.clone();
+            }
+        }
+    }
+
+    let mut process_cache = ProcessInfoCache::new();
+    get_ports_internal(&mut process_cache, false).unwrap_or_default()
+}
+
+// Сбор статистики для набора терминалов (терминал -> PID оболочки)
+fn collect_stats(system: &System, terminals: &[(u32, u32)], ports: &[Port]) -> Vec<TerminalStats> {
+    // Дочерние процессы по PID родителя
+    let mut children: HashMap<Pid, Vec<Pid>> = HashMap::new();
+    for (pid, process) in system.processes() {
+        if let Some(parent) = process.parent() {
+            children.entry(parent).or_default().push(*pid);
+        }
+    }
+
+    terminals
+        .iter()
+        .filter_map(|&(terminal_id, root_pid)| {
+            let root = system.process(Pid::from_u32(root_pid))?;
+
+            let mut processes = Vec::new();
+            let mut visited = HashSet::new();
+            let mut queue = std::collections::VecDeque::from([Pid::from_u32(root_pid)]);
+            while let Some(pid) = queue.pop_front() {
+                if !visited.insert(pid) {
+                    continue;
+                }
+                if let Some(process) = system.process(pid) {
+                    processes.push(ProcessStats {
+                        pid: pid.as_u32(),
+                        parent_pid: process.parent().map(|p| p.as_u32()),
+                        name: process.name().to_string(),
+                        cpu_usage: process.cpu_usage(),
+                        memory: process.memory(),
+                        runtime_secs: process.run_time(),
+                    });
+                }
+                if let Some(descendants) = children.get(&pid) {
+                    queue.extend(descendants.iter().copied());
+                }
+            }
+
+            let pids: HashSet<String> = processes.iter().map(|p| p.pid.to_string()).collect();
+
+            Some(TerminalStats {
+                terminal_id,
+                root_pid,
+                cpu_usage: processes.iter().map(|p| p.cpu_usage).sum(),
+                memory: processes.iter().map(|p| p.memory).sum(),
+                runtime_secs: root.run_time(),
+                ports: ports.iter().filter(|port| pids.contains(&port.pid)).cloned().collect(),
+                processes,
+            })
+        })
+        .collect()
+}
+
+fn terminal_stats_blocking(app: &AppHandle, terminals: &[(u32, u32)]) -> Result<Vec<TerminalStats>, String> {
+    let ports = current_ports(app);
+    let guard = refresh_processes()?;
+    let system = guard.as_ref().ok_or("Список процессов недоступен")?;
+    Ok(collect_stats(system, terminals, &ports))
+}
+
+#[tauri::command]
+pub async fn get_terminal_stats(state: State<'_, PtyState>, app: AppHandle, terminal_id: u32) -> Result<TerminalStats, String> {
+    let root_pid = state
+        .process_ids()
+        .await
+        .into_iter()
+        .find(|(id, _)| *id == terminal_id)
+        .ok_or_else(|| format!("Терминал с ID {} не найден", terminal_id))?
+        .1
+        .ok_or_else(|| format!("У терминала {} нет локального процесса", terminal_id))?;
+
+    let stats = tauri::async_runtime::spawn_blocking(move || {
+        terminal_stats_blocking(&app, &[(terminal_id, root_pid)])
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    stats
+        .into_iter()
+        .next()
+        .ok_or_else(|| format!("Процесс {} терминала {} не найден", root_pid, terminal_id))
+}
+
+// Запуск фоновой задачи отправки pty-stats
+pub fn start_terminal_stats_task(app_handle: AppHandle) {
+    println!("[TerminalStats] Запуск фоновой задачи статистики терминалов");
+
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(STATS_INTERVAL).await;
+
+            if !is_stats_active() {
+                continue;
+            }
+
+            let terminals: Vec<(u32, u32)> = match app_handle.try_state::<PtyState>() {
+                Some(state) => state
+                    .process_ids()
+                    .await
+                    .into_iter()
+                    .filter_map(|(terminal_id, pid)| pid.map(|pid| (terminal_id, pid)))
+                    .collect(),
+                None => continue,
+            };
+
+            if terminals.is_empty() {
+                continue;
+            }
+
+            let app = app_handle.clone();
+            match tauri::async_runtime::spawn_blocking(move || terminal_stats_blocking(&app, &terminals)).await {
+                Ok(Ok(stats)) => {
+                    let _ = app_handle.emit("pty-stats", stats);
+                }
+                Ok(Err(e)) => eprintln!("[TerminalStats] Ошибка сбора статистики: {}", e),
+                Err(e) => eprintln!("[TerminalStats] Ошибка задачи статистики: {}", e),
+            }
+        }
+    });
+}
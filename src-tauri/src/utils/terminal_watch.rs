@@ -0,0 +1,180 @@
+// Модуль наблюдения за выводом терминала
+// Регулярные выражения проверяются построчно в задаче чтения терминала,
+// строка, разорванная между двумя чтениями, собирается перед проверкой
+// и только затем очищается от управляющих последовательностей
+
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::utils::shell_integration::strip_ansi;
+
+// Максимальная длина незавершенной строки, которую храним между чтениями
+const MAX_PARTIAL_LINE: usize = 16 * 1024;
+
+static NEXT_WATCH_ID: AtomicU32 = AtomicU32::new(1);
+
+// Режим срабатывания наблюдателя
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchMode {
+    // Срабатывает один раз и удаляется
+    #[default]
+    Once,
+    // Срабатывает на каждое совпадение
+    Persistent,
+}
+
+// Наблюдатель за выводом терминала
+#[derive(Debug, Clone)]
+pub struct OutputWatch {
+    pub id: u32,
+    pub pattern: String,
+    pub mode: WatchMode,
+    regex: Regex,
+}
+
+impl OutputWatch {
+    pub fn new(pattern: &str, mode: WatchMode, case_insensitive: bool) -> Result<Self, String> {
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(case_insensitive)
+            .build()
+            .map_err(|e| format!("Некорректное регулярное выражение: {}", e))?;
+
+        Ok(OutputWatch {
+            id: NEXT_WATCH_ID.fetch_add(1, Ordering::SeqCst),
+            pattern: pattern.to_string(),
+            mode,
+            regex,
+        })
+    }
+}
+
+// Информация о наблюдателе для клиента
+#[derive(Debug, Clone, Serialize)]
+pub struct WatchInfo {
+    pub id: u32,
+    pub pattern: String,
+    pub mode: WatchMode,
+}
+
+impl From<&OutputWatch> for WatchInfo {
+    fn from(watch: &OutputWatch) -> Self {
+        WatchInfo {
+            id: watch.id,
+            pattern: watch.pattern.clone(),
+            mode: watch.mode,
+        }
+    }
+}
+
+// Совпадение, отправляемое клиенту событием pty-match
+#[derive(Debug, Clone, Serialize)]
+pub struct WatchMatch {
+    pub terminal_id: u32,
+    pub watch_id: u32,
+    pub pattern: String,
+    pub line: String,
+}
+
+// Набор наблюдателей терминала, общий для команд и задачи чтения
+pub type WatchSet = Arc<Mutex<Vec<OutputWatch>>>;
+
+pub fn new_watch_set() -> WatchSet {
+    Arc::new(Mutex::new(Vec::new()))
+}
+
+// Построчная проверка вывода с переносом незавершенной строки между чтениями
+pub struct LineMatcher {
+    terminal_id: u32,
+    partial: String,
+    // Наблюдатели, уже сработавшие на текущей незавершенной строке
+    partial_matched: HashSet<u32>,
+}
+
+impl LineMatcher {
+    pub fn new(terminal_id: u32) -> Self {
+        LineMatcher {
+            terminal_id,
+            partial: String::new(),
+            partial_matched: HashSet::new(),
+        }
+    }
+
+    pub fn feed(&mut self, chunk: &str, watches: &WatchSet) -> Vec<WatchMatch> {
+        let mut watches = match watches.lock() {
+            Ok(watches) => watches,
+            Err(_) => return Vec::new(),
+        };
+
+        if watches.is_empty() {
+            self.partial.clear();
+            self.partial_matched.clear();
+            return Vec::new();
+        }
+
+        // Управляющие последовательности удаляются уже из собранной строки,
+        // иначе последовательность, разорванная между чтениями, попадет в текст
+        self.partial.push_str(chunk);
+
+        let mut matches = Vec::new();
+        while let Some(pos) = self.partial.find('\n') {
+            let raw: String = self.partial.drain(..=pos).collect();
+            let already_matched = std::mem::take(&mut self.partial_matched);
+            // Одиночный \r (строка прогресса) strip_ansi превращает в перевод строки
+            for line in strip_ansi(&raw).split('\n') {
+                self.check_line(line.trim_end(), &mut watches, &already_matched, &mut matches);
+            }
+        }
+
+        // Проверяем и незавершенную строку (например, приглашение без перевода строки)
+        if !self.partial.is_empty() {
+            let text = strip_ansi(&self.partial);
+            let already_matched = self.partial_matched.clone();
+            let before = matches.len();
+            for line in text.split('\n') {
+                self.check_line(line, &mut watches, &already_matched, &mut matches);
+            }
+            for found in &matches[before..] {
+                self.partial_matched.insert(found.watch_id);
+            }
+        }
+
+        if self.partial.len() > MAX_PARTIAL_LINE {
+            self.partial.clear();
+            self.partial_matched.clear();
+        }
+
+        matches
+    }
+
+    fn check_line(
+        &self,
+        line: &str,
+        watches: &mut Vec<OutputWatch>,
+        skip: &HashSet<u32>,
+        matches: &mut Vec<WatchMatch>,
+    ) {
+        if line.is_empty() {
+            return;
+        }
+
+        watches.retain(|watch| {
+            if skip.contains(&watch.id) || !watch.regex.is_match(line) {
+                return true;
+            }
+
+            matches.push(WatchMatch {
+                terminal_id: self.terminal_id,
+                watch_id: watch.id,
+                pattern: watch.pattern.clone(),
+                line: line.to_string(),
+            });
+
+            // Одноразовый наблюдатель удаляется после срабатывания
+            watch.mode == WatchMode::Persistent
+        });
+    }
+}
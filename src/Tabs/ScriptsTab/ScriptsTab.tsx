@@ -8,7 +8,7 @@ import { WebLinksAddon } from "xterm-addon-web-links";
 import { Unicode11Addon } from "xterm-addon-unicode11";
 import { listen } from "@tauri-apps/api/event";
 import "xterm/css/xterm.css";
-import { Play, Save, TerminalSquare, AlertTriangle, FileText } from 'lucide-react';
+import { Play, Square, Save, TerminalSquare, AlertTriangle, FileText } from 'lucide-react';
 
 // Типы для языков программирования
 type LanguageType = "powershell" | "shell" | "python";
@@ -23,6 +23,31 @@ interface ScriptItem {
   content?: string;
 }
 
+// Событие script-output: очередная порция вывода скрипта
+interface ScriptOutputEvent {
+  run_id: string;
+  stream: 'stdout' | 'stderr';
+  chunk: string;
+  timestamp: number;
+}
+
+// Событие script-exit: завершение скрипта
+interface ScriptExitEvent {
+  run_id: string;
+  reason: 'exited' | 'cancelled' | 'timed_out';
+  exit_code: number | null;
+  success: boolean;
+  duration_ms: number;
+  error: string | null;
+  history_id: number | null;
+  peak_memory_kb: number | null;
+  cpu_time_ms: number | null;
+}
+
+type ScriptRunEvent =
+  | { kind: 'output'; payload: ScriptOutputEvent }
+  | { kind: 'exit'; payload: ScriptExitEvent };
+
 // Интерфейс для ошибок
 interface ScriptError {
   lineNumber: number;
@@ -347,6 +372,7 @@ const ScriptsTab: React.FC = () => {
   const [terminalInput, setTerminalInput] = useState<string>("");
   const [terminalHistory, setTerminalHistory] = useState<{command: string; output: string}[]>([]);
   const [isRunning, setIsRunning] = useState(false);
+  const [runId, setRunId] = useState<string | null>(null);
   const [errors, setErrors] = useState<ScriptError[]>([]);
   const [editorInstance, setEditorInstance] = useState<any>(null);
   const [monacoInstance, setMonacoInstance] = useState<Monaco | null>(null);
@@ -364,6 +390,12 @@ const ScriptsTab: React.FC = () => {
   const terminalInitializedRef = useRef<boolean>(false);
   const commandBufferRef = useRef<string>('');
 
+  // Запуск скрипта: идентификатор известен после ответа start_script,
+  // а события, пришедшие раньше, копятся до этого момента
+  const runIdRef = useRef<string | null>(null);
+  const awaitingRunRef = useRef<boolean>(false);
+  const earlyRunEventsRef = useRef<ScriptRunEvent[]>([]);
+
   // Получаем шаблонный код для выбранного языка
   const getTemplateForLanguage = (lang: LanguageType): string => {
     switch (lang) {
@@ -1354,6 +1386,61 @@ const ScriptsTab: React.FC = () => {
     }
   }, [errors, editorInstance, monacoInstance]);
 
+  // Вывод и завершение запущенного скрипта
+  const applyRunEvent = useCallback((event: ScriptRunEvent) => {
+    if (event.kind === 'output') {
+      setConsoleOutput(prev => prev + event.payload.chunk);
+      return;
+    }
+
+    const exit = event.payload;
+    let summary = "";
+    if (exit.error) {
+      summary += `\n\nОшибка при выполнении скрипта: ${exit.error}`;
+    }
+    if (exit.reason === 'cancelled') {
+      summary += "\n\nСкрипт отменен";
+    } else if (exit.reason === 'timed_out') {
+      summary += "\n\nСкрипт завершен по таймауту";
+    }
+    summary += `\n\nКод возврата: ${exit.exit_code ?? -1}`;
+    if (exit.peak_memory_kb !== null && exit.cpu_time_ms !== null) {
+      summary += `\nПиковая память: ${(exit.peak_memory_kb / 1024).toFixed(1)} МБ, процессорное время: ${(exit.cpu_time_ms / 1000).toFixed(2)} с`;
+    }
+
+    setConsoleOutput(prev => prev + summary);
+    runIdRef.current = null;
+    setRunId(null);
+    setIsRunning(false);
+  }, []);
+
+  // Подписка на события запуска скриптов (события чужих запусков, например по расписанию, пропускаются)
+  useEffect(() => {
+    const handleRunEvent = (event: ScriptRunEvent) => {
+      if (runIdRef.current === null) {
+        if (awaitingRunRef.current) {
+          earlyRunEventsRef.current.push(event);
+        }
+        return;
+      }
+      if (event.payload.run_id === runIdRef.current) {
+        applyRunEvent(event);
+      }
+    };
+
+    const unlistenOutput = listen<ScriptOutputEvent>("script-output", (event) => {
+      handleRunEvent({ kind: 'output', payload: event.payload });
+    });
+    const unlistenExit = listen<ScriptExitEvent>("script-exit", (event) => {
+      handleRunEvent({ kind: 'exit', payload: event.payload });
+    });
+
+    return () => {
+      unlistenOutput.then(unlisten => unlisten());
+      unlistenExit.then(unlisten => unlisten());
+    };
+  }, [applyRunEvent]);
+
   // Обработчик запуска скрипта
   const handleRunScript = async () => {
     setIsRunning(true);
@@ -1376,22 +1463,40 @@ const ScriptsTab: React.FC = () => {
       setConsoleOutput(prev => prev + warningMessage);
     }
     
+    awaitingRunRef.current = true;
+    earlyRunEventsRef.current = [];
     try {
-      // Вызываем Rust функцию для запуска скрипта через invoke
-      const result = await invoke<string>("run_script", { 
+      // Запускаем скрипт; вывод приходит событиями script-output, завершение - script-exit
+      const id = await invoke<string>("start_script", { 
         script: scriptContent,
         language: language
       });
       
-      // Выводим результат в консоль
-      setConsoleOutput(prev => prev + "\n" + result);
+      runIdRef.current = id;
+      setRunId(id);
+      const earlyEvents = earlyRunEventsRef.current.filter(event => event.payload.run_id === id);
+      earlyRunEventsRef.current = [];
+      earlyEvents.forEach(applyRunEvent);
       
     } catch (error: unknown) {
       console.error("Ошибка при запуске скрипта:", error);
       const errorMessage = error instanceof Error ? error.message : String(error);
       setConsoleOutput(prev => prev + `\n\nОшибка при запуске скрипта: ${errorMessage}`);
-    } finally {
       setIsRunning(false);
+    } finally {
+      awaitingRunRef.current = false;
+    }
+  };
+
+  // Обработчик отмены скрипта (завершается все дерево процессов)
+  const handleCancelScript = async () => {
+    if (runId === null) return;
+
+    try {
+      await invoke("cancel_script", { runId });
+    } catch (error: unknown) {
+      const errorMessage = error instanceof Error ? error.message : String(error);
+      setConsoleOutput(prev => prev + `\n\nНе удалось отменить скрипт: ${errorMessage}`);
     }
   };
 
@@ -1920,7 +2025,7 @@ const ScriptsTab: React.FC = () => {
       
       // Закрываем процесс терминала
       if (terminalId !== null) {
-        invoke("close_process", { terminalId })
+        invoke("close_terminal_process", { terminalId })
           .catch(err => console.error("Failed to close terminal process:", err));
       }
       
@@ -2085,6 +2190,15 @@ const ScriptsTab: React.FC = () => {
               >
                 {isRunning ? "Выполняется..." : <><Play size={16} /> Запустить</>}
               </button>
+              {isRunning && (
+                <button 
+                  className="btn btn-secondary btn-cancel"
+                  onClick={handleCancelScript}
+                  disabled={runId === null}
+                >
+                  <Square size={16} /> Остановить
+                </button>
+              )}
               <button 
                 className="btn btn-secondary btn-save"
                 onClick={handleSaveScript}